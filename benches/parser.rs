@@ -2,6 +2,7 @@
 
 extern crate test;
 use std::fs::File;
+use std::process::Command;
 
 use adexplorersnapshot::parser::ADExplorerSnapshot;
 use memmap2::Mmap;
@@ -21,3 +22,22 @@ fn snapshot(b: &mut Bencher) {
         );
     });
 }
+
+/// Converts the snapshot into a tgz with the binary, covering parsing, the
+/// parallel output generation and the archive writer together.
+#[bench]
+fn convert(b: &mut Bencher) {
+    let output = std::env::temp_dir().join("adexplorersnapshot-bench.tar.gz");
+
+    b.iter(|| {
+        let status = Command::new(env!("CARGO_BIN_EXE_convertsnapshot"))
+            .args(["-q", "-o"])
+            .arg(&output)
+            .arg(SNAPSHOT_PATH)
+            .status()
+            .expect("Failed to run convertsnapshot");
+        assert!(status.success());
+    });
+
+    std::fs::remove_file(&output).ok();
+}
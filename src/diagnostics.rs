@@ -0,0 +1,130 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Logger that prints errors straight away but collects warnings by their log
+/// target, so a warning repeated for thousands of objects is reported once
+/// with a count.
+pub struct Diagnostics {
+    level: LevelFilter,
+    warnings: Mutex<HashMap<String, Category>>,
+}
+
+/// Warnings seen for one target: the first message, as an example, and how
+/// many there were.
+struct Category {
+    first_seen: usize,
+    message: String,
+    count: usize,
+}
+
+impl Diagnostics {
+    pub fn new(level: LevelFilter) -> Self {
+        Diagnostics {
+            level,
+            warnings: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// One line per warning category, in the order they were first seen.
+    pub fn summary(&self) -> Vec<String> {
+        let warnings = self.warnings.lock().unwrap();
+        let mut categories: Vec<(&String, &Category)> = warnings.iter().collect();
+        categories.sort_by_key(|(_, category)| category.first_seen);
+        categories
+            .into_iter()
+            .map(|(target, category)| match category.count {
+                1 => format!("warning: {}", category.message),
+                count => format!(
+                    "warning: {} (and {} more {} warnings)",
+                    category.message,
+                    count - 1,
+                    target
+                ),
+            })
+            .collect()
+    }
+}
+
+impl Log for Diagnostics {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        if record.level() == Level::Error {
+            eprintln!("error: {}", record.args());
+            return;
+        }
+
+        let mut warnings = self.warnings.lock().unwrap();
+        let first_seen = warnings.len();
+        warnings
+            .entry(record.target().to_string())
+            .or_insert_with(|| Category {
+                first_seen,
+                message: record.args().to_string(),
+                count: 0,
+            })
+            .count += 1;
+    }
+
+    fn flush(&self) {
+        for line in self.summary() {
+            eprintln!("{}", line);
+        }
+        self.warnings.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warn(diagnostics: &Diagnostics, target: &str, message: &str) {
+        diagnostics.log(
+            &Record::builder()
+                .level(Level::Warn)
+                .target(target)
+                .args(format_args!("{}", message))
+                .build(),
+        );
+    }
+
+    #[test]
+    fn test_repeated_warnings_aggregate() {
+        let diagnostics = Diagnostics::new(LevelFilter::Warn);
+        for i in 0..1000 {
+            warn(
+                &diagnostics,
+                "group_member",
+                &format!("Unresolved group member: CN=user{}", i),
+            );
+        }
+        warn(
+            &diagnostics,
+            "delegation",
+            "Invalid delegation target: HOST",
+        );
+
+        assert_eq!(
+            diagnostics.summary(),
+            vec![
+                "warning: Unresolved group member: CN=user0 (and 999 more group_member warnings)",
+                "warning: Invalid delegation target: HOST",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_quiet_drops_warnings() {
+        let diagnostics = Diagnostics::new(LevelFilter::Error);
+        warn(&diagnostics, "group_member", "Unresolved group member");
+
+        assert!(diagnostics.summary().is_empty());
+    }
+}
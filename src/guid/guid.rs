@@ -5,9 +5,10 @@ use nom::{
     sequence::tuple,
     IResult,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
-#[derive(Debug, PartialEq, Eq, Serialize, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Clone)]
 pub struct GUID {
     data1: u32,
     data2: u16,
@@ -25,6 +26,14 @@ impl GUID {
         parse_guid(input)
     }
 
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.data1.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&self.data2.to_le_bytes());
+        bytes.extend_from_slice(&self.data3.to_le_bytes());
+        bytes.extend_from_slice(&self.data4);
+        bytes
+    }
+
     pub fn to_string(&self) -> String {
         format!(
             "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
@@ -43,6 +52,44 @@ impl GUID {
     }
 }
 
+impl FromStr for GUID {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim_start_matches('{').trim_end_matches('}');
+        let parts: Vec<&str> = s.split('-').collect();
+        if parts.len() != 5
+            || [8, 4, 4, 4, 12]
+                .iter()
+                .zip(&parts)
+                .any(|(&len, part)| part.len() != len)
+            // Also keeps from_str_radix from accepting a sign, and the byte
+            // slicing below on char boundaries
+            || !parts
+                .iter()
+                .all(|part| part.bytes().all(|b| b.is_ascii_hexdigit()))
+        {
+            return Err("Invalid GUID format".to_string());
+        }
+
+        let data1 = u32::from_str_radix(parts[0], 16).map_err(|e| e.to_string())?;
+        let data2 = u16::from_str_radix(parts[1], 16).map_err(|e| e.to_string())?;
+        let data3 = u16::from_str_radix(parts[2], 16).map_err(|e| e.to_string())?;
+        let tail = format!("{}{}", parts[3], parts[4]);
+        let mut data4 = [0u8; 8];
+        for (i, byte) in data4.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&tail[i * 2..i * 2 + 2], 16).map_err(|e| e.to_string())?;
+        }
+
+        Ok(GUID {
+            data1,
+            data2,
+            data3,
+            data4,
+        })
+    }
+}
+
 fn parse_guid(input: &[u8]) -> IResult<&[u8], GUID> {
     let (input, (data1, data2, data3, data4)) = tuple((
         le_u32,
@@ -79,6 +126,26 @@ mod tests {
         assert_eq!(guid.to_string(), "9B026DA6-0D3C-465C-8BEE-5199D7165CBA");
     }
 
+    #[test]
+    fn test_guid_from_str() {
+        let bytes = [
+            166, 109, 2, 155, 60, 13, 92, 70, 139, 238, 81, 153, 215, 22, 92, 186,
+        ];
+        let guid: GUID = "9b026da6-0d3c-465c-8bee-5199d7165cba".parse().unwrap();
+        assert_eq!(guid, GUID::from_bytes(&bytes).unwrap());
+        assert_eq!(
+            "{9B026DA6-0D3C-465C-8BEE-5199D7165CBA}".parse::<GUID>(),
+            Ok(guid)
+        );
+        assert!("9b026da6-0d3c-465c".parse::<GUID>().is_err());
+        assert!("00000000-0000-0000-éé-000000000000"
+            .parse::<GUID>()
+            .is_err());
+        assert!("+0000000-0000-0000-0000-000000000000"
+            .parse::<GUID>()
+            .is_err());
+    }
+
     #[test]
     fn test_from_next_bytes() {
         let bytes = [
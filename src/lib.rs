@@ -2,5 +2,6 @@ mod guid;
 mod security_descriptor;
 mod sid;
 
+pub mod diagnostics;
 pub mod output;
 pub mod parser;
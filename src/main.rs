@@ -1,35 +1,176 @@
+use clap::builder::PossibleValuesParser;
 use clap::Parser;
 use flate2::write::GzEncoder;
 use flate2::Compression;
-use rand::Rng;
+use log::LevelFilter;
+use rayon::prelude::*;
+use std::any::Any;
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::BufWriter;
-use std::io::{Error, ErrorKind};
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::io::{Error, ErrorKind, Seek, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::sync_channel;
+use std::thread;
 use std::time::Instant;
 use tar::Builder;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
 
+use adexplorersnapshot::diagnostics::Diagnostics;
 use adexplorersnapshot::output::bloodhound::{
-    ComputersOutput, ContainersOutput, DomainsOutput, GPOsOutput, GroupsOutput, OUsOutput,
-    UsersOutput,
+    unresolved_delegation_targets, ComputersStream, ContainersOutput, DomainsOutput, GPOsOutput,
+    GroupsOutput, OUsOutput, UsersStream, REQUIRED_ATTRIBUTES,
 };
-use adexplorersnapshot::parser::ADExplorerSnapshot;
+use adexplorersnapshot::parser::{ADExplorerSnapshot, Header, Snapshot, SnapshotReader};
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
     #[clap(help = "Input .dat file path")]
-    input: String,
+    #[cfg_attr(feature = "http", clap(required_unless_present = "input_url"))]
+    #[cfg_attr(not(feature = "http"), clap(required = true))]
+    input: Option<String>,
 
-    #[clap(short, long, help = "Output .tar.gz file path")]
+    #[cfg(feature = "http")]
+    #[clap(
+        long,
+        help = "Input snapshot URL (http://, https:// or file://)",
+        conflicts_with = "input"
+    )]
+    input_url: Option<String>,
+
+    #[clap(
+        short,
+        long,
+        help = "Output archive or directory path; - writes a tgz archive to stdout"
+    )]
     output: Option<String>,
 
-    #[clap(short, long, help = "Compression level (0-9, default 6)")]
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = OutputFormat::Tgz,
+        help = "Write a .tar.gz archive, a .zip archive or a directory of JSON files"
+    )]
+    format: OutputFormat,
+
+    #[clap(
+        long,
+        value_delimiter = ',',
+        value_parser = PossibleValuesParser::new(OUTPUT_NAMES),
+        conflicts_with = "exclude",
+        help = "Only generate these outputs"
+    )]
+    only: Vec<String>,
+
+    #[clap(
+        long,
+        value_delimiter = ',',
+        value_parser = PossibleValuesParser::new(OUTPUT_NAMES),
+        help = "Skip generating these outputs"
+    )]
+    exclude: Vec<String>,
+
+    #[clap(
+        short,
+        long,
+        value_parser = clap::value_parser!(u32).range(0..=9),
+        help = "Compression level (0-9, default 6); 0 stores the outputs uncompressed"
+    )]
     compression: Option<u32>,
 
+    #[clap(
+        long,
+        help = "Parsed snapshot cache path, loaded if present and written otherwise"
+    )]
+    cache: Option<PathBuf>,
+
+    #[clap(
+        long,
+        help = "Domain SID to use when the snapshot has no domain object"
+    )]
+    domain_sid: Option<String>,
+
+    #[clap(
+        long,
+        help = "Include each object's base64 nTSecurityDescriptor in the output"
+    )]
+    include_raw_sd: bool,
+
+    #[clap(
+        long,
+        help = "Name the source server and capture time in each output's meta block"
+    )]
+    snapshot_meta: bool,
+
+    #[clap(
+        long,
+        value_name = "IDENTIFIER",
+        help = "Print the attributes of one object (SID, GUID, DN or sAMAccountName) and exit"
+    )]
+    dump: Option<String>,
+
+    #[clap(
+        long,
+        help = "List the ADSTYPE of every attribute in the snapshot and exit"
+    )]
+    ads_types: bool,
+
+    #[clap(
+        long,
+        conflicts_with_all = ["cache", "dump"],
+        help = "Only keep the attributes the outputs use, to reduce memory on large snapshots. Every object is still loaded before the outputs are written"
+    )]
+    #[cfg_attr(feature = "http", clap(conflicts_with = "input_url"))]
+    low_memory: bool,
+
+    #[clap(
+        long,
+        help = "Skip objects that fail to parse instead of aborting, for corrupt snapshots"
+    )]
+    #[cfg_attr(feature = "http", clap(conflicts_with = "input_url"))]
+    lenient: bool,
+
     #[clap(short, long, help = "Verbose output")]
     verbose: bool,
+
+    #[clap(short, long, help = "Only report errors, not warnings")]
+    quiet: bool,
+}
+
+/// File stems of every output, in the order they are generated.
+const OUTPUT_NAMES: [&str; 7] = [
+    "domains",
+    "users",
+    "computers",
+    "groups",
+    "ous",
+    "containers",
+    "gpos",
+];
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    Tgz,
+    Zip,
+    Dir,
+}
+
+/// Set when the archive is written to stdout, so status messages go to
+/// stderr instead of corrupting it.
+static STATUS_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if STATUS_TO_STDERR.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
 }
 
 trait Output: Send {
@@ -47,62 +188,342 @@ fn main() -> std::io::Result<()> {
     let args = Args::parse();
 
     let verbose = args.verbose;
+    let to_stdout = args.output.as_deref() == Some("-");
+    if to_stdout {
+        if args.format != OutputFormat::Tgz {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Only the tgz format can be written to stdout",
+            ));
+        }
+        STATUS_TO_STDERR.store(true, Ordering::Relaxed);
+    }
+
+    let level = if args.quiet {
+        LevelFilter::Error
+    } else {
+        LevelFilter::Warn
+    };
+    let diagnostics: &'static Diagnostics = Box::leak(Box::new(Diagnostics::new(level)));
+    log::set_logger(diagnostics).map_err(Error::other)?;
+    log::set_max_level(level);
+
+    if args.ads_types {
+        return print_ads_types(&args);
+    }
 
     if verbose {
-        println!("Parsing");
+        status!("Parsing");
     }
     let parsing_start = Instant::now();
-    let snapshot = ADExplorerSnapshot::snapshot_from_file(&args.input)?;
+    let mut snapshot = load_snapshot(&args)?;
+    if let Some(domain_sid) = &args.domain_sid {
+        if snapshot.caches.domain_sid.is_none() {
+            snapshot.set_domain_sid(domain_sid)?;
+        } else {
+            log::warn!(target: "domain_sid", "Ignoring --domain-sid, the snapshot has a domain object");
+        }
+    }
+    snapshot.include_raw_sd = args.include_raw_sd;
+    snapshot.include_snapshot_meta = args.snapshot_meta;
     if verbose {
-        println!("Parsing took: {:?}", parsing_start.elapsed());
+        status!("Parsing took: {:?}", parsing_start.elapsed());
+    }
+
+    if let Some(identifier) = &args.dump {
+        let dump = snapshot.dump_object(identifier).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NotFound,
+                format!("No object found for {}", identifier),
+            )
+        })?;
+        println!("{}", serde_json::to_string_pretty(&dump)?);
+        log::logger().flush();
+        return Ok(());
     }
 
+    let compression_level = args.compression.unwrap_or(6);
     let output_path = args.output.map(PathBuf::from).unwrap_or_else(|| {
-        let random_name: String = rand::thread_rng()
-            .sample_iter(&rand::distributions::Alphanumeric)
-            .take(10)
-            .map(char::from)
-            .collect();
-        PathBuf::from(format!("{}.tar.gz", random_name))
+        PathBuf::from(default_output_name(
+            &snapshot.snapshot.header,
+            args.format,
+            compression_level,
+        ))
     });
 
-    let file = File::create(&output_path)?;
-    let buf_writer = BufWriter::with_capacity(8 * 1024 * 1024, file);
-    let compression_level = args.compression.unwrap_or(6);
-    let gzip_encoder = GzEncoder::new(buf_writer, Compression::new(compression_level));
-    let archive = Mutex::new(Builder::new(gzip_encoder));
+    let failed = match args.format {
+        // Level 0 would still run every byte through deflate, so write a
+        // plain tar instead
+        OutputFormat::Tgz if compression_level == 0 => {
+            let mut archive = Builder::new(create_output(&output_path)?);
 
-    process_outputs(&archive, &snapshot, verbose)?;
+            let failed =
+                process_outputs(&mut archive, &snapshot, &args.only, &args.exclude, verbose)?;
+
+            let write_start = Instant::now();
+            archive.into_inner()?.flush()?;
+            if verbose {
+                status!("Writing tar took: {:?}", write_start.elapsed());
+            }
+            failed
+        }
+        OutputFormat::Tgz => {
+            let gzip_encoder = GzEncoder::new(
+                create_output(&output_path)?,
+                Compression::new(compression_level),
+            );
+            let mut archive = Builder::new(gzip_encoder);
+
+            let failed =
+                process_outputs(&mut archive, &snapshot, &args.only, &args.exclude, verbose)?;
+
+            let write_start = Instant::now();
+            archive.into_inner()?.finish()?;
+            if verbose {
+                status!("Writing zip took: {:?}", write_start.elapsed());
+            }
+            failed
+        }
+        OutputFormat::Zip => {
+            let file = File::create(&output_path)?;
+            let buf_writer = BufWriter::with_capacity(8 * 1024 * 1024, file);
+            let options = match compression_level {
+                0 => SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+                level => SimpleFileOptions::default()
+                    .compression_method(CompressionMethod::Deflated)
+                    .compression_level(Some(level as i64)),
+            };
+            let mut archive = ZipSink {
+                writer: ZipWriter::new(buf_writer),
+                options,
+            };
+
+            let failed =
+                process_outputs(&mut archive, &snapshot, &args.only, &args.exclude, verbose)?;
+
+            let write_start = Instant::now();
+            archive.writer.finish()?.flush()?;
+            if verbose {
+                status!("Writing zip took: {:?}", write_start.elapsed());
+            }
+            failed
+        }
+        OutputFormat::Dir => {
+            std::fs::create_dir_all(&output_path)?;
+            process_outputs(
+                &mut DirectorySink(output_path.clone()),
+                &snapshot,
+                &args.only,
+                &args.exclude,
+                verbose,
+            )?
+        }
+    };
 
-    let write_start = Instant::now();
-    archive.into_inner().unwrap().into_inner()?.finish()?;
     if verbose {
-        println!("Writing zip took: {:?}", write_start.elapsed());
+        for spn in unresolved_delegation_targets(&snapshot) {
+            status!("Unresolved delegation target: {}", spn);
+        }
     }
 
-    println!("Output written to: {}", output_path.display());
-    println!("Total elapsed time: {:?}", start_time.elapsed());
+    log::logger().flush();
+    if to_stdout {
+        status!("Output written to: stdout");
+    } else {
+        status!("Output written to: {}", output_path.display());
+    }
+    status!("Total elapsed time: {:?}", start_time.elapsed());
+
+    if !failed.is_empty() {
+        return Err(Error::other(format!(
+            "Failed to generate {}",
+            failed.join(", ")
+        )));
+    }
 
     Ok(())
 }
 
+/// Names the output after the snapshot's server and capture time, so repeated
+/// runs over the same snapshot produce the same file.
+fn default_output_name(header: &Header, format: OutputFormat, compression_level: u32) -> String {
+    let server: String = header
+        .server
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let server = if server.is_empty() {
+        "snapshot".to_string()
+    } else {
+        server
+    };
+
+    // FILETIME counts 100ns intervals since 1601-01-01
+    let captured = (header.filetime / 10_000_000)
+        .checked_sub(11_644_473_600)
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs as i64, 0))
+        .map(|time| time.format("%Y%m%d%H%M%S").to_string())
+        .unwrap_or_else(|| "0".to_string());
+
+    match format {
+        OutputFormat::Tgz if compression_level == 0 => format!("{}_{}.tar", server, captured),
+        OutputFormat::Tgz => format!("{}_{}.tar.gz", server, captured),
+        OutputFormat::Zip => format!("{}_{}.zip", server, captured),
+        OutputFormat::Dir => format!("{}_{}", server, captured),
+    }
+}
+
+/// Lists attribute types from the header and properties alone, without
+/// parsing any objects.
+fn print_ads_types(args: &Args) -> std::io::Result<()> {
+    let input = args
+        .input
+        .as_ref()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "--ads-types needs an input file"))?;
+    let (_, mut properties) = Snapshot::metadata_only(input)?;
+    properties.sort_by(|a, b| (a.ads_type, &a.prop_name).cmp(&(b.ads_type, &b.prop_name)));
+
+    for property in properties {
+        println!("{}\t{}", property.ads_type, property.prop_name);
+    }
+    Ok(())
+}
+
+fn load_snapshot(args: &Args) -> std::io::Result<ADExplorerSnapshot> {
+    if let Some(cache) = &args.cache {
+        if cache.exists() {
+            return ADExplorerSnapshot::load_cache(cache);
+        }
+        let snapshot = parse_snapshot(args)?;
+        snapshot.save_cache(cache)?;
+        return Ok(snapshot);
+    }
+
+    parse_snapshot(args)
+}
+
+fn parse_snapshot(args: &Args) -> std::io::Result<ADExplorerSnapshot> {
+    #[cfg(feature = "http")]
+    if let Some(url) = &args.input_url {
+        return ADExplorerSnapshot::snapshot_from_url(url);
+    }
+
+    let input = args
+        .input
+        .as_ref()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "No input provided"))?;
+    let mut reader = SnapshotReader::from_file(input)?;
+    if args.low_memory {
+        reader = reader.retain_attributes(REQUIRED_ATTRIBUTES);
+    }
+    if args.lenient {
+        reader = reader.lenient();
+    }
+
+    let snapshot = if args.verbose {
+        reader.into_snapshot_with_progress(&mut print_progress)?
+    } else {
+        reader.into_snapshot()?
+    };
+    for error in &snapshot.errors {
+        log::warn!(
+            target: "parse",
+            "Skipped object {} at offset {:#x}: {}",
+            error.index,
+            error.offset,
+            error.error
+        );
+    }
+    Ok(ADExplorerSnapshot::from_snapshot(snapshot))
+}
+
+/// Buffered writer for the archive at `path`, or stdout for `-`.
+fn create_output(path: &Path) -> std::io::Result<Box<dyn Write + Send>> {
+    if path == Path::new("-") {
+        // Not locked, since the archive is written from another thread
+        return Ok(Box::new(BufWriter::with_capacity(
+            8 * 1024 * 1024,
+            std::io::stdout(),
+        )));
+    }
+    let file = File::create(path)?;
+    Ok(Box::new(BufWriter::with_capacity(8 * 1024 * 1024, file)))
+}
+
+/// Live object count for `--verbose`, rewritten in place on one line.
+fn print_progress(parsed: usize, total: usize) {
+    let end = if parsed == total { "\n" } else { "" };
+    if STATUS_TO_STDERR.load(Ordering::Relaxed) {
+        eprint!("\rParsed {}/{} objects{}", parsed, total, end);
+    } else {
+        print!("\rParsed {}/{} objects{}", parsed, total, end);
+        let _ = std::io::stdout().flush();
+    }
+}
+
+type OutputFn<'a> = Box<dyn Fn() -> Box<dyn Output + 'a> + Send + Sync + 'a>;
+
+/// Destination for the generated JSON files.
+trait OutputSink {
+    fn write_file(&mut self, name: &str, bytes: &[u8]) -> std::io::Result<()>;
+}
+
+impl<W: Write> OutputSink for Builder<W> {
+    fn write_file(&mut self, name: &str, bytes: &[u8]) -> std::io::Result<()> {
+        let mut header = tar::Header::new_ustar();
+        header.set_size(bytes.len() as u64);
+        header.set_cksum();
+
+        self.append_data(&mut header, name, bytes)
+    }
+}
+
+struct ZipSink<W: Write + Seek> {
+    writer: ZipWriter<W>,
+    options: SimpleFileOptions,
+}
+
+impl<W: Write + Seek> OutputSink for ZipSink<W> {
+    fn write_file(&mut self, name: &str, bytes: &[u8]) -> std::io::Result<()> {
+        self.writer.start_file(name, self.options)?;
+        self.writer.write_all(bytes)
+    }
+}
+
+/// Writes each output as a plain file in a directory.
+struct DirectorySink(PathBuf);
+
+impl OutputSink for DirectorySink {
+    fn write_file(&mut self, name: &str, bytes: &[u8]) -> std::io::Result<()> {
+        std::fs::write(self.0.join(name), bytes)
+    }
+}
+
 fn process_outputs(
-    archive: &Mutex<Builder<GzEncoder<BufWriter<File>>>>,
+    sink: &mut (dyn OutputSink + Send),
     snapshot: &ADExplorerSnapshot,
+    only: &[String],
+    exclude: &[String],
     verbose: bool,
-) -> std::io::Result<()> {
-    let output_types: Vec<(&str, Box<dyn Fn() -> Box<dyn Output>>)> = vec![
+) -> std::io::Result<Vec<&'static str>> {
+    let mut output_types: Vec<(&str, OutputFn)> = vec![
         (
             "domains.json",
             Box::new(|| Box::new(DomainsOutput::new(snapshot))),
         ),
         (
             "users.json",
-            Box::new(|| Box::new(UsersOutput::new(snapshot))),
+            Box::new(|| Box::new(UsersStream::new(snapshot))),
         ),
         (
             "computers.json",
-            Box::new(|| Box::new(ComputersOutput::new(snapshot))),
+            Box::new(|| Box::new(ComputersStream::new(snapshot))),
         ),
         (
             "groups.json",
@@ -118,44 +539,257 @@ fn process_outputs(
             Box::new(|| Box::new(GPOsOutput::new(snapshot))),
         ),
     ];
+    output_types.retain(|(filename, _)| {
+        let stem = filename.trim_end_matches(".json");
+        (only.is_empty() || only.iter().any(|name| name == stem))
+            && !exclude.iter().any(|name| name == stem)
+    });
 
-    for (filename, output_fn) in output_types {
-        if verbose {
-            println!("Generating {}", filename);
-        }
-        let start = Instant::now();
-        let output = output_fn();
-        if verbose {
-            println!("Generating {} took: {:?}", filename, start.elapsed());
-        }
+    write_outputs(sink, output_types, verbose)
+}
 
-        add_output(archive, filename, &*output, verbose)?;
+/// Generates every output in parallel and hands each to the sink as soon as
+/// its JSON is ready. The channel holds at most one finished buffer, so
+/// memory stays bounded while the sink is written from a single thread.
+///
+/// An output that fails or panics is logged and left out so the others still
+/// reach the sink; the names of those outputs are returned.
+fn write_outputs<'a>(
+    sink: &mut (dyn OutputSink + Send),
+    output_types: Vec<(&'a str, OutputFn)>,
+    verbose: bool,
+) -> std::io::Result<Vec<&'a str>> {
+    let mut filenames = HashSet::new();
+    for (filename, _) in &output_types {
+        if !filenames.insert(*filename) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Duplicate output filename: {}", filename),
+            ));
+        }
     }
 
-    Ok(())
+    let (sender, receiver) = sync_channel::<(&str, std::io::Result<Vec<u8>>)>(1);
+
+    // Generation stays on this thread, so it runs in the caller's rayon pool,
+    // while a writer thread adds the results to the sink as they finish
+    thread::scope(|scope| {
+        let writer = scope.spawn(move || {
+            let mut failed = Vec::new();
+            for (filename, json) in receiver {
+                match json {
+                    Ok(json) => add_output(sink, filename, &json, verbose)?,
+                    Err(e) => {
+                        log::error!("Failed to generate {}: {}", filename, e);
+                        failed.push(filename);
+                    }
+                }
+            }
+            Ok(failed)
+        });
+
+        output_types
+            .into_par_iter()
+            .for_each_with(sender, |sender, (filename, output_fn)| {
+                if verbose {
+                    status!("Generating {}", filename);
+                }
+                let start = Instant::now();
+                let json = panic::catch_unwind(AssertUnwindSafe(|| output_fn().to_json()))
+                    .map_err(|payload| Error::other(panic_message(&payload)))
+                    .and_then(|json| json.map_err(Error::other));
+                if verbose {
+                    status!("Generating {} took: {:?}", filename, start.elapsed());
+                }
+                // The receiver only hangs up after a write error, which the
+                // writer reports
+                let _ = sender.send((filename, json));
+            });
+
+        writer
+            .join()
+            .unwrap_or_else(|payload| Err(Error::other(panic_message(&payload))))
+    })
+}
+
+fn panic_message(payload: &Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panicked".to_string())
 }
 
 fn add_output(
-    archive: &Mutex<Builder<GzEncoder<BufWriter<File>>>>,
+    sink: &mut dyn OutputSink,
     filename: &str,
-    output: &dyn Output,
+    json: &[u8],
     verbose: bool,
 ) -> std::io::Result<()> {
     if verbose {
-        println!("Processing {}", filename);
+        status!("Processing {}", filename);
     }
     let start = Instant::now();
-    let mut header = tar::Header::new_ustar();
-    let json = output
-        .to_json()
-        .map_err(|e| Error::new(ErrorKind::Other, e))?;
-    header.set_size(json.len() as u64);
-    header.set_cksum();
-
-    let mut archive = archive.lock().unwrap();
-    archive.append_data(&mut header, filename, json.as_slice())?;
+    sink.write_file(filename, json)?;
     if verbose {
-        println!("Processing {} took: {:?}", filename, start.elapsed());
+        status!("Processing {} took: {:?}", filename, start.elapsed());
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_outputs_generate_concurrently() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Duration;
+
+        let active = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let generate = || {
+            let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(100));
+            active.fetch_sub(1, Ordering::SeqCst);
+            Box::new(Vec::<u8>::new()) as Box<dyn Output>
+        };
+        let output_types: Vec<(&str, OutputFn)> = vec![
+            ("users.json", Box::new(generate)),
+            ("computers.json", Box::new(generate)),
+        ];
+
+        // Two threads, whatever the machine running the tests has
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()
+            .unwrap();
+        let mut archive = Builder::new(Vec::new());
+        let failed = pool
+            .install(|| write_outputs(&mut archive, output_types, false))
+            .unwrap();
+        assert!(failed.is_empty());
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_duplicate_output_filename() {
+        let output_types: Vec<(&str, OutputFn)> = vec![
+            ("users.json", Box::new(|| Box::new(Vec::<u8>::new()))),
+            ("users.json", Box::new(|| Box::new(Vec::<u8>::new()))),
+        ];
+
+        let mut archive = Builder::new(Vec::new());
+        let err = write_outputs(&mut archive, output_types, false).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_failing_outputs_are_skipped() {
+        let output_types: Vec<(&str, OutputFn)> = vec![
+            ("users.json", Box::new(|| Box::new(vec![1u8, 2, 3]))),
+            (
+                "groups.json",
+                // Maps with non-string keys can't be serialized to JSON
+                Box::new(|| Box::new(std::collections::HashMap::from([(vec![1u8], 0u8)]))),
+            ),
+            ("gpos.json", Box::new(|| panic!("generator failed"))),
+            ("ous.json", Box::new(|| Box::new(Vec::<u8>::new()))),
+        ];
+
+        let mut archive = Builder::new(Vec::new());
+        let mut failed = write_outputs(&mut archive, output_types, false).unwrap();
+        failed.sort();
+        assert_eq!(failed, ["gpos.json", "groups.json"]);
+
+        let bytes = archive.into_inner().unwrap();
+        let mut names: Vec<String> = tar::Archive::new(bytes.as_slice())
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().display().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["ous.json", "users.json"]);
+    }
+
+    #[test]
+    fn test_compression_level_range() {
+        for level in ["0", "9"] {
+            let args = Args::try_parse_from(["convertsnapshot", "-c", level, "x.dat"]).unwrap();
+            assert_eq!(args.compression, Some(level.parse().unwrap()));
+        }
+        let error = Args::try_parse_from(["convertsnapshot", "-c", "10", "x.dat"]).unwrap_err();
+        assert_eq!(error.kind(), clap::error::ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn test_directory_sink() {
+        let dir = std::env::temp_dir().join("convertsnapshot-dir-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let output_types: Vec<(&str, OutputFn)> = vec![
+            ("users.json", Box::new(|| Box::new(vec![1u8, 2, 3]))),
+            ("groups.json", Box::new(|| Box::new(Vec::<u8>::new()))),
+        ];
+
+        let failed = write_outputs(&mut DirectorySink(dir.clone()), output_types, false).unwrap();
+        let users = std::fs::read_to_string(dir.join("users.json")).unwrap();
+        let groups = std::fs::read_to_string(dir.join("groups.json")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(failed.is_empty());
+        assert_eq!(users, "[1,2,3]");
+        assert_eq!(groups, "[]");
+    }
+
+    #[test]
+    fn test_zip_sink_entry_names() {
+        let output_types: Vec<(&str, OutputFn)> = vec![
+            ("users.json", Box::new(|| Box::new(vec![1u8, 2, 3]))),
+            ("groups.json", Box::new(|| Box::new(Vec::<u8>::new()))),
+        ];
+        let mut sink = ZipSink {
+            writer: ZipWriter::new(std::io::Cursor::new(Vec::new())),
+            options: SimpleFileOptions::default(),
+        };
+
+        let failed = write_outputs(&mut sink, output_types, false).unwrap();
+        assert!(failed.is_empty());
+        let bytes = sink.writer.finish().unwrap().into_inner();
+
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(names, ["groups.json", "users.json"]);
+
+        let mut users = String::new();
+        archive
+            .by_name("users.json")
+            .unwrap()
+            .read_to_string(&mut users)
+            .unwrap();
+        assert_eq!(users, "[1,2,3]");
+    }
+
+    #[test]
+    fn test_output_selection_arguments() {
+        let args =
+            Args::try_parse_from(["convertsnapshot", "--only", "users,groups", "x.dat"]).unwrap();
+        assert_eq!(args.only, ["users", "groups"]);
+
+        assert!(
+            Args::try_parse_from(["convertsnapshot", "--exclude", "sessions", "x.dat"]).is_err()
+        );
+        assert!(Args::try_parse_from([
+            "convertsnapshot",
+            "--only",
+            "users",
+            "--exclude",
+            "gpos",
+            "x.dat"
+        ])
+        .is_err());
+    }
+}
@@ -1,11 +1,109 @@
 use super::utils::Aces;
+use crate::guid::GUID;
 use crate::parser::{ADExplorerSnapshot, ObjectType};
 use crate::parser::{AttributeValue, Object};
-use crate::security_descriptor::ControlFlag;
+use crate::security_descriptor::{ControlFlag, SDDL};
+use crate::sid::SID;
+use base64::Engine;
+use std::collections::BTreeSet;
 
-pub fn get_sid(obj: &Object) -> String {
-    obj.get_object_identifier()
-        .unwrap_or("ERR_UNKNOWN".to_string())
+/// Returns the BloodHound object identifier of `obj`.
+pub fn get_sid(obj: &Object, snapshot: &ADExplorerSnapshot) -> String {
+    let sid = obj
+        .identifier_for(snapshot.get_type(obj))
+        .unwrap_or("ERR_UNKNOWN".to_string());
+
+    normalize_sid(sid, snapshot)
+}
+
+/// The identifier BloodHound expects for `sid`. Well-known SIDs are prefixed
+/// with the domain SID, as they are not unique across domains. Every SID that
+/// becomes an object identifier goes through here so one principal never ends
+/// up as two nodes.
+pub fn normalize_sid(sid: String, snapshot: &ADExplorerSnapshot) -> String {
+    match &snapshot.caches.domain_sid {
+        Some(domain_sid) if WELLKNOWN_SIDS.contains(&sid.as_str()) => {
+            format!("{}-{}", domain_sid.to_string(), sid)
+        }
+        _ => sid,
+    }
+}
+
+/// Objects with no identity and no recognised type, such as application
+/// partition entries, which would otherwise surface as `ERR_UNKNOWN` edges.
+pub fn is_noise_object(obj: &Object) -> bool {
+    obj.get_first("objectSid").is_none()
+        && obj.get_first("objectGUID").is_none()
+        && obj.get_type() == ObjectType::Unknown
+}
+
+/// Resolves `sid` to the identifier and node type of the principal it names,
+/// either an object in the snapshot or a well-known group.
+pub fn resolve_sid(sid: &SID, snapshot: &ADExplorerSnapshot) -> Option<(String, String)> {
+    if let Some(obj) = snapshot.get_sid(sid) {
+        return Some((get_sid(obj, snapshot), type_string(obj, snapshot)));
+    }
+
+    let sid = sid.to_string();
+    if snapshot.caches.domain_sid.is_some() && WELLKNOWN_SIDS.contains(&sid.as_str()) {
+        Some((normalize_sid(sid, snapshot), "Group".to_string()))
+    } else {
+        None
+    }
+}
+
+/// Unix time held by a FILETIME attribute such as lastLogon or pwdLastSet.
+/// As in SharpHound, -1 means unknown (absent or "never"), while a stored 0
+/// stays 0.
+pub fn get_timestamp(obj: &Object, attribute: &str) -> i64 {
+    obj.get_first(attribute)
+        .and_then(AttributeValue::as_unix_timestamp)
+        .unwrap_or(-1)
+}
+
+/// whenCreated is single-valued in AD; should a snapshot carry several values,
+/// the earliest real time is the creation time.
+pub fn get_when_created(obj: &Object) -> i64 {
+    obj.get("whenCreated")
+        .and_then(|values| {
+            values
+                .iter()
+                .filter_map(AttributeValue::as_unix_timestamp)
+                .filter(|&t| t > 0)
+                .min()
+        })
+        .unwrap_or(0)
+}
+
+/// Direct children of `obj` that BloodHound has node types for.
+pub fn get_child_nodes<'a>(obj: &Object, snapshot: &'a ADExplorerSnapshot) -> Vec<&'a Object> {
+    let parent_dn = match obj
+        .get_first("distinguishedName")
+        .and_then(AttributeValue::as_string)
+    {
+        Some(dn) => dn,
+        None => return Vec::new(),
+    };
+
+    snapshot
+        .caches
+        .dn_cache
+        .get_ou_children(parent_dn)
+        .into_iter()
+        .filter_map(|index| snapshot.snapshot.objects.get(index))
+        .filter(|child_obj| {
+            matches!(
+                snapshot.get_type(child_obj),
+                ObjectType::User
+                    | ObjectType::UserDisabled
+                    | ObjectType::Computer
+                    | ObjectType::Group
+                    | ObjectType::OU
+                    | ObjectType::Container
+                    | ObjectType::GPO
+            )
+        })
+        .collect()
 }
 
 pub fn is_acl_protected(obj: &Object) -> bool {
@@ -15,13 +113,129 @@ pub fn is_acl_protected(obj: &Object) -> bool {
         .unwrap_or(false)
 }
 
+/// Base64 of the raw nTSecurityDescriptor, when requested with `include_raw_sd`.
+pub fn get_raw_security_descriptor(obj: &Object, snapshot: &ADExplorerSnapshot) -> Option<String> {
+    if !snapshot.include_raw_sd {
+        return None;
+    }
+
+    match obj.get_first("nTSecurityDescriptor") {
+        Some(AttributeValue::NTSecurityDescriptor(bytes)) => {
+            Some(base64::engine::general_purpose::STANDARD.encode(bytes))
+        }
+        _ => None,
+    }
+}
+
+/// Name of the primary group, if its SID resolves within the snapshot.
+pub fn get_primary_group_name(
+    primary_group_sid: &str,
+    snapshot: &ADExplorerSnapshot,
+) -> Option<String> {
+    let sid = primary_group_sid.parse::<SID>().ok()?;
+    snapshot
+        .get_sid(&sid)?
+        .get_first("name")
+        .and_then(AttributeValue::as_string)
+        .cloned()
+}
+
+/// adminCount is an integer in the schema, though some snapshots decode it as
+/// a boolean; any non-zero count marks the object.
+pub fn get_admin_count(obj: &Object) -> bool {
+    match obj.get_first("adminCount") {
+        Some(AttributeValue::Integer(count)) => *count != 0,
+        Some(AttributeValue::Boolean(flag)) => *flag,
+        _ => false,
+    }
+}
+
+/// Whether SDProp has stamped the AdminSDHolder ACL onto `obj`: it is marked
+/// with adminCount=1 and its DACL no longer inherits.
+pub fn is_adminsdholder_protected(obj: &Object) -> bool {
+    get_admin_count(obj)
+        && obj
+            .get_first("nTSecurityDescriptor")
+            .and_then(AttributeValue::as_nt_security_descriptor)
+            .map(|sd| sd.control_flags.is_set(ControlFlag::PD))
+            .unwrap_or(false)
+}
+
+/// Which LAPS implementations manage a computer's local administrator
+/// password. Both can be set while a domain migrates to Windows LAPS.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Laps {
+    pub legacy: bool,
+    pub windows: bool,
+}
+
+impl Laps {
+    pub fn any(&self) -> bool {
+        self.legacy || self.windows
+    }
+
+    /// schemaIDGUIDs of the password attributes readable on the computer.
+    pub fn password_guids(&self, snapshot: &ADExplorerSnapshot) -> Vec<GUID> {
+        let caches = &snapshot.caches;
+        let mut guids = Vec::new();
+        if self.legacy {
+            guids.extend(caches.legacy_laps_password_guids.iter().cloned());
+        }
+        if self.windows {
+            guids.extend(caches.windows_laps_password_guids.iter().cloned());
+        }
+        guids
+    }
+}
+
+pub fn get_laps(obj: &Object) -> Laps {
+    let has = |name: &str| {
+        obj.get(name)
+            .and_then(|values| values.first())
+            .is_some_and(|value| match value {
+                // An expiration time of 0 means no password has been set yet
+                AttributeValue::LargeInteger(time) => *time != 0,
+                _ => true,
+            })
+    };
+
+    Laps {
+        legacy: has("ms-Mcs-AdmPwdExpirationTime"),
+        windows: has("msLAPS-PasswordExpirationTime") || has("msLAPS-EncryptedPassword"),
+    }
+}
+
 pub fn get_aces(obj: &Object, snapshot: &ADExplorerSnapshot) -> Vec<Aces> {
-    let has_laps = obj.get("ms-Mcs-AdmPwdExpirationTime").is_some();
-    let object_type = obj.get_type();
-    obj.get_first("nTSecurityDescriptor")
-        .and_then(AttributeValue::as_nt_security_descriptor)
-        .map(|sd| Aces::from_security_descriptor(&sd, snapshot, &object_type, has_laps))
-        .unwrap_or_default()
+    let laps = get_laps(obj);
+    let object_type = snapshot.get_type(obj);
+    let Some(AttributeValue::NTSecurityDescriptor(bytes)) = obj.get_first("nTSecurityDescriptor")
+    else {
+        return Vec::new();
+    };
+
+    let schema = snapshot.schema();
+    let class_guids: Vec<GUID> = obj
+        .get("objectClass")
+        .into_iter()
+        .flat_map(|values| values.iter().filter_map(AttributeValue::as_str))
+        .filter_map(|class| schema.class_by_name(class))
+        .map(|class| class.schema_id_guid.clone())
+        .collect();
+
+    match SDDL::from_bytes(bytes) {
+        Ok(sd) => Aces::from_security_descriptor(&sd, snapshot, &object_type, &class_guids, laps),
+        Err(e) => {
+            log::warn!(
+                target: "security_descriptor",
+                "Skipping ACEs of {}: unparseable nTSecurityDescriptor ({:?})",
+                obj.get_first("distinguishedName")
+                    .and_then(AttributeValue::as_str)
+                    .unwrap_or("<unknown>"),
+                e.map(|e| e.code)
+            );
+            Vec::new()
+        }
+    }
 }
 
 pub fn ldap2domain(ldap: &str) -> String {
@@ -32,10 +246,56 @@ pub fn ldap2domain(ldap: &str) -> String {
         .join(".")
 }
 
-pub fn type_string(obj: &Object) -> String {
-    match obj.get_type() {
+/// Domain FQDN of `dn`, falling back to the domain of the server the snapshot
+/// was taken from when the DN has no DC= components.
+pub fn get_domain(dn: &str, snapshot: &ADExplorerSnapshot) -> String {
+    let domain = ldap2domain(dn);
+    if !domain.is_empty() {
+        return domain;
+    }
+
+    snapshot
+        .snapshot
+        .header
+        .server
+        .split_once('.')
+        .map(|(_, domain)| domain.to_string())
+        .unwrap_or_default()
+}
+
+/// msDS-AllowedToDelegateTo SPNs whose host doesn't resolve to an object in
+/// the snapshot, sorted and deduplicated.
+pub fn unresolved_delegation_targets(snapshot: &ADExplorerSnapshot) -> Vec<String> {
+    let targets: BTreeSet<&str> = snapshot
+        .snapshot
+        .objects
+        .iter()
+        .flat_map(|obj| {
+            obj.get("msDS-AllowedToDelegateTo")
+                .into_iter()
+                .flatten()
+                .filter_map(AttributeValue::as_str)
+                .filter(move |spn| snapshot.resolve_host(spn_host(spn), obj).is_none())
+        })
+        .collect();
+
+    targets.into_iter().map(str::to_string).collect()
+}
+
+/// Host part of an SPN such as `service/host:port/name`. Values without a
+/// service class are taken to be a bare host name.
+pub fn spn_host(spn: &str) -> &str {
+    let host = match spn.split_once('/') {
+        Some((_, rest)) => rest.split('/').next().unwrap_or(rest),
+        None => spn,
+    };
+    host.split(':').next().unwrap_or(host)
+}
+
+pub fn type_string(obj: &Object, snapshot: &ADExplorerSnapshot) -> String {
+    match snapshot.get_type(obj) {
         ObjectType::Computer => "Computer".to_string(),
-        ObjectType::Domain => "Domain".to_string(),
+        ObjectType::Domain | ObjectType::TrustedDomain => "Domain".to_string(),
         ObjectType::Group => "Group".to_string(),
         ObjectType::User => "User".to_string(),
         ObjectType::UserDisabled => "User".to_string(),
@@ -45,3 +305,238 @@ pub fn type_string(obj: &Object) -> String {
         ObjectType::Unknown => "Unknown".to_string(),
     }
 }
+
+const WELLKNOWN_SIDS: &[&str] = &[
+    "S-1-0",
+    "S-1-0-0",
+    "S-1-1",
+    "S-1-1-0",
+    "S-1-2",
+    "S-1-2-0",
+    "S-1-2-1",
+    "S-1-3",
+    "S-1-3-0",
+    "S-1-3-1",
+    "S-1-3-2",
+    "S-1-3-3",
+    "S-1-3-4",
+    "S-1-5-1",
+    "S-1-5-2",
+    "S-1-5-3",
+    "S-1-5-4",
+    "S-1-5-6",
+    "S-1-5-7",
+    "S-1-5-8",
+    "S-1-5-9",
+    "S-1-5-10",
+    "S-1-5-11",
+    "S-1-5-12",
+    "S-1-5-13",
+    "S-1-5-14",
+    "S-1-5-15",
+    "S-1-5-17",
+    "S-1-5-18",
+    "S-1-5-19",
+    "S-1-5-20",
+    "S-1-5-21-0-0-0-496",
+    "S-1-5-21-0-0-0-497",
+    "S-1-5-32-544",
+    "S-1-5-32-545",
+    "S-1-5-32-546",
+    "S-1-5-32-547",
+    "S-1-5-32-548",
+    "S-1-5-32-549",
+    "S-1-5-32-550",
+    "S-1-5-32-551",
+    "S-1-5-32-552",
+    "S-1-5-32-554",
+    "S-1-5-32-555",
+    "S-1-5-32-556",
+    "S-1-5-32-557",
+    "S-1-5-32-558",
+    "S-1-5-32-559",
+    "S-1-5-32-560",
+    "S-1-5-32-561",
+    "S-1-5-32-562",
+    "S-1-5-32-568",
+    "S-1-5-32-569",
+    "S-1-5-32-573",
+    "S-1-5-32-574",
+    "S-1-5-32-575",
+    "S-1-5-32-576",
+    "S-1-5-32-577",
+    "S-1-5-32-578",
+    "S-1-5-32-579",
+    "S-1-5-32-580",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_when_created_uses_earliest_value() {
+        let obj = Object::from_attributes(vec![(
+            "whenCreated",
+            vec![
+                AttributeValue::UTCTime(1_700_000_000),
+                AttributeValue::UTCTime(1_600_000_000),
+            ],
+        )]);
+
+        assert_eq!(get_when_created(&obj), 1_600_000_000);
+        assert_eq!(get_when_created(&Object::from_attributes(vec![])), 0);
+
+        // "Never" and unset values aren't creation times
+        let obj = Object::from_attributes(vec![(
+            "whenCreated",
+            vec![
+                AttributeValue::LargeInteger(i64::MAX),
+                AttributeValue::LargeInteger(0),
+                AttributeValue::UTCTime(1_700_000_000),
+            ],
+        )]);
+        assert_eq!(get_when_created(&obj), 1_700_000_000);
+    }
+
+    #[test]
+    fn test_timestamp_defaults() {
+        let obj = Object::from_attributes(vec![
+            ("lastLogon", vec![AttributeValue::LargeInteger(0)]),
+            ("pwdLastSet", vec![AttributeValue::LargeInteger(i64::MAX)]),
+            (
+                "lastLogonTimestamp",
+                vec![AttributeValue::LargeInteger(133_540_000_000_000_000)],
+            ),
+        ]);
+
+        assert_eq!(get_timestamp(&obj, "lastLogon"), 0);
+        assert_eq!(get_timestamp(&obj, "pwdLastSet"), -1);
+        assert_eq!(get_timestamp(&obj, "lastLogonTimestamp"), 1_709_526_400);
+        assert_eq!(get_timestamp(&obj, "accountExpires"), -1);
+    }
+
+    #[test]
+    fn test_admin_count_representations() {
+        let admin_count = |value| Object::from_attributes(vec![("adminCount", vec![value])]);
+
+        assert!(get_admin_count(&admin_count(AttributeValue::Integer(1))));
+        assert!(get_admin_count(&admin_count(AttributeValue::Integer(2))));
+        assert!(get_admin_count(&admin_count(AttributeValue::Boolean(true))));
+        assert!(!get_admin_count(&admin_count(AttributeValue::Integer(0))));
+        assert!(!get_admin_count(&admin_count(AttributeValue::Boolean(
+            false
+        ))));
+        assert!(!get_admin_count(&Object::from_attributes(vec![])));
+    }
+
+    #[test]
+    fn test_wellknown_sids_normalized_everywhere() {
+        use crate::output::bloodhound::test_utils::{
+            allow_descriptor, classes, group_object, sid, snapshot_with, string, with_attributes,
+            DOMAIN_DN, DOMAIN_SID,
+        };
+        use crate::output::bloodhound::GroupsOutput;
+
+        let administrators = Object::from_attributes(vec![
+            ("objectClass", classes(&["top", "group"])),
+            (
+                "distinguishedName",
+                vec![string(&format!(
+                    "CN=Administrators,CN=Builtin,{}",
+                    DOMAIN_DN
+                ))],
+            ),
+            ("name", vec![string("Administrators")]),
+            ("objectSid", vec![sid("S-1-5-32-544")]),
+        ]);
+        let staff = with_attributes(
+            group_object(
+                "Staff",
+                1100,
+                &[&format!("CN=Administrators,CN=Builtin,{}", DOMAIN_DN)],
+            ),
+            vec![(
+                "nTSecurityDescriptor",
+                vec![allow_descriptor(&["S-1-5-32-544"])],
+            )],
+        );
+        let snapshot = snapshot_with(vec![administrators, staff]);
+        let expected = format!("{}-S-1-5-32-544", DOMAIN_SID);
+
+        let objects = &snapshot.snapshot.objects;
+        assert_eq!(get_sid(&objects[1], &snapshot), expected);
+        assert_eq!(get_aces(&objects[2], &snapshot)[0].principal_sid, expected);
+        let groups = serde_json::to_value(GroupsOutput::new(&snapshot)).unwrap();
+        assert_eq!(
+            groups["data"][1]["Members"][0]["ObjectIdentifier"],
+            expected
+        );
+        assert_eq!(
+            normalize_sid(format!("{}-1100", DOMAIN_SID), &snapshot),
+            format!("{}-1100", DOMAIN_SID)
+        );
+    }
+
+    #[test]
+    fn test_laps_versions() {
+        let expiration = |name| (name, vec![AttributeValue::LargeInteger(133000000000000000)]);
+        let legacy = Object::from_attributes(vec![expiration("ms-Mcs-AdmPwdExpirationTime")]);
+        let windows = Object::from_attributes(vec![
+            expiration("msLAPS-PasswordExpirationTime"),
+            (
+                "msLAPS-EncryptedPassword",
+                vec![AttributeValue::OctetString(vec![1, 2, 3])],
+            ),
+        ]);
+        let unset = Object::from_attributes(vec![(
+            "ms-Mcs-AdmPwdExpirationTime",
+            vec![AttributeValue::LargeInteger(0)],
+        )]);
+
+        assert_eq!(
+            get_laps(&legacy),
+            Laps {
+                legacy: true,
+                windows: false
+            }
+        );
+        assert_eq!(
+            get_laps(&windows),
+            Laps {
+                legacy: false,
+                windows: true
+            }
+        );
+        assert!(!get_laps(&unset).any());
+
+        let mut snapshot = ADExplorerSnapshot::from_objects(Vec::new());
+        let legacy_guid: GUID = "0c8e7a12-5d0b-4f1e-9b3a-6e2d4c8f1a75".parse().unwrap();
+        let windows_guid: GUID = "7b1f6e2a-3c4d-4e5f-8a9b-0c1d2e3f4a5b".parse().unwrap();
+        snapshot.caches.legacy_laps_password_guids = vec![legacy_guid.clone()];
+        snapshot.caches.windows_laps_password_guids = vec![windows_guid.clone()];
+
+        assert_eq!(
+            get_laps(&legacy).password_guids(&snapshot),
+            vec![legacy_guid]
+        );
+        assert_eq!(
+            get_laps(&windows).password_guids(&snapshot),
+            vec![windows_guid]
+        );
+    }
+
+    #[test]
+    fn test_spn_host() {
+        assert_eq!(spn_host("HTTP/web.example.com"), "web.example.com");
+        assert_eq!(spn_host("web.example.com"), "web.example.com");
+        assert_eq!(
+            spn_host("MSSQLSvc/sql.example.com:1433/instance"),
+            "sql.example.com"
+        );
+        assert_eq!(
+            spn_host("ldap/dc01.example.com/example.com"),
+            "dc01.example.com"
+        );
+    }
+}
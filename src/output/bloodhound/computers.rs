@@ -1,8 +1,15 @@
-use super::common::{get_aces, get_sid, is_acl_protected, ldap2domain};
-use super::utils::{Aces, Meta};
+use super::common::{
+    get_aces, get_admin_count, get_domain, get_laps, get_primary_group_name,
+    get_raw_security_descriptor, get_sid, get_timestamp, get_when_created, is_acl_protected,
+    is_adminsdholder_protected, resolve_sid, spn_host,
+};
+use super::users::GMSA_CLASS;
+use super::utils::{serialize_streamed, Aces, Meta};
 use crate::output::bloodhound::common::type_string;
-use crate::parser::{ADExplorerSnapshot, AttributeValue, Object};
-use serde::{Deserialize, Serialize};
+use crate::parser::{ADExplorerSnapshot, AttributeValue, Object, ObjectType, SamAccountType};
+use crate::security_descriptor::ACE;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashSet;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ComputersOutput {
@@ -13,31 +20,72 @@ pub struct ComputersOutput {
 
 impl ComputersOutput {
     pub fn new(snapshot: &ADExplorerSnapshot) -> Self {
-        let computers: Vec<Computer> = snapshot
+        let computers: Vec<Computer> = Self::computers(snapshot)
+            .map(|obj| Computer::new(obj, snapshot))
+            .collect();
+
+        Self {
+            meta: Meta::new("computers", computers.len(), 5, snapshot),
+            computers,
+        }
+    }
+
+    /// Objects the computers output covers.
+    fn computers(snapshot: &ADExplorerSnapshot) -> impl Iterator<Item = &Object> {
+        snapshot
             .snapshot
             .objects
             .iter()
-            .filter(|obj| {
-                obj.get_first("sAMAccountType")
-                    .and_then(AttributeValue::as_integer)
-                    .map(|account_type| account_type == 805306369)
-                    .unwrap_or(false)
-            })
-            .map(|obj| Computer::new(obj, snapshot))
-            .collect();
+            .filter(move |obj| Self::is_computer(obj, snapshot))
+    }
+
+    /// Only machine accounts count. Interdomain trust accounts also end in `$`
+    /// but have their own account type.
+    fn is_computer(obj: &Object, snapshot: &ADExplorerSnapshot) -> bool {
+        if let Some(object_type) = snapshot.type_override(obj) {
+            return object_type == ObjectType::Computer;
+        }
+
+        // gMSAs are machine accounts too, but are emitted as users
+        obj.sam_account_type()
+            .is_some_and(SamAccountType::is_computer)
+            && !obj.has_attribute_class(GMSA_CLASS)
+    }
+}
+
+/// Serializes the same JSON as `ComputersOutput`, but builds each `Computer`
+/// as it is written instead of holding all of them at once. The snapshot's
+/// objects stay loaded, since the records are resolved against all of them.
+pub struct ComputersStream<'a> {
+    snapshot: &'a ADExplorerSnapshot,
+    meta: Meta,
+    computers: Vec<&'a Object>,
+}
+
+impl<'a> ComputersStream<'a> {
+    pub fn new(snapshot: &'a ADExplorerSnapshot) -> Self {
+        let computers: Vec<&Object> = ComputersOutput::computers(snapshot).collect();
 
         Self {
-            meta: Meta {
-                methods: 46067,
-                r#type: "computers".to_string(),
-                count: computers.len() as u64,
-                version: 5,
-            },
+            snapshot,
+            meta: Meta::new("computers", computers.len(), 5, snapshot),
             computers,
         }
     }
 }
 
+impl Serialize for ComputersStream<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let snapshot = self.snapshot;
+        let computers = self
+            .computers
+            .iter()
+            .map(|obj| Computer::new(obj, snapshot));
+
+        serialize_streamed(serializer, &self.meta, computers)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Computer {
     #[serde(rename = "Properties")]
@@ -52,6 +100,9 @@ pub struct Computer {
     #[serde(rename = "PrimaryGroupSID")]
     pub primary_group_sid: String,
 
+    #[serde(rename = "PrimaryGroupName", skip_serializing_if = "Option::is_none")]
+    pub primary_group_name: Option<String>,
+
     #[serde(rename = "HasSIDHistory")]
     pub has_sid_history: Vec<SIDHistoryItem>,
 
@@ -67,6 +118,10 @@ pub struct Computer {
     #[serde(rename = "LocalGroups")]
     pub local_groups: Vec<LocalGroup>,
 
+    /// Accounts whose secrets are cached on this RODC
+    #[serde(rename = "RevealedUsers", skip_serializing_if = "Vec::is_empty")]
+    pub revealed_users: Vec<RevealedUser>,
+
     #[serde(rename = "Aces")]
     aces: Vec<Aces>,
 
@@ -78,24 +133,35 @@ pub struct Computer {
 
     #[serde(rename = "IsACLProtected")]
     is_acl_protected: bool,
+
+    #[serde(
+        rename = "RawSecurityDescriptor",
+        skip_serializing_if = "Option::is_none"
+    )]
+    raw_security_descriptor: Option<String>,
 }
 
 impl Computer {
     pub fn new(obj: &Object, snapshot: &ADExplorerSnapshot) -> Self {
+        let primary_group_sid = get_primary_group_sid(obj, snapshot);
+
         Computer {
             properties: ComputerProperties::new(obj, snapshot),
             allowed_to_delegate: process_allowed_to_delegate(obj, snapshot),
-            allowed_to_act: process_allowed_to_act(obj),
-            primary_group_sid: get_primary_group_sid(obj, snapshot),
-            has_sid_history: process_sid_history(obj),
+            allowed_to_act: process_allowed_to_act(obj, snapshot),
+            primary_group_name: get_primary_group_name(&primary_group_sid, snapshot),
+            primary_group_sid,
+            has_sid_history: process_sid_history(obj, snapshot),
             sessions: SessionsInfo::default(),
             privileged_sessions: SessionsInfo::default(),
             registry_sessions: SessionsInfo::default(),
             local_groups: Vec::new(), // This would need to be populated if the data is available
+            revealed_users: process_revealed_users(obj, snapshot),
             aces: get_aces(obj, snapshot),
-            object_identifier: get_sid(obj),
+            object_identifier: get_sid(obj, snapshot),
             is_deleted: false, // Assuming this information is not available in the snapshot
             is_acl_protected: is_acl_protected(obj),
+            raw_security_descriptor: get_raw_security_descriptor(obj, snapshot),
         }
     }
 }
@@ -112,11 +178,17 @@ pub struct ComputerProperties {
     pub enabled: bool,
     pub unconstraineddelegation: bool,
     pub trustedtoauth: bool,
+    pub isdc: bool,
+    pub isrodc: bool,
     pub lastlogon: i64,
     pub lastlogontimestamp: i64,
     pub pwdlastset: i64,
     pub serviceprincipalnames: Vec<String>,
+    pub hasspn: bool,
     pub operatingsystem: Option<String>,
+    pub admincount: bool,
+    pub protected: bool,
+    /// Raw SIDs from sIDHistory, see `HasSIDHistory` for the resolved principals
     pub sidhistory: Vec<String>,
     pub samaccountname: Option<String>,
 }
@@ -129,7 +201,7 @@ impl ComputerProperties {
             .map(|v| v.clone())
             .unwrap_or_default();
 
-        let domain = ldap2domain(&distinguished_name).to_uppercase();
+        let domain = get_domain(&distinguished_name, snapshot).to_uppercase();
         let name = obj
             .get_first("name")
             .and_then(AttributeValue::as_string)
@@ -141,49 +213,52 @@ impl ComputerProperties {
             .and_then(AttributeValue::as_integer)
             .unwrap_or(0);
 
+        // SharpHound names computer nodes after their DNS host name
+        let name = obj
+            .get_first("dNSHostName")
+            .and_then(AttributeValue::as_string)
+            .map(|hostname| hostname.to_uppercase())
+            .unwrap_or_else(|| format!("{}$@{}", name.to_uppercase(), domain));
+
+        let serviceprincipalnames: Vec<String> = obj
+            .get("servicePrincipalName")
+            .map(|values| {
+                values
+                    .iter()
+                    .filter_map(AttributeValue::as_string)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // Every computer registers HOST/ and RestrictedKrbHost/ SPNs, so only
+        // count the ones beyond those
+        let hasspn = serviceprincipalnames.iter().any(|spn| {
+            let spn = spn.to_uppercase();
+            !spn.starts_with("HOST/") && !spn.starts_with("RESTRICTEDKRBHOST/")
+        });
+
         ComputerProperties {
             domain: domain.clone(),
-            name: format!("{}@{}", name.to_uppercase(), domain),
+            name,
             distinguishedname: distinguished_name,
             domainsid: snapshot.caches.domain_sid.as_ref().unwrap().to_string(),
-            haslaps: obj
-                .get_first("ms-mcs-admpwdexpirationtime")
-                .and_then(AttributeValue::as_integer)
-                .map(|v| v != 0)
-                .unwrap_or(false),
+            haslaps: get_laps(obj).any(),
             description: obj
                 .get_first("description")
                 .and_then(AttributeValue::as_string)
                 .map(|v| v.clone()),
-            whencreated: obj
-                .get_first("whenCreated")
-                .and_then(AttributeValue::as_unix_timestamp)
-                .unwrap_or(0),
+            whencreated: get_when_created(obj),
             enabled: uac & 2 == 0,
             unconstraineddelegation: uac & 0x00080000 == 0x00080000,
             trustedtoauth: uac & 0x01000000 == 0x01000000,
-            lastlogon: obj
-                .get_first("lastLogon")
-                .and_then(AttributeValue::as_unix_timestamp)
-                .unwrap_or(0),
-            lastlogontimestamp: obj
-                .get_first("lastLogonTimestamp")
-                .and_then(AttributeValue::as_unix_timestamp)
-                .unwrap_or(-1),
-            pwdlastset: obj
-                .get_first("pwdLastSet")
-                .and_then(AttributeValue::as_unix_timestamp)
-                .unwrap_or(0),
-            serviceprincipalnames: obj
-                .get("servicePrincipalName")
-                .map(|values| {
-                    values
-                        .iter()
-                        .filter_map(AttributeValue::as_string)
-                        .cloned()
-                        .collect()
-                })
-                .unwrap_or_default(),
+            isdc: uac & 0x00002000 == 0x00002000,
+            isrodc: uac & 0x04000000 == 0x04000000,
+            lastlogon: get_timestamp(obj, "lastLogon"),
+            lastlogontimestamp: get_timestamp(obj, "lastLogonTimestamp"),
+            pwdlastset: get_timestamp(obj, "pwdLastSet"),
+            serviceprincipalnames,
+            hasspn,
             operatingsystem: obj
                 .get_first("operatingSystem")
                 .and_then(AttributeValue::as_string)
@@ -193,6 +268,8 @@ impl ComputerProperties {
                         .map(|sp| format!("{} {}", os, sp))
                         .unwrap_or_else(|| os.to_string())
                 }),
+            admincount: get_admin_count(obj),
+            protected: is_adminsdholder_protected(obj),
             sidhistory: obj
                 .get("sIDHistory")
                 .map(|values| {
@@ -219,6 +296,14 @@ pub struct DelegationTarget {
     pub object_type: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevealedUser {
+    #[serde(rename = "DistinguishedName")]
+    pub distinguished_name: String,
+    #[serde(rename = "ObjectIdentifier", skip_serializing_if = "Option::is_none")]
+    pub object_identifier: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SIDHistoryItem {
     #[serde(rename = "ObjectIdentifier")]
@@ -272,13 +357,11 @@ fn process_allowed_to_delegate(
                 .iter()
                 .filter_map(AttributeValue::as_string)
                 .flat_map(|host| {
-                    let target = host.split('/').nth(1).unwrap_or(host);
-                    if let Some(target_obj) = snapshot.get_computer(target) {
+                    let target = spn_host(host);
+                    if let Some(target_obj) = snapshot.resolve_host(target, obj) {
                         vec![DelegationTarget {
-                            object_identifier: target_obj
-                                .get_object_identifier()
-                                .unwrap_or("ERR_UNKNOWN".to_string()),
-                            object_type: type_string(target_obj),
+                            object_identifier: get_sid(target_obj, snapshot),
+                            object_type: type_string(target_obj, snapshot),
                         }]
                     } else if target.contains('.') {
                         vec![DelegationTarget {
@@ -286,7 +369,6 @@ fn process_allowed_to_delegate(
                             object_type: "Computer".to_string(),
                         }]
                     } else {
-                        eprintln!("Invalid delegation target: {}", host);
                         vec![]
                     }
                 })
@@ -295,10 +377,57 @@ fn process_allowed_to_delegate(
         .unwrap_or_default()
 }
 
-fn process_allowed_to_act(_obj: &Object) -> Vec<DelegationTarget> {
-    // TODO: Property msDS-AllowedToActOnBehalfOfOtherIdentity?
+/// Principals granted resource-based constrained delegation to `obj` by the
+/// allow ACEs of msDS-AllowedToActOnBehalfOfOtherIdentity. SIDs that can't be
+/// resolved are kept and assumed to be computers, which RBCD usually names.
+fn process_allowed_to_act(obj: &Object, snapshot: &ADExplorerSnapshot) -> Vec<DelegationTarget> {
+    let Some(dacl) = obj
+        .get_first("msDS-AllowedToActOnBehalfOfOtherIdentity")
+        .and_then(AttributeValue::as_nt_security_descriptor)
+        .and_then(|sd| sd.dacl)
+    else {
+        return Vec::new();
+    };
+
+    dacl.aces
+        .iter()
+        .filter_map(|ace| match ace {
+            ACE::AccessAllowed(ace) => Some(&ace.sid),
+            _ => None,
+        })
+        .map(|sid| {
+            let (object_identifier, object_type) = resolve_sid(sid, snapshot)
+                .unwrap_or_else(|| (sid.to_string(), "Computer".to_string()));
+            DelegationTarget {
+                object_identifier,
+                object_type,
+            }
+        })
+        .collect()
+}
+
+/// Principals named by an RODC's msDS-RevealedUsers. The attribute holds one
+/// DN-with-binary value per replicated secret, so each DN is listed once.
+fn process_revealed_users(obj: &Object, snapshot: &ADExplorerSnapshot) -> Vec<RevealedUser> {
+    let uac = obj
+        .get_first("userAccountControl")
+        .and_then(AttributeValue::as_integer)
+        .unwrap_or(0);
+    if uac & 0x04000000 == 0 {
+        return Vec::new();
+    }
 
-    Vec::new()
+    let mut seen = HashSet::new();
+    obj.get("msDS-RevealedUsers")
+        .into_iter()
+        .flatten()
+        .filter_map(AttributeValue::as_dn_with_binary)
+        .filter(|(_, dn)| seen.insert(dn.to_uppercase()))
+        .map(|(_, dn)| RevealedUser {
+            distinguished_name: dn.to_string(),
+            object_identifier: snapshot.get_dn(dn).map(|user| get_sid(user, snapshot)),
+        })
+        .collect()
 }
 
 fn get_primary_group_sid(obj: &Object, snapshot: &ADExplorerSnapshot) -> String {
@@ -312,17 +441,291 @@ fn get_primary_group_sid(obj: &Object, snapshot: &ADExplorerSnapshot) -> String
     format!("{}-{}", domain_sid.to_string(), group_id)
 }
 
-fn process_sid_history(obj: &Object) -> Vec<SIDHistoryItem> {
+/// The `sidhistory` property entries resolved to the principals they name.
+/// SIDs from other domains can't be resolved, so they keep the raw SID and are
+/// assumed to be the same kind of principal as `obj`.
+fn process_sid_history(obj: &Object, snapshot: &ADExplorerSnapshot) -> Vec<SIDHistoryItem> {
     obj.get("sIDHistory")
         .map(|values| {
             values
                 .iter()
                 .filter_map(AttributeValue::as_sid)
-                .map(|sid| SIDHistoryItem {
-                    object_identifier: sid.to_string(),
-                    object_type: "Computer".to_string(),
+                .map(|sid| {
+                    let (object_identifier, object_type) = resolve_sid(&sid, snapshot)
+                        .unwrap_or_else(|| (sid.to_string(), "Computer".to_string()));
+                    SIDHistoryItem {
+                        object_identifier,
+                        object_type,
+                    }
                 })
                 .collect()
         })
         .unwrap_or_default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::bloodhound::common::unresolved_delegation_targets;
+    use crate::output::bloodhound::test_utils::{
+        admin_protected, allow_descriptor, computer_object, sid, snapshot_with, string,
+        user_object, with_attributes, DOMAIN_DN, DOMAIN_SID,
+    };
+
+    #[test]
+    fn test_computer_name_from_dns_host_name() {
+        let with_dns = with_attributes(
+            computer_object("WS01", 1500, 0x1000),
+            vec![("dNSHostName", vec![string("ws01.example.com")])],
+        );
+        let without_dns = computer_object("WS02", 1501, 0x1000);
+
+        let snapshot = snapshot_with(vec![with_dns, without_dns]);
+        let objects = &snapshot.snapshot.objects;
+
+        assert_eq!(
+            ComputerProperties::new(&objects[1], &snapshot).name,
+            "WS01.EXAMPLE.COM"
+        );
+        assert_eq!(
+            ComputerProperties::new(&objects[2], &snapshot).name,
+            "WS02$@EXAMPLE.COM"
+        );
+    }
+
+    #[test]
+    fn test_computer_hasspn_ignores_default_spns() {
+        let default_spns = vec![
+            string("HOST/WS01"),
+            string("HOST/ws01.example.com"),
+            string("RestrictedKrbHost/WS01"),
+        ];
+        let with_defaults = with_attributes(
+            computer_object("WS01", 1500, 0x1000),
+            vec![("servicePrincipalName", default_spns.clone())],
+        );
+        let mut extra_spns = default_spns;
+        extra_spns.push(string("MSSQLSvc/ws02.example.com:1433"));
+        let with_extra = with_attributes(
+            computer_object("WS02", 1501, 0x1000),
+            vec![("servicePrincipalName", extra_spns)],
+        );
+
+        let snapshot = snapshot_with(vec![with_defaults, with_extra]);
+        let objects = &snapshot.snapshot.objects;
+
+        assert!(!ComputerProperties::new(&objects[1], &snapshot).hasspn);
+        assert!(ComputerProperties::new(&objects[2], &snapshot).hasspn);
+    }
+
+    #[test]
+    fn test_computer_adminsdholder_protected() {
+        let snapshot = snapshot_with(vec![
+            admin_protected(computer_object("DC01", 1000, 0x2000)),
+            computer_object("WS01", 1500, 0x1000),
+        ]);
+        let objects = &snapshot.snapshot.objects;
+        let dc = ComputerProperties::new(&objects[1], &snapshot);
+        let workstation = ComputerProperties::new(&objects[2], &snapshot);
+
+        assert!(dc.admincount && dc.protected);
+        assert!(!workstation.admincount && !workstation.protected);
+    }
+
+    #[test]
+    fn test_trust_account_not_a_computer() {
+        let trust = with_attributes(
+            user_object("PARTNER$", 1600, 0x800),
+            vec![("sAMAccountType", vec![AttributeValue::Integer(805306370)])],
+        );
+        let snapshot = snapshot_with(vec![trust, computer_object("WS01", 1500, 0x1000)]);
+        let output = ComputersOutput::new(&snapshot);
+
+        assert_eq!(output.computers.len(), 1);
+        assert_eq!(output.computers[0].properties.name, "WS01$@EXAMPLE.COM");
+    }
+
+    #[test]
+    fn test_delegation_to_additional_dns_host_name() {
+        let web = with_attributes(
+            computer_object("WEB01", 1500, 0x1000),
+            vec![
+                ("dNSHostName", vec![string("web01.example.com")]),
+                (
+                    "msDS-AdditionalDnsHostName",
+                    vec![string("intranet.example.com"), string("INTRANET")],
+                ),
+            ],
+        );
+        let frontend = with_attributes(
+            computer_object("FE01", 1501, 0x1001000),
+            vec![(
+                "msDS-AllowedToDelegateTo",
+                vec![string("HTTP/intranet.example.com")],
+            )],
+        );
+
+        let snapshot = snapshot_with(vec![web, frontend]);
+        let targets = process_allowed_to_delegate(&snapshot.snapshot.objects[2], &snapshot);
+
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].object_identifier, format!("{}-1500", DOMAIN_SID));
+        assert_eq!(targets[0].object_type, "Computer");
+    }
+
+    #[test]
+    fn test_delegation_short_name_stays_in_domain() {
+        let child = with_attributes(
+            computer_object("WEB01", 1500, 0x1000),
+            vec![(
+                "distinguishedName",
+                vec![string("CN=WEB01,CN=Computers,DC=CHILD,DC=EXAMPLE,DC=COM")],
+            )],
+        );
+        let frontend = with_attributes(
+            computer_object("FE01", 1501, 0x1001000),
+            vec![(
+                "msDS-AllowedToDelegateTo",
+                vec![string("HTTP/web01.corp.local")],
+            )],
+        );
+
+        let snapshot = snapshot_with(vec![child, frontend]);
+        let targets = process_allowed_to_delegate(&snapshot.snapshot.objects[2], &snapshot);
+
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].object_identifier, "WEB01.CORP.LOCAL");
+    }
+
+    #[test]
+    fn test_delegation_ambiguous_short_name() {
+        let frontend = with_attributes(
+            computer_object("FE01", 1501, 0x1001000),
+            vec![(
+                "msDS-AllowedToDelegateTo",
+                vec![string("HTTP/web01.corp.local")],
+            )],
+        );
+
+        let snapshot = snapshot_with(vec![
+            computer_object("WEB01", 1500, 0x1000),
+            user_object("web01", 1502, 0x200),
+            frontend,
+        ]);
+        let targets = process_allowed_to_delegate(&snapshot.snapshot.objects[3], &snapshot);
+
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].object_identifier, "WEB01.CORP.LOCAL");
+    }
+
+    #[test]
+    fn test_unresolved_delegation_targets() {
+        let frontend = with_attributes(
+            computer_object("FE01", 1501, 0x1001000),
+            vec![(
+                "msDS-AllowedToDelegateTo",
+                vec![
+                    string("HTTP/FE01"),
+                    string("HTTP/gone.example.com"),
+                    string("cifs/NOSUCHHOST"),
+                ],
+            )],
+        );
+
+        let snapshot = snapshot_with(vec![frontend]);
+        let targets = process_allowed_to_delegate(&snapshot.snapshot.objects[1], &snapshot);
+
+        assert_eq!(targets.len(), 2);
+        assert_eq!(
+            unresolved_delegation_targets(&snapshot),
+            vec![
+                "HTTP/gone.example.com".to_string(),
+                "cifs/NOSUCHHOST".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_allowed_to_act() {
+        let attacker_sid = format!("{}-1600", DOMAIN_SID);
+        let target = with_attributes(
+            computer_object("FS01", 1601, 0x1000),
+            vec![(
+                "msDS-AllowedToActOnBehalfOfOtherIdentity",
+                vec![allow_descriptor(&[&attacker_sid])],
+            )],
+        );
+        let snapshot = snapshot_with(vec![computer_object("EVIL01", 1600, 0x1000), target]);
+
+        let principals = process_allowed_to_act(&snapshot.snapshot.objects[2], &snapshot);
+        assert_eq!(principals.len(), 1);
+        assert_eq!(principals[0].object_identifier, attacker_sid);
+        assert_eq!(principals[0].object_type, "Computer");
+        assert!(process_allowed_to_act(&snapshot.snapshot.objects[1], &snapshot).is_empty());
+    }
+
+    #[test]
+    fn test_read_only_domain_controller() {
+        let snapshot = snapshot_with(vec![
+            computer_object("DC01", 1000, 0x82000),
+            // WORKSTATION_TRUST_ACCOUNT | PARTIAL_SECRETS_ACCOUNT
+            computer_object("RODC01", 1700, 0x04001000),
+        ]);
+        assert_eq!(snapshot.caches.domain_controllers, vec![1]);
+        assert_eq!(snapshot.caches.read_only_domain_controllers, vec![2]);
+
+        let dc = ComputerProperties::new(&snapshot.snapshot.objects[1], &snapshot);
+        assert!(dc.isdc && !dc.isrodc);
+        let rodc = ComputerProperties::new(&snapshot.snapshot.objects[2], &snapshot);
+        assert!(!rodc.isdc && rodc.isrodc);
+    }
+
+    #[test]
+    fn test_rodc_revealed_users() {
+        let alice_dn = format!("CN=alice,CN=Users,{}", DOMAIN_DN);
+        let revealed = |dn: &str| AttributeValue::DNWithBinary {
+            count: 4,
+            binary: vec![0x90, 0x00, 0x09, 0x00],
+            dn: dn.to_string(),
+        };
+        let rodc = with_attributes(
+            computer_object("RODC01", 1700, 0x04001000),
+            vec![(
+                "msDS-RevealedUsers",
+                vec![
+                    revealed(&alice_dn),
+                    revealed(&alice_dn.to_lowercase()),
+                    revealed("CN=gone,CN=Users,DC=EXAMPLE,DC=COM"),
+                ],
+            )],
+        );
+        let snapshot = snapshot_with(vec![rodc, user_object("alice", 1104, 0x200)]);
+
+        let revealed_users = process_revealed_users(&snapshot.snapshot.objects[1], &snapshot);
+        assert_eq!(revealed_users.len(), 2);
+        assert_eq!(revealed_users[0].distinguished_name, alice_dn);
+        assert_eq!(
+            revealed_users[0].object_identifier,
+            Some(format!("{}-1104", DOMAIN_SID))
+        );
+        assert_eq!(revealed_users[1].object_identifier, None);
+    }
+
+    #[test]
+    fn test_sid_history_resolves_types() {
+        let old_user_sid = format!("{}-1100", DOMAIN_SID);
+        let foreign_sid = "S-1-5-21-7000-8000-9000-1500";
+        let migrated = with_attributes(
+            computer_object("WS01", 1500, 0x1000),
+            vec![("sIDHistory", vec![sid(&old_user_sid), sid(foreign_sid)])],
+        );
+        let snapshot = snapshot_with(vec![migrated, user_object("alice", 1100, 0x200)]);
+        let computer = Computer::new(&snapshot.snapshot.objects[1], &snapshot);
+
+        assert_eq!(computer.has_sid_history.len(), 2);
+        assert_eq!(computer.has_sid_history[0].object_identifier, old_user_sid);
+        assert_eq!(computer.has_sid_history[0].object_type, "User");
+        assert_eq!(computer.has_sid_history[1].object_identifier, foreign_sid);
+        assert_eq!(computer.has_sid_history[1].object_type, "Computer");
+    }
+}
@@ -0,0 +1,41 @@
+//! SharpHound collection method flags reported in each output's `meta.methods`.
+
+pub const GROUP: u64 = 1 << 0;
+pub const LOCAL_ADMIN: u64 = 1 << 1;
+pub const GPO_LOCAL_GROUP: u64 = 1 << 2;
+pub const SESSION: u64 = 1 << 3;
+pub const LOGGED_ON: u64 = 1 << 4;
+pub const TRUSTS: u64 = 1 << 5;
+pub const ACL: u64 = 1 << 6;
+pub const CONTAINER: u64 = 1 << 7;
+pub const RDP: u64 = 1 << 8;
+pub const OBJECT_PROPS: u64 = 1 << 9;
+pub const SESSION_LOOP: u64 = 1 << 10;
+pub const LOGGED_ON_LOOP: u64 = 1 << 11;
+pub const DCOM: u64 = 1 << 12;
+pub const SPN_TARGETS: u64 = 1 << 13;
+pub const PS_REMOTE: u64 = 1 << 14;
+pub const USER_RIGHTS: u64 = 1 << 15;
+
+/// The methods SharpHound's default collection reports.
+pub const DEFAULT_METHODS: u64 = GROUP
+    | LOCAL_ADMIN
+    | LOGGED_ON
+    | TRUSTS
+    | ACL
+    | CONTAINER
+    | RDP
+    | OBJECT_PROPS
+    | DCOM
+    | SPN_TARGETS
+    | USER_RIGHTS;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_methods() {
+        assert_eq!(DEFAULT_METHODS, 46067);
+    }
+}
@@ -1,4 +1,7 @@
-use super::common::{get_aces, is_acl_protected, ldap2domain};
+use super::common::{
+    get_aces, get_child_nodes, get_domain, get_raw_security_descriptor, get_sid, is_acl_protected,
+    type_string,
+};
 use super::utils::{Aces, Meta};
 use crate::parser::{ADExplorerSnapshot, AttributeValue, Object, ObjectType};
 use serde::{Deserialize, Serialize};
@@ -15,17 +18,12 @@ impl ContainersOutput {
             .snapshot
             .objects
             .iter()
-            .filter(|obj| obj.get_type() == ObjectType::Container)
+            .filter(|obj| snapshot.get_type(obj) == ObjectType::Container)
             .map(|obj| Container::new(obj, snapshot))
             .collect();
 
         Self {
-            meta: Meta {
-                methods: 46067,
-                r#type: "containers".to_string(),
-                count: containers.len() as u64,
-                version: 5,
-            },
+            meta: Meta::new("containers", containers.len(), 5, snapshot),
             containers,
         }
     }
@@ -50,14 +48,19 @@ pub struct Container {
 
     #[serde(rename = "IsACLProtected")]
     is_acl_protected: bool,
+
+    #[serde(
+        rename = "RawSecurityDescriptor",
+        skip_serializing_if = "Option::is_none"
+    )]
+    raw_security_descriptor: Option<String>,
 }
 
 impl Container {
     pub fn new(obj: &Object, snapshot: &ADExplorerSnapshot) -> Self {
         Container {
             properties: ContainerProperties::new(obj, snapshot),
-            // TODO: How do you get child objects of a container?
-            child_objects: Vec::new(),
+            child_objects: process_child_objects(obj, snapshot),
             aces: get_aces(obj, snapshot),
             object_identifier: obj
                 .get_first("objectGUID")
@@ -66,10 +69,23 @@ impl Container {
                 .unwrap_or_default(),
             is_deleted: false, // Assuming this information is not available in the snapshot
             is_acl_protected: is_acl_protected(obj),
+            raw_security_descriptor: get_raw_security_descriptor(obj, snapshot),
         }
     }
 }
 
+// Containers such as CN=System hold many objects BloodHound doesn't draw (RID
+// manager, DFS configuration, ...), so only keep the node types it models.
+fn process_child_objects(obj: &Object, snapshot: &ADExplorerSnapshot) -> Vec<ChildObject> {
+    get_child_nodes(obj, snapshot)
+        .into_iter()
+        .map(|child_obj| ChildObject {
+            object_identifier: get_sid(child_obj, snapshot),
+            object_type: type_string(child_obj, snapshot),
+        })
+        .collect()
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ContainerProperties {
     pub domain: String,
@@ -85,7 +101,7 @@ impl ContainerProperties {
             .and_then(AttributeValue::as_string)
             .map(|v| v.clone())
             .unwrap_or_default();
-        let domain = ldap2domain(&distinguished_name);
+        let domain = get_domain(&distinguished_name, snapshot);
         let name = obj
             .get_first("name")
             .and_then(AttributeValue::as_string)
@@ -108,3 +124,81 @@ pub struct ChildObject {
     #[serde(rename = "ObjectType")]
     pub object_type: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::bloodhound::test_utils::{
+        classes, sid, snapshot_with, string, user_object, with_attributes, DOMAIN_DN, DOMAIN_SID,
+    };
+
+    #[test]
+    fn test_system_container_children() {
+        let system = Object::from_attributes(vec![
+            ("objectClass", classes(&["top", "container"])),
+            (
+                "distinguishedName",
+                vec![string(&format!("CN=System,{}", DOMAIN_DN))],
+            ),
+            ("name", vec![string("System")]),
+        ]);
+        let rid_manager = Object::from_attributes(vec![
+            ("objectClass", classes(&["top", "rIDManager"])),
+            (
+                "distinguishedName",
+                vec![string(&format!("CN=RID Manager$,CN=System,{}", DOMAIN_DN))],
+            ),
+        ]);
+        let svc = with_attributes(
+            user_object("svc", 1100, 0x200),
+            vec![(
+                "distinguishedName",
+                vec![string(&format!("CN=svc,CN=System,{}", DOMAIN_DN))],
+            )],
+        );
+
+        let snapshot = snapshot_with(vec![system, rid_manager, svc]);
+        let container = Container::new(&snapshot.snapshot.objects[1], &snapshot);
+
+        assert_eq!(container.child_objects.len(), 1);
+        assert_eq!(
+            container.child_objects[0].object_identifier,
+            format!("{}-1100", DOMAIN_SID)
+        );
+        assert_eq!(container.child_objects[0].object_type, "User");
+    }
+
+    #[test]
+    fn test_builtin_child_object_is_domain_prefixed() {
+        let builtin = Object::from_attributes(vec![
+            ("objectClass", classes(&["top", "builtinDomain"])),
+            (
+                "distinguishedName",
+                vec![string(&format!("CN=Builtin,{}", DOMAIN_DN))],
+            ),
+            ("name", vec![string("Builtin")]),
+        ]);
+        let administrators = Object::from_attributes(vec![
+            ("objectClass", classes(&["top", "group"])),
+            (
+                "distinguishedName",
+                vec![string(&format!(
+                    "CN=Administrators,CN=Builtin,{}",
+                    DOMAIN_DN
+                ))],
+            ),
+            ("name", vec![string("Administrators")]),
+            ("objectSid", vec![sid("S-1-5-32-544")]),
+        ]);
+
+        let snapshot = snapshot_with(vec![builtin, administrators]);
+        let container = Container::new(&snapshot.snapshot.objects[1], &snapshot);
+
+        assert_eq!(container.child_objects.len(), 1);
+        assert_eq!(
+            container.child_objects[0].object_identifier,
+            format!("{}-S-1-5-32-544", DOMAIN_SID)
+        );
+        assert_eq!(container.child_objects[0].object_type, "Group");
+    }
+}
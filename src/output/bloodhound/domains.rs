@@ -2,8 +2,12 @@ use crate::parser::{ADExplorerSnapshot, AttributeValue, Object};
 use crate::security_descriptor::ControlFlag;
 use serde::{Deserialize, Serialize};
 
-use super::common::get_aces;
-use super::utils::Aces;
+use super::common::{
+    get_aces, get_child_nodes, get_raw_security_descriptor, get_sid, get_when_created, ldap2domain,
+    type_string,
+};
+use super::ous::{process_links, Link};
+use super::utils::{Aces, Meta};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DomainsOutput {
@@ -14,28 +18,18 @@ pub struct DomainsOutput {
 
 impl DomainsOutput {
     pub fn new(snapshot: &ADExplorerSnapshot) -> Self {
+        let domains: Vec<Domain> = snapshot
+            .get_domains()
+            .map(|domain| Domain::new(domain, snapshot))
+            .collect();
+
         Self {
-            meta: Meta {
-                methods: 46067,
-                r#type: "domains".to_string(),
-                count: 5,
-            },
-            domains: snapshot
-                .get_root_domain()
-                .map(|root| Domain::new(root, snapshot))
-                .into_iter()
-                .collect(),
+            meta: Meta::new("domains", domains.len(), 5, snapshot),
+            domains,
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Meta {
-    methods: u64,
-    r#type: String,
-    count: u64,
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Domain {
     #[serde(rename = "Properties")]
@@ -48,7 +42,7 @@ pub struct Domain {
     pub trusts: Vec<Trust>,
 
     #[serde(rename = "Links")]
-    links: Vec<Links>,
+    links: Vec<Link>,
 
     #[serde(rename = "Aces")]
     aces: Vec<Aces>,
@@ -61,6 +55,12 @@ pub struct Domain {
 
     #[serde(rename = "IsACLProtected")]
     is_acl_protected: bool,
+
+    #[serde(
+        rename = "RawSecurityDescriptor",
+        skip_serializing_if = "Option::is_none"
+    )]
+    raw_security_descriptor: Option<String>,
 }
 
 impl Domain {
@@ -82,26 +82,24 @@ impl Domain {
 
         Domain {
             properties: DomainProperties::new(obj, snapshot),
-            child_objects: Vec::new(),
-            trusts: process_trusts(snapshot),
-            links: Vec::new(),
+            child_objects: get_child_nodes(obj, snapshot)
+                .into_iter()
+                .map(|child_obj| ChildObject {
+                    object_identifier: get_sid(child_obj, snapshot),
+                    object_type: type_string(child_obj, snapshot),
+                })
+                .collect(),
+            trusts: process_trusts(obj, snapshot),
+            links: process_links(obj),
             aces: get_aces(obj, snapshot),
             object_identifier: guid.to_string(),
             is_deleted: false,
             is_acl_protected,
+            raw_security_descriptor: get_raw_security_descriptor(obj, snapshot),
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Links {
-    #[serde(rename = "IsEnforced")]
-    is_enforced: bool,
-
-    #[serde(rename = "GUID")]
-    guid: String,
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChildObject {
     #[serde(rename = "ObjectIdentifier")]
@@ -125,22 +123,20 @@ pub struct DomainProperties {
 
 impl DomainProperties {
     pub fn new(obj: &Object, _snapshot: &ADExplorerSnapshot) -> Self {
+        let distinguished_name = obj
+            .get_first("distinguishedName")
+            .and_then(AttributeValue::as_string)
+            .unwrap()
+            .to_string();
+
         DomainProperties {
             name: obj
                 .get_first("name")
                 .and_then(AttributeValue::as_string)
                 .unwrap()
                 .clone(),
-            domain: obj
-                .get_first("name")
-                .and_then(AttributeValue::as_string)
-                .unwrap()
-                .to_uppercase(),
-            distinguishedname: obj
-                .get_first("distinguishedName")
-                .and_then(AttributeValue::as_string)
-                .unwrap()
-                .to_string(),
+            domain: ldap2domain(&distinguished_name).to_uppercase(),
+            distinguishedname: distinguished_name,
             domainsid: Self::get_domain_sid(obj),
             description: obj
                 .get_first("description")
@@ -158,26 +154,18 @@ impl DomainProperties {
     }
 
     pub fn get_functional_level(obj: &Object) -> String {
-        obj.get_first("msDS-Behavior-Version")
-            .and_then(AttributeValue::as_integer)
-            .map(|level| match level {
-                0 => "2000 Mixed/Native",
-                1 => "2003 Interim",
-                2 => "2003",
-                3 => "2008",
-                4 => "2008 R2",
-                5 => "2012",
-                6 => "2012 R2",
-                7 => "2016",
-                _ => "Unknown",
-            })
-            .unwrap_or("Unknown")
-            .to_string()
+        obj.functional_level()
+            .unwrap_or_else(|| "Unknown".to_string())
     }
 
+    /// whenCreated like the other object types. creationTime is a FILETIME
+    /// that moves with domain replication, so it is only a fallback.
     pub fn get_when_created(obj: &Object) -> i64 {
+        if obj.get("whenCreated").is_some() {
+            return get_when_created(obj);
+        }
         obj.get_first("creationTime")
-            .and_then(AttributeValue::as_large_integer)
+            .and_then(AttributeValue::as_unix_timestamp)
             .unwrap_or(0)
     }
 }
@@ -203,11 +191,24 @@ pub struct Trust {
     trust_type: String,
 }
 
-pub fn process_trusts(snapshot: &ADExplorerSnapshot) -> Vec<Trust> {
+/// Trusts of `domain`. A trustedDomain object belongs to the domain its DN
+/// sits in, so each trust is listed under exactly one domain.
+pub fn process_trusts(domain: &Object, snapshot: &ADExplorerSnapshot) -> Vec<Trust> {
+    let domain_name = domain
+        .get_first("distinguishedName")
+        .and_then(AttributeValue::as_string)
+        .map(|dn| ldap2domain(dn))
+        .unwrap_or_default();
+
     snapshot
         .snapshot
         .objects
         .iter()
+        .filter(|obj| {
+            obj.get_first("distinguishedName")
+                .and_then(AttributeValue::as_string)
+                .is_some_and(|dn| ldap2domain(dn).eq_ignore_ascii_case(&domain_name))
+        })
         .filter_map(|obj| process_trust(obj))
         .collect()
 }
@@ -222,27 +223,32 @@ fn process_trust(obj: &Object) -> Option<Trust> {
         return None;
     }
 
+    let target_domain_name = obj
+        .get_first("name")
+        .and_then(AttributeValue::as_string)
+        .map(String::to_string)
+        .unwrap();
+    let trust_attributes = obj
+        .get_first("trustAttributes")
+        .and_then(AttributeValue::as_integer)
+        .unwrap_or_default();
+    let source_domain_name = obj
+        .get_first("distinguishedName")
+        .and_then(AttributeValue::as_string)
+        .map(|dn| ldap2domain(dn))
+        .unwrap_or_default();
+
     Some(Trust {
         target_domain_sid: obj
             .get_first("securityIdentifier")
             .and_then(AttributeValue::as_sid)
             .map(|s| s.to_string())
             .unwrap_or("Unknown".to_string()),
-        target_domain_name: obj
-            .get_first("name")
-            .and_then(AttributeValue::as_string)
-            .map(String::to_string)
-            .unwrap(),
         is_transitive: obj
             .get_first("trustTransitive")
             .and_then(AttributeValue::as_boolean)
             .unwrap_or_default(),
-        sid_filtering_enabled: (obj
-            .get_first("trustAttributes")
-            .and_then(AttributeValue::as_integer)
-            .unwrap_or_default()
-            & 0x00000040)
-            != 0,
+        sid_filtering_enabled: sid_filtering_enabled(trust_attributes),
         trust_direction: match obj
             .get_first("trustDirection")
             .and_then(AttributeValue::as_integer)
@@ -254,15 +260,353 @@ fn process_trust(obj: &Object) -> Option<Trust> {
             3 => "Bidirectional".to_string(),
             _ => "Unknown".to_string(),
         },
-        trust_type: match obj
-            .get_first("trustType")
-            .and_then(AttributeValue::as_integer)
-            .unwrap_or_default()
-        {
-            1 => "WINDOWS_NON_ACTIVE_DIRECTORY".to_string(),
-            2 => "WINDOWS_ACTIVE_DIRECTORY".to_string(),
-            3 => "MIT".to_string(),
-            _ => "Unknown".to_string(),
-        },
+        trust_type: trust_type(
+            obj.get_first("trustType")
+                .and_then(AttributeValue::as_integer),
+            trust_attributes,
+            &source_domain_name,
+            &target_domain_name,
+        ),
+        target_domain_name: target_domain_name.to_uppercase(),
     })
 }
+
+// TRUST_ATTRIBUTE_* flags from MS-ADTS 6.1.6.7.9
+const QUARANTINED_DOMAIN: u32 = 0x00000004;
+const FOREST_TRANSITIVE: u32 = 0x00000008;
+const WITHIN_FOREST: u32 = 0x00000020;
+const TREAT_AS_EXTERNAL: u32 = 0x00000040;
+
+/// Forest trusts filter SIDs unless SID history has been enabled on them,
+/// which sets TREAT_AS_EXTERNAL. Every other trust only filters when it is
+/// quarantined.
+fn sid_filtering_enabled(trust_attributes: u32) -> bool {
+    if trust_attributes & WITHIN_FOREST == 0 && trust_attributes & FOREST_TRANSITIVE != 0 {
+        trust_attributes & TREAT_AS_EXTERNAL == 0
+    } else {
+        trust_attributes & QUARANTINED_DOMAIN != 0
+    }
+}
+
+// TRUST_TYPE_* values from MS-ADTS 6.1.6.7.15
+const TRUST_TYPE_MIT: u32 = 0x00000003;
+const TRUST_TYPE_DCE: u32 = 0x00000004;
+
+/// Classifies a trust the way SharpHound does. Within a forest, a trust
+/// between a domain and its parent or child is ParentChild, anything else a
+/// CrossLink (shortcut) trust. Trusts with Kerberos realms and DCE cells
+/// aren't domain trusts, so trustType is checked before the attributes.
+fn trust_type(
+    trust_type: Option<u32>,
+    trust_attributes: u32,
+    source_domain: &str,
+    target_domain: &str,
+) -> String {
+    if trust_type == Some(TRUST_TYPE_MIT) {
+        "MIT".to_string()
+    } else if trust_type == Some(TRUST_TYPE_DCE) {
+        "Unknown".to_string()
+    } else if trust_attributes & WITHIN_FOREST != 0 {
+        let source = source_domain.to_lowercase();
+        let target = target_domain.to_lowercase();
+        if source.ends_with(&format!(".{}", target)) || target.ends_with(&format!(".{}", source)) {
+            "ParentChild".to_string()
+        } else {
+            "CrossLink".to_string()
+        }
+    } else if trust_attributes & FOREST_TRANSITIVE != 0 {
+        "Forest".to_string()
+    } else {
+        "External".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::bloodhound::common::resolve_sid;
+    use crate::output::bloodhound::test_utils::{
+        classes, domain_object, sid, snapshot_with, string, user_object, with_attributes,
+        DOMAIN_DN, DOMAIN_SID,
+    };
+    use crate::parser::ObjectType;
+
+    fn trusted_domain(name: &str, trust_attributes: u32) -> Object {
+        Object::from_attributes(vec![
+            ("objectClass", classes(&["top", "leaf", "trustedDomain"])),
+            (
+                "distinguishedName",
+                vec![string(&format!("CN={},CN=System,{}", name, DOMAIN_DN))],
+            ),
+            ("name", vec![string(name)]),
+            ("trustDirection", vec![AttributeValue::Integer(3)]),
+            (
+                "trustAttributes",
+                vec![AttributeValue::Integer(trust_attributes)],
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_domain_direct_children() {
+        let staff = Object::from_attributes(vec![
+            ("objectClass", classes(&["top", "organizationalUnit"])),
+            (
+                "distinguishedName",
+                vec![string(&format!("OU=Staff,{}", DOMAIN_DN))],
+            ),
+            (
+                "objectGUID",
+                vec![AttributeValue::OctetString(vec![0x11; 16])],
+            ),
+        ]);
+        let alice = with_attributes(
+            user_object("alice", 1100, 0x200),
+            vec![(
+                "distinguishedName",
+                vec![string(&format!("CN=alice,OU=Staff,{}", DOMAIN_DN))],
+            )],
+        );
+        let domain = with_attributes(
+            domain_object(),
+            vec![(
+                "objectGUID",
+                vec![AttributeValue::OctetString(vec![0x22; 16])],
+            )],
+        );
+        let snapshot = ADExplorerSnapshot::from_objects(vec![domain, staff, alice]);
+        let domain = Domain::new(&snapshot.snapshot.objects[0], &snapshot);
+
+        assert_eq!(domain.child_objects.len(), 1);
+        assert_eq!(domain.child_objects[0].object_type, "OU");
+        assert_eq!(
+            domain.child_objects[0].object_identifier,
+            "11111111-1111-1111-1111-111111111111"
+        );
+    }
+
+    #[test]
+    fn test_domain_gplinks() {
+        let policy = |guid: &str, options: u32| {
+            format!(
+                "[LDAP://cn={{{}}},cn=policies,cn=system,{};{}]",
+                guid, DOMAIN_DN, options
+            )
+        };
+        let domain = with_attributes(
+            domain_object(),
+            vec![
+                (
+                    "objectGUID",
+                    vec![AttributeValue::OctetString(vec![0x22; 16])],
+                ),
+                (
+                    "gPLink",
+                    vec![string(&format!(
+                        "{}{}{}",
+                        policy("31B2F340-016D-11D2-945F-00C04FB984F9", 2),
+                        policy("6AC1786C-016F-11D2-945F-00C04FB984F9", 1),
+                        policy("2E8B5A1F-3C4D-4E5F-8A9B-0C1D2E3F4A5B", 3),
+                    ))],
+                ),
+            ],
+        );
+        let snapshot = ADExplorerSnapshot::from_objects(vec![domain]);
+        let domain = Domain::new(&snapshot.snapshot.objects[0], &snapshot);
+
+        assert_eq!(domain.links.len(), 1);
+        assert_eq!(domain.links[0].guid, "31B2F340-016D-11D2-945F-00C04FB984F9");
+        assert!(domain.links[0].is_enforced);
+    }
+
+    #[test]
+    fn test_domain_when_created_is_unix_time() {
+        let with = |attributes: Vec<(&str, Vec<AttributeValue>)>| {
+            DomainProperties::get_when_created(&with_attributes(domain_object(), attributes))
+        };
+
+        // 2024-03-01 12:30:45 UTC as a FILETIME
+        let creation_time = (
+            "creationTime",
+            vec![AttributeValue::LargeInteger(133537698450000000)],
+        );
+        assert_eq!(with(vec![creation_time.clone()]), 1709296245);
+        assert_eq!(
+            with(vec![
+                creation_time,
+                ("whenCreated", vec![AttributeValue::UTCTime(1600000000)]),
+            ]),
+            1600000000
+        );
+    }
+
+    #[test]
+    fn test_domain_is_dotted_fqdn() {
+        let snapshot = snapshot_with(vec![]);
+        let properties = DomainProperties::new(&snapshot.snapshot.objects[0], &snapshot);
+
+        assert_eq!(properties.domain, "EXAMPLE.COM");
+        assert_eq!(properties.distinguishedname, DOMAIN_DN);
+    }
+
+    #[test]
+    fn test_parent_child_trust() {
+        let trust = process_trust(&trusted_domain("child.example.com", 0x20)).unwrap();
+        assert_eq!(trust.trust_type, "ParentChild");
+    }
+
+    #[test]
+    fn test_cross_link_trust() {
+        let trust = process_trust(&trusted_domain("other.example.org", 0x20)).unwrap();
+        assert_eq!(trust.trust_type, "CrossLink");
+    }
+
+    #[test]
+    fn test_forest_trust() {
+        let trust = process_trust(&trusted_domain("partner.com", 0x08)).unwrap();
+        assert_eq!(trust.trust_type, "Forest");
+        assert!(trust.sid_filtering_enabled);
+
+        let trust = process_trust(&trusted_domain("partner.com", 0x48)).unwrap();
+        assert_eq!(trust.trust_type, "Forest");
+        assert!(!trust.sid_filtering_enabled);
+    }
+
+    #[test]
+    fn test_external_trust() {
+        let trust = process_trust(&trusted_domain("legacy.local", 0x00)).unwrap();
+        assert_eq!(trust.trust_type, "External");
+        assert!(!trust.sid_filtering_enabled);
+    }
+
+    #[test]
+    fn test_realm_trust() {
+        let realm = with_attributes(
+            trusted_domain("REALM.EXAMPLE.ORG", 0x00),
+            vec![("trustType", vec![AttributeValue::Integer(TRUST_TYPE_MIT)])],
+        );
+        let trust = process_trust(&realm).unwrap();
+        assert_eq!(trust.trust_type, "MIT");
+
+        let uplevel = with_attributes(
+            trusted_domain("legacy.local", 0x00),
+            vec![("trustType", vec![AttributeValue::Integer(2)])],
+        );
+        let trust = process_trust(&uplevel).unwrap();
+        assert_eq!(trust.trust_type, "External");
+    }
+
+    #[test]
+    fn test_quarantined_external_trust() {
+        let trust = process_trust(&trusted_domain("legacy.local", 0x04)).unwrap();
+        assert_eq!(trust.trust_type, "External");
+        assert!(trust.sid_filtering_enabled);
+    }
+
+    #[test]
+    fn test_within_forest_trust_sid_filtering() {
+        let trust = process_trust(&trusted_domain("child.example.com", 0x20)).unwrap();
+        assert!(!trust.sid_filtering_enabled);
+
+        let trust = process_trust(&trusted_domain("child.example.com", 0x24)).unwrap();
+        assert!(trust.sid_filtering_enabled);
+    }
+
+    #[test]
+    fn test_trusts_belong_to_their_domain() {
+        let child = Object::from_attributes(vec![
+            ("objectClass", classes(&["top", "domain", "domainDNS"])),
+            (
+                "distinguishedName",
+                vec![string(&format!("DC=CHILD,{}", DOMAIN_DN))],
+            ),
+            ("name", vec![string("CHILD")]),
+        ]);
+        let child_trust = with_attributes(
+            trusted_domain("example.com", 0x20),
+            vec![(
+                "distinguishedName",
+                vec![string(&format!(
+                    "CN=example.com,CN=System,DC=CHILD,{}",
+                    DOMAIN_DN
+                ))],
+            )],
+        );
+        let snapshot = snapshot_with(vec![
+            trusted_domain("child.example.com", 0x20),
+            trusted_domain("partner.com", 0x08),
+            child,
+            child_trust,
+        ]);
+        let objects = &snapshot.snapshot.objects;
+
+        let names = |domain: &Object| -> Vec<String> {
+            process_trusts(domain, &snapshot)
+                .into_iter()
+                .map(|trust| trust.target_domain_name)
+                .collect()
+        };
+        assert_eq!(names(&objects[0]), ["CHILD.EXAMPLE.COM", "PARTNER.COM"]);
+        assert_eq!(names(&objects[3]), ["EXAMPLE.COM"]);
+    }
+
+    #[test]
+    fn test_trusted_domain_sid_resolves_to_domain() {
+        let partner_sid = "S-1-5-21-4000-5000-6000";
+        let with_sid = |name: &str, value: &str| {
+            with_attributes(
+                trusted_domain(name, 0x08),
+                vec![("securityIdentifier", vec![sid(value)])],
+            )
+        };
+        let snapshot = snapshot_with(vec![
+            with_sid("partner.com", partner_sid),
+            // Names this snapshot's own domain, whose object must still win
+            with_sid("example.com", DOMAIN_SID),
+        ]);
+
+        let trust = &snapshot.snapshot.objects[1];
+        assert_eq!(snapshot.get_type(trust), ObjectType::TrustedDomain);
+        assert_eq!(
+            resolve_sid(&partner_sid.parse().unwrap(), &snapshot),
+            Some((partner_sid.to_string(), "Domain".to_string()))
+        );
+        let domain = snapshot.get_sid(&DOMAIN_SID.parse().unwrap()).unwrap();
+        assert_eq!(snapshot.get_type(domain), ObjectType::Domain);
+    }
+
+    #[test]
+    fn test_one_domain_per_naming_context() {
+        let domain = |dn: &str, name: &str, domain_sid: &str, guid: u8| {
+            Object::from_attributes(vec![
+                ("objectClass", classes(&["top", "domain", "domainDNS"])),
+                ("distinguishedName", vec![string(dn)]),
+                ("name", vec![string(name)]),
+                ("objectSid", vec![sid(domain_sid)]),
+                (
+                    "objectGUID",
+                    vec![AttributeValue::OctetString(vec![guid; 16])],
+                ),
+                ("instanceType", vec![AttributeValue::Integer(5)]),
+            ])
+        };
+        let snapshot = ADExplorerSnapshot::from_objects(vec![
+            domain(DOMAIN_DN, "EXAMPLE", DOMAIN_SID, 0x11),
+            domain(
+                &format!("DC=CHILD,{}", DOMAIN_DN),
+                "CHILD",
+                "S-1-5-21-4000-5000-6000",
+                0x22,
+            ),
+        ]);
+
+        let output = DomainsOutput::new(&snapshot);
+        assert_eq!(output.meta.count, 2);
+        let sids: Vec<&str> = output
+            .domains
+            .iter()
+            .map(|domain| domain.properties.domainsid.as_str())
+            .collect();
+        assert_eq!(sids, [DOMAIN_SID, "S-1-5-21-4000-5000-6000"]);
+    }
+}
@@ -1,4 +1,6 @@
-use super::common::{get_aces, is_acl_protected, ldap2domain};
+use super::common::{
+    get_aces, get_domain, get_raw_security_descriptor, get_when_created, is_acl_protected,
+};
 use super::utils::{Aces, Meta};
 use crate::parser::{ADExplorerSnapshot, AttributeValue, Object, ObjectType};
 use serde::{Deserialize, Serialize};
@@ -16,17 +18,12 @@ impl GPOsOutput {
             .snapshot
             .objects
             .iter()
-            .filter(|v| v.get_type() == ObjectType::GPO)
+            .filter(|v| snapshot.get_type(v) == ObjectType::GPO)
             .map(|obj| GPO::new(obj, snapshot))
             .collect();
 
         Self {
-            meta: Meta {
-                methods: 46067,
-                r#type: "gpos".to_string(),
-                count: gpos.len() as u64,
-                version: 6,
-            },
+            meta: Meta::new("gpos", gpos.len(), 6, snapshot),
             gpos,
         }
     }
@@ -48,6 +45,12 @@ pub struct GPO {
 
     #[serde(rename = "IsACLProtected")]
     is_acl_protected: bool,
+
+    #[serde(
+        rename = "RawSecurityDescriptor",
+        skip_serializing_if = "Option::is_none"
+    )]
+    raw_security_descriptor: Option<String>,
 }
 
 impl GPO {
@@ -62,6 +65,7 @@ impl GPO {
                 .unwrap_or_default(),
             is_deleted: false, // Assuming this information is not available in the snapshot
             is_acl_protected: is_acl_protected(obj),
+            raw_security_descriptor: get_raw_security_descriptor(obj, snapshot),
         }
     }
 }
@@ -83,11 +87,25 @@ impl GPOProperties {
             .and_then(AttributeValue::as_string)
             .map(|v| v.clone())
             .unwrap_or_default();
-        let domain = ldap2domain(&distinguished_name);
+        let domain = get_domain(&distinguished_name, snapshot);
+        // Nameless GPOs fall back to their CN, the {GUID} of the policy
+        let cn = distinguished_name
+            .split(',')
+            .next()
+            .and_then(|rdn| rdn.split_once('='))
+            .map(|(_, value)| value.to_string())
+            .unwrap_or_default();
         let name = obj
             .get_first("displayName")
             .and_then(AttributeValue::as_string)
-            .map(|v| v.clone())
+            .filter(|name| !name.is_empty())
+            .cloned()
+            .unwrap_or_else(|| cn.clone());
+        let gpcpath = obj
+            .get_first("gPCFileSysPath")
+            .and_then(AttributeValue::as_string)
+            .cloned()
+            .or_else(|| Self::default_gpcpath(obj, &cn, &domain))
             .unwrap_or_default();
 
         GPOProperties {
@@ -95,15 +113,113 @@ impl GPOProperties {
             name: format!("{}@{}", name.to_uppercase(), domain.to_uppercase()),
             distinguishedname: distinguished_name,
             domainsid: snapshot.caches.domain_sid.as_ref().unwrap().to_string(),
-            whencreated: obj
-                .get_first("whenCreated")
-                .and_then(AttributeValue::as_unix_timestamp)
-                .unwrap_or(0),
-            gpcpath: obj
-                .get_first("gPCFileSysPath")
-                .and_then(AttributeValue::as_string)
-                .map(|v| v.to_string())
-                .unwrap_or_default(),
+            whencreated: get_when_created(obj),
+            // SharpHound reports the path uppercased
+            gpcpath: gpcpath.to_uppercase(),
         }
     }
+
+    /// The SYSVOL folder a GPO's files live in, named after its CN, or after
+    /// its objectGUID when the CN isn't a GUID.
+    fn default_gpcpath(obj: &Object, cn: &str, domain: &str) -> Option<String> {
+        let folder = if cn.starts_with('{') && cn.ends_with('}') {
+            cn.to_string()
+        } else {
+            let guid = obj
+                .get_first("objectGUID")
+                .and_then(AttributeValue::as_guid)?;
+            format!("{{{}}}", guid.to_string())
+        };
+        Some(format!(
+            "\\\\{}\\SysVol\\{}\\Policies\\{}",
+            domain, domain, folder
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::bloodhound::test_utils::{
+        allow_mask_descriptor, classes, group_object, snapshot_with, string, with_attributes,
+        DOMAIN_DN, DOMAIN_SID,
+    };
+
+    const POLICY: &str = "{31B2F340-016D-11D2-945F-00C04FB984F9}";
+
+    fn gpo_object(attributes: Vec<(&str, Vec<AttributeValue>)>) -> Object {
+        let gpo = Object::from_attributes(vec![
+            (
+                "objectClass",
+                classes(&["top", "container", "groupPolicyContainer"]),
+            ),
+            (
+                "distinguishedName",
+                vec![string(&format!(
+                    "CN={},CN=Policies,CN=System,{}",
+                    POLICY, DOMAIN_DN
+                ))],
+            ),
+        ]);
+        with_attributes(gpo, attributes)
+    }
+
+    #[test]
+    fn test_gpo_name_and_path() {
+        let snapshot = snapshot_with(vec![gpo_object(vec![
+            ("displayName", vec![string("Default Domain Policy")]),
+            (
+                "gPCFileSysPath",
+                vec![string(&format!(
+                    "\\\\example.com\\SysVol\\example.com\\Policies\\{}",
+                    POLICY
+                ))],
+            ),
+        ])]);
+        let properties = GPOProperties::new(&snapshot.snapshot.objects[1], &snapshot);
+
+        assert_eq!(properties.name, "DEFAULT DOMAIN POLICY@EXAMPLE.COM");
+        assert_eq!(
+            properties.gpcpath,
+            format!("\\\\EXAMPLE.COM\\SYSVOL\\EXAMPLE.COM\\POLICIES\\{}", POLICY)
+        );
+    }
+
+    #[test]
+    fn test_gpo_without_name_or_path() {
+        let snapshot = snapshot_with(vec![gpo_object(Vec::new())]);
+        let properties = GPOProperties::new(&snapshot.snapshot.objects[1], &snapshot);
+
+        assert_eq!(properties.name, format!("{}@EXAMPLE.COM", POLICY));
+        assert_eq!(
+            properties.gpcpath,
+            format!("\\\\EXAMPLE.COM\\SYSVOL\\EXAMPLE.COM\\POLICIES\\{}", POLICY)
+        );
+    }
+
+    #[test]
+    fn test_gpo_full_control_is_generic_all() {
+        let editors = format!("{}-1100", DOMAIN_SID);
+        let snapshot = snapshot_with(vec![
+            group_object("GPO Editors", 1100, &[]),
+            gpo_object(vec![
+                ("gPCFileSysPath", vec![string("\\\\example.com\\SysVol")]),
+                (
+                    "nTSecurityDescriptor",
+                    vec![allow_mask_descriptor(&[&editors], 0x000F01FF)],
+                ),
+            ]),
+        ]);
+        let output = serde_json::to_value(GPOsOutput::new(&snapshot)).unwrap();
+
+        assert_eq!(
+            output["data"][0]["Aces"],
+            serde_json::json!([{
+                "PrincipalSID": editors,
+                "PrincipalType": "Group",
+                "RightName": "GenericAll",
+                "IsInherited": false,
+            }])
+        );
+    }
 }
@@ -1,8 +1,14 @@
-use super::common::{get_aces, get_sid, is_acl_protected, ldap2domain, type_string};
+use super::common::{
+    get_aces, get_admin_count, get_domain, get_raw_security_descriptor, get_sid, get_when_created,
+    is_acl_protected, is_adminsdholder_protected, is_noise_object, type_string,
+};
 use super::utils::{Aces, Meta};
-use crate::parser::{ADExplorerSnapshot, AttributeValue, Object};
+use crate::guid::GUID;
+use crate::parser::{
+    split_dn, ADExplorerSnapshot, AttributeValue, Cache, Object, ObjectType, SamAccountType,
+};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GroupsOutput {
@@ -13,7 +19,6 @@ pub struct GroupsOutput {
 
 impl GroupsOutput {
     pub fn new(snapshot: &ADExplorerSnapshot) -> Self {
-        let domain_sid = snapshot.caches.domain_sid.as_ref().unwrap().to_string();
         let highvalue_sids: HashSet<&str> = [
             "S-1-5-32-544",
             "S-1-5-32-550",
@@ -25,32 +30,44 @@ impl GroupsOutput {
         .cloned()
         .collect();
 
+        let primary_groups = PrimaryGroupIndex::new(snapshot);
         let groups: Vec<Group> = snapshot
             .snapshot
             .objects
             .iter()
-            .filter(|obj| {
-                obj.get("objectClass")
-                    .map(|values| {
-                        values
-                            .iter()
-                            .any(|v| v.as_string() == Some(&"group".to_string()))
-                    })
-                    .unwrap_or(false)
+            .filter(|obj| Self::is_group(obj, snapshot))
+            .map(|obj| {
+                let mut group = Group::new(obj, snapshot, &highvalue_sids);
+                group.add_members(primary_group_members(obj, &primary_groups, snapshot));
+                group
             })
-            .map(|obj| Group::new(obj, snapshot, &domain_sid, &highvalue_sids))
             .collect();
 
         Self {
-            meta: Meta {
-                methods: 46067,
-                r#type: "groups".to_string(),
-                count: groups.len() as u64,
-                version: 5,
-            },
+            meta: Meta::new("groups", groups.len(), 5, snapshot),
             groups,
         }
     }
+
+    fn is_group(obj: &Object, snapshot: &ADExplorerSnapshot) -> bool {
+        if let Some(object_type) = snapshot.type_override(obj) {
+            return object_type == ObjectType::Group;
+        }
+
+        let class_condition = obj
+            .get("objectClass")
+            .map(|values| {
+                values
+                    .iter()
+                    .any(|v| v.as_string() == Some(&"group".to_string()))
+            })
+            .unwrap_or(false);
+
+        // Fall back to the account type for objects whose objectClass is missing
+        let account_type_condition = obj.sam_account_type().is_some_and(SamAccountType::is_group);
+
+        class_condition || account_type_condition || obj.get_type() == ObjectType::Group
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -72,32 +89,50 @@ pub struct Group {
 
     #[serde(rename = "IsACLProtected")]
     is_acl_protected: bool,
+
+    #[serde(
+        rename = "RawSecurityDescriptor",
+        skip_serializing_if = "Option::is_none"
+    )]
+    raw_security_descriptor: Option<String>,
 }
 
 impl Group {
     pub fn new(
         obj: &Object,
         snapshot: &ADExplorerSnapshot,
-        domain_sid: &str,
         highvalue_sids: &HashSet<&str>,
     ) -> Self {
-        let sid = get_sid(obj);
-        let object_identifier = if WELLKNOWN_SIDS.contains(&sid.as_str()) {
-            format!("{}-{}", domain_sid, sid)
-        } else {
-            sid.clone()
-        };
+        let sid = obj
+            .get_object_identifier()
+            .unwrap_or("ERR_UNKNOWN".to_string());
 
         Group {
             properties: GroupProperties::new(obj, snapshot, &sid, highvalue_sids),
             members: process_members(obj, snapshot),
             aces: get_aces(obj, snapshot),
-            object_identifier,
+            object_identifier: get_sid(obj, snapshot),
             is_deleted: obj
                 .get_first("isDeleted")
                 .and_then(AttributeValue::as_boolean)
                 .unwrap_or(false),
             is_acl_protected: is_acl_protected(obj),
+            raw_security_descriptor: get_raw_security_descriptor(obj, snapshot),
+        }
+    }
+}
+
+impl Group {
+    fn add_members(&mut self, members: Vec<GroupMember>) {
+        let mut seen: HashSet<String> = self
+            .members
+            .iter()
+            .map(|m| m.object_identifier.clone())
+            .collect();
+        for member in members {
+            if seen.insert(member.object_identifier.clone()) {
+                self.members.push(member);
+            }
         }
     }
 }
@@ -110,6 +145,7 @@ pub struct GroupProperties {
     pub name: String,
     pub distinguishedname: String,
     pub admincount: bool,
+    pub protected: bool,
     pub description: Option<String>,
     pub whencreated: i64,
 }
@@ -126,7 +162,7 @@ impl GroupProperties {
             .and_then(AttributeValue::as_string)
             .map(|s| s.to_string())
             .unwrap_or_default();
-        let domain = ldap2domain(&distinguished_name).to_uppercase();
+        let domain = get_domain(&distinguished_name, snapshot).to_uppercase();
         let name = obj
             .get_first("name")
             .and_then(AttributeValue::as_string)
@@ -139,19 +175,13 @@ impl GroupProperties {
             highvalue: is_highvalue(sid, highvalue_sids),
             name: format!("{}@{}", name.to_uppercase(), domain),
             distinguishedname: distinguished_name.to_string(),
-            admincount: obj
-                .get_first("adminCount")
-                .and_then(AttributeValue::as_integer)
-                .map(|count| count == 1)
-                .unwrap_or(false),
+            admincount: get_admin_count(obj),
+            protected: is_adminsdholder_protected(obj),
             description: obj
                 .get_first("description")
                 .and_then(AttributeValue::as_string)
                 .map(|v| v.to_string()),
-            whencreated: obj
-                .get_first("whenCreated")
-                .and_then(AttributeValue::as_unix_timestamp)
-                .unwrap_or(0),
+            whencreated: get_when_created(obj),
         }
     }
 }
@@ -165,22 +195,162 @@ pub struct GroupMember {
 }
 
 fn process_members(obj: &Object, snapshot: &ADExplorerSnapshot) -> Vec<GroupMember> {
+    let own_sid = get_sid(obj, snapshot);
+    let mut visited = HashSet::new();
     obj.get("member")
         .map(|values| {
             values
                 .iter()
                 .filter_map(AttributeValue::as_string)
-                .filter_map(|member_dn| resolve_membership(member_dn, snapshot))
+                .filter_map(|member_dn| resolve_membership(member_dn, snapshot, &mut visited))
+                .map(|(_, member)| member)
+                // A group listing itself would otherwise produce a self-edge
+                .filter(|member| member.object_identifier != own_sid)
                 .collect()
         })
         .unwrap_or_default()
 }
 
-fn resolve_membership(member_dn: &str, snapshot: &ADExplorerSnapshot) -> Option<GroupMember> {
-    snapshot.get_dn(member_dn).map(|obj| GroupMember {
-        object_identifier: get_sid(obj),
-        object_type: type_string(obj),
-    })
+/// Users and computers by the SID of their primary group. primaryGroupID is a
+/// RID in the object's own domain. Build it once and share it between groups.
+pub struct PrimaryGroupIndex(HashMap<String, Vec<usize>>);
+
+impl PrimaryGroupIndex {
+    pub fn new(snapshot: &ADExplorerSnapshot) -> Self {
+        let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, obj) in snapshot.snapshot.objects.iter().enumerate() {
+            if !matches!(
+                snapshot.get_type(obj),
+                ObjectType::User | ObjectType::UserDisabled | ObjectType::Computer
+            ) {
+                continue;
+            }
+            let Some(rid) = obj
+                .get_first("primaryGroupID")
+                .and_then(AttributeValue::as_integer)
+            else {
+                continue;
+            };
+            let Some(sid) = obj.get_first("objectSid").and_then(AttributeValue::as_sid) else {
+                continue;
+            };
+            let sid = sid.to_string();
+            if let Some((domain_sid, _)) = sid.rsplit_once('-') {
+                index
+                    .entry(format!("{}-{}", domain_sid, rid))
+                    .or_default()
+                    .push(i);
+            }
+        }
+        PrimaryGroupIndex(index)
+    }
+}
+
+/// Users and computers whose primary group is `obj`. AD leaves the primary
+/// group out of `member`, so these memberships only show in primaryGroupID.
+pub fn primary_group_members(
+    obj: &Object,
+    index: &PrimaryGroupIndex,
+    snapshot: &ADExplorerSnapshot,
+) -> Vec<GroupMember> {
+    let Some(sid) = obj.get_first("objectSid").and_then(AttributeValue::as_sid) else {
+        return Vec::new();
+    };
+
+    index
+        .0
+        .get(&sid.to_string())
+        .into_iter()
+        .flatten()
+        .map(|&i| &snapshot.snapshot.objects[i])
+        .map(|member| GroupMember {
+            object_identifier: get_sid(member, snapshot),
+            object_type: type_string(member, snapshot),
+        })
+        .collect()
+}
+
+/// Resolves the members of `obj`, descending into nested groups. Each object is
+/// emitted at most once, so membership cycles terminate.
+pub fn effective_members(obj: &Object, snapshot: &ADExplorerSnapshot) -> Vec<GroupMember> {
+    // The root is visited up front, so a cycle back to it doesn't list the
+    // group as its own member
+    let mut visited: HashSet<usize> = obj
+        .get_first("distinguishedName")
+        .and_then(AttributeValue::as_string)
+        .and_then(|dn| snapshot.caches.dn_cache.get(dn))
+        .copied()
+        .into_iter()
+        .collect();
+    let mut members = Vec::new();
+    expand_members(obj, snapshot, &mut visited, &mut members);
+    members
+}
+
+fn expand_members(
+    obj: &Object,
+    snapshot: &ADExplorerSnapshot,
+    visited: &mut HashSet<usize>,
+    members: &mut Vec<GroupMember>,
+) {
+    let member_dns = obj
+        .get("member")
+        .map(|values| values.iter().filter_map(AttributeValue::as_string));
+
+    for member_dn in member_dns.into_iter().flatten() {
+        if let Some((index, member)) = resolve_membership(member_dn, snapshot, visited) {
+            members.push(member);
+            let member_obj = &snapshot.snapshot.objects[index];
+            if snapshot.get_type(member_obj) == ObjectType::Group {
+                expand_members(member_obj, snapshot, visited, members);
+            }
+        }
+    }
+}
+
+/// Resolves `member_dn` to its object index and member entry, returning `None`
+/// if it is unknown or was already in `visited`.
+fn resolve_membership(
+    member_dn: &str,
+    snapshot: &ADExplorerSnapshot,
+    visited: &mut HashSet<usize>,
+) -> Option<(usize, GroupMember)> {
+    let index = match snapshot.caches.dn_cache.get(&member_dn.to_string()) {
+        Some(index) => *index,
+        None => resolve_tombstone(member_dn, snapshot)?,
+    };
+    if !visited.insert(index) {
+        return None;
+    }
+
+    let obj = snapshot.snapshot.objects.get(index)?;
+    if is_noise_object(obj) {
+        return None;
+    }
+
+    Some((
+        index,
+        GroupMember {
+            object_identifier: get_sid(obj, snapshot),
+            object_type: type_string(obj, snapshot),
+        },
+    ))
+}
+
+/// Deleted objects are renamed to `CN=name\0ADEL:<guid>` and moved, so their
+/// old DN no longer matches. Resolve them through the GUID instead.
+fn resolve_tombstone(member_dn: &str, snapshot: &ADExplorerSnapshot) -> Option<usize> {
+    let rdn = *split_dn(member_dn).first()?;
+    let (_, guid) = rdn.split_once("DEL:")?;
+
+    let index = guid
+        .parse::<GUID>()
+        .ok()
+        .and_then(|guid| snapshot.caches.guid_cache.get(&guid).copied());
+    if index.is_none() {
+        log::warn!(target: "group_member", "Unresolved deleted group member: {}", member_dn);
+    }
+    index
 }
 
 fn is_highvalue(sid: &str, highvalue_sids: &HashSet<&str>) -> bool {
@@ -191,66 +361,211 @@ fn is_highvalue(sid: &str, highvalue_sids: &HashSet<&str>) -> bool {
         || highvalue_sids.contains(sid)
 }
 
-const WELLKNOWN_SIDS: &[&str] = &[
-    "S-1-0",
-    "S-1-0-0",
-    "S-1-1",
-    "S-1-1-0",
-    "S-1-2",
-    "S-1-2-0",
-    "S-1-2-1",
-    "S-1-3",
-    "S-1-3-0",
-    "S-1-3-1",
-    "S-1-3-2",
-    "S-1-3-3",
-    "S-1-3-4",
-    "S-1-5-1",
-    "S-1-5-2",
-    "S-1-5-3",
-    "S-1-5-4",
-    "S-1-5-6",
-    "S-1-5-7",
-    "S-1-5-8",
-    "S-1-5-9",
-    "S-1-5-10",
-    "S-1-5-11",
-    "S-1-5-12",
-    "S-1-5-13",
-    "S-1-5-14",
-    "S-1-5-15",
-    "S-1-5-17",
-    "S-1-5-18",
-    "S-1-5-19",
-    "S-1-5-20",
-    "S-1-5-21-0-0-0-496",
-    "S-1-5-21-0-0-0-497",
-    "S-1-5-32-544",
-    "S-1-5-32-545",
-    "S-1-5-32-546",
-    "S-1-5-32-547",
-    "S-1-5-32-548",
-    "S-1-5-32-549",
-    "S-1-5-32-550",
-    "S-1-5-32-551",
-    "S-1-5-32-552",
-    "S-1-5-32-554",
-    "S-1-5-32-555",
-    "S-1-5-32-556",
-    "S-1-5-32-557",
-    "S-1-5-32-558",
-    "S-1-5-32-559",
-    "S-1-5-32-560",
-    "S-1-5-32-561",
-    "S-1-5-32-562",
-    "S-1-5-32-568",
-    "S-1-5-32-569",
-    "S-1-5-32-573",
-    "S-1-5-32-574",
-    "S-1-5-32-575",
-    "S-1-5-32-576",
-    "S-1-5-32-577",
-    "S-1-5-32-578",
-    "S-1-5-32-579",
-    "S-1-5-32-580",
-];
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::bloodhound::test_utils::{
+        admin_protected, computer_object, group_object, sid, snapshot_with, string, user_object,
+        with_attributes, DOMAIN_DN, DOMAIN_SID,
+    };
+
+    fn dn(name: &str) -> String {
+        format!("CN={},CN=Users,{}", name, DOMAIN_DN)
+    }
+
+    #[test]
+    fn test_group_without_object_class() {
+        let orphan = Object::from_attributes(vec![
+            (
+                "distinguishedName",
+                vec![string(&format!("CN=Orphan,CN=Users,{}", DOMAIN_DN))],
+            ),
+            ("name", vec![string("Orphan")]),
+            ("objectSid", vec![sid(&format!("{}-1300", DOMAIN_SID))]),
+            ("sAMAccountType", vec![AttributeValue::Integer(268435456)]),
+        ]);
+        let snapshot = snapshot_with(vec![orphan, user_object("alice", 1100, 0x200)]);
+        let output = GroupsOutput::new(&snapshot);
+
+        assert_eq!(output.groups.len(), 1);
+        assert_eq!(
+            output.groups[0].properties.name,
+            "ORPHAN@EXAMPLE.COM".to_string()
+        );
+    }
+
+    #[test]
+    fn test_tombstoned_member() {
+        let guid = "6f2e4c1a-3b5d-4e7f-8a9b-0c1d2e3f4a5b";
+        let bob = with_attributes(
+            user_object("bob", 1101, 0x200),
+            vec![
+                (
+                    "distinguishedName",
+                    vec![string(&format!(
+                        "CN=bob\\0ADEL:{},CN=Deleted Objects,{}",
+                        guid, DOMAIN_DN
+                    ))],
+                ),
+                (
+                    "objectGUID",
+                    vec![AttributeValue::OctetString(
+                        guid.parse::<GUID>().unwrap().to_bytes(),
+                    )],
+                ),
+            ],
+        );
+        let snapshot = snapshot_with(vec![
+            group_object(
+                "Staff",
+                1200,
+                &[
+                    &format!("CN=bob\\0ADEL:{},CN=Users,{}", guid, DOMAIN_DN),
+                    &format!(
+                        "CN=carol\\0ADEL:00000000-0000-0000-0000-000000000001,CN=Users,{}",
+                        DOMAIN_DN
+                    ),
+                ],
+            ),
+            bob,
+        ]);
+        let members = process_members(&snapshot.snapshot.objects[1], &snapshot);
+
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].object_identifier, format!("{}-1101", DOMAIN_SID));
+    }
+
+    #[test]
+    fn test_tombstoned_member_with_escaped_comma() {
+        let guid = "6f2e4c1a-3b5d-4e7f-8a9b-0c1d2e3f4a5b";
+        let bob = with_attributes(
+            user_object("bob", 1101, 0x200),
+            vec![(
+                "objectGUID",
+                vec![AttributeValue::OctetString(
+                    guid.parse::<GUID>().unwrap().to_bytes(),
+                )],
+            )],
+        );
+        let snapshot = snapshot_with(vec![
+            group_object(
+                "Staff",
+                1200,
+                &[&format!(
+                    "CN=Smith\\, Bob\\0ADEL:{},CN=Users,{}",
+                    guid, DOMAIN_DN
+                )],
+            ),
+            bob,
+        ]);
+        let members = process_members(&snapshot.snapshot.objects[1], &snapshot);
+
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].object_identifier, format!("{}-1101", DOMAIN_SID));
+    }
+
+    #[test]
+    fn test_self_membership_dropped() {
+        let snapshot = snapshot_with(vec![
+            group_object("Loop", 1200, &[&dn("Loop"), &dn("alice")]),
+            user_object("alice", 1100, 0x200),
+        ]);
+        let members = process_members(&snapshot.snapshot.objects[1], &snapshot);
+
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].object_identifier, format!("{}-1100", DOMAIN_SID));
+    }
+
+    #[test]
+    fn test_effective_members_self_reference_terminates() {
+        let snapshot = snapshot_with(vec![group_object("Loop", 1200, &[&dn("Loop")])]);
+        let members = effective_members(&snapshot.snapshot.objects[1], &snapshot);
+
+        assert!(members
+            .iter()
+            .all(|m| m.object_identifier != format!("{}-1200", DOMAIN_SID)));
+    }
+
+    #[test]
+    fn test_effective_members_cycle() {
+        let snapshot = snapshot_with(vec![
+            group_object("A", 1200, &[&dn("B")]),
+            group_object("B", 1201, &[&dn("A"), &dn("alice")]),
+            user_object("alice", 1100, 0x200),
+        ]);
+        let members = effective_members(&snapshot.snapshot.objects[1], &snapshot);
+        let sids: Vec<&str> = members
+            .iter()
+            .map(|m| m.object_identifier.as_str())
+            .collect();
+
+        // B and alice, but not A itself
+        assert_eq!(members.len(), 2);
+        assert!(sids.contains(&format!("{}-1100", DOMAIN_SID).as_str()));
+        assert!(!sids.contains(&format!("{}-1200", DOMAIN_SID).as_str()));
+    }
+
+    #[test]
+    fn test_group_adminsdholder_protected() {
+        let snapshot = snapshot_with(vec![
+            admin_protected(group_object("Domain Admins", 512, &[])),
+            group_object("Staff", 1200, &[]),
+        ]);
+        let output = GroupsOutput::new(&snapshot);
+
+        assert!(output.groups[0].properties.admincount);
+        assert!(output.groups[0].properties.protected);
+        assert!(!output.groups[1].properties.protected);
+    }
+
+    #[test]
+    fn test_primary_group_members() {
+        let with_primary_group = |obj: Object, rid: u32| {
+            with_attributes(
+                obj,
+                vec![("primaryGroupID", vec![AttributeValue::Integer(rid)])],
+            )
+        };
+        let snapshot = snapshot_with(vec![
+            group_object("Domain Users", 513, &[&dn("bob")]),
+            group_object("Domain Computers", 515, &[]),
+            with_primary_group(user_object("alice", 1100, 0x200), 513),
+            with_primary_group(user_object("bob", 1101, 0x200), 513),
+            with_primary_group(computer_object("WS01", 1102, 0x1000), 515),
+        ]);
+
+        let index = PrimaryGroupIndex::new(&snapshot);
+        let users = primary_group_members(&snapshot.snapshot.objects[1], &index, &snapshot);
+        let sids: Vec<&str> = users.iter().map(|m| m.object_identifier.as_str()).collect();
+        assert_eq!(
+            sids,
+            vec![
+                format!("{}-1100", DOMAIN_SID),
+                format!("{}-1101", DOMAIN_SID)
+            ]
+        );
+
+        // bob is also listed in member, but only appears once
+        let output = GroupsOutput::new(&snapshot);
+        let members = |group: &Group| -> Vec<(String, String)> {
+            let mut members: Vec<(String, String)> = group
+                .members
+                .iter()
+                .map(|m| (m.object_identifier.clone(), m.object_type.clone()))
+                .collect();
+            members.sort();
+            members
+        };
+        assert_eq!(
+            members(&output.groups[0]),
+            vec![
+                (format!("{}-1100", DOMAIN_SID), "User".to_string()),
+                (format!("{}-1101", DOMAIN_SID), "User".to_string()),
+            ]
+        );
+        assert_eq!(
+            members(&output.groups[1]),
+            vec![(format!("{}-1102", DOMAIN_SID), "Computer".to_string())]
+        );
+    }
+}
@@ -1,17 +1,241 @@
 mod common;
 mod computers;
+pub mod constants;
 mod containers;
 mod domains;
 mod gpos;
 mod groups;
 mod ous;
+#[cfg(test)]
+pub(crate) mod test_utils;
 mod users;
 mod utils;
 
-pub use computers::ComputersOutput;
+pub use common::unresolved_delegation_targets;
+pub use computers::{ComputersOutput, ComputersStream};
 pub use containers::ContainersOutput;
 pub use domains::DomainsOutput;
 pub use gpos::GPOsOutput;
-pub use groups::GroupsOutput;
+pub use groups::{
+    effective_members, primary_group_members, GroupMember, GroupsOutput, PrimaryGroupIndex,
+};
 pub use ous::OUsOutput;
-pub use users::UsersOutput;
+pub use users::{UsersOutput, UsersStream};
+
+/// Every attribute the outputs and caches read. Snapshots parsed with only
+/// these attributes produce the same outputs as fully parsed ones.
+pub const REQUIRED_ATTRIBUTES: &[&str] = &[
+    "adminCount",
+    "certificateTemplates",
+    "creationTime",
+    "dNSHostName",
+    "description",
+    "displayName",
+    "distinguishedName",
+    "gPCFileSysPath",
+    "gPLink",
+    "gPOptions",
+    "instanceType",
+    "isDeleted",
+    "lastLogon",
+    "lastLogonTimestamp",
+    "member",
+    "ms-Mcs-AdmPwd",
+    "ms-Mcs-AdmPwdExpirationTime",
+    "msDS-AdditionalDnsHostName",
+    "msDS-AllowedToActOnBehalfOfOtherIdentity",
+    "msDS-AllowedToDelegateTo",
+    "msDS-Behavior-Version",
+    "msDS-GroupMSAMembership",
+    "msDS-RevealedUsers",
+    "msLAPS-EncryptedPassword",
+    "msLAPS-Password",
+    "msLAPS-PasswordExpirationTime",
+    "nCName",
+    "nTSecurityDescriptor",
+    "name",
+    "objectCategory",
+    "objectClass",
+    "objectGUID",
+    "objectSid",
+    "operatingSystem",
+    "operatingSystemServicePack",
+    "primaryGroupID",
+    "pwdLastSet",
+    "sAMAccountName",
+    "sAMAccountType",
+    "sIDHistory",
+    "securityIdentifier",
+    "servicePrincipalName",
+    "systemFlags",
+    "trustAttributes",
+    "trustDirection",
+    "trustTransitive",
+    "trustType",
+    "userAccountControl",
+    "whenCreated",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::test_utils::{
+        computer_object, group_object, snapshot_with, user_object, with_attributes,
+    };
+    use super::{ComputersOutput, ComputersStream, GroupsOutput, UsersOutput, UsersStream};
+    use crate::parser::{AttributeValue, Object, ObjectType};
+    use serde::Serialize;
+
+    fn with_account_type(obj: Object, account_type: u32) -> Object {
+        with_attributes(
+            obj,
+            vec![(
+                "sAMAccountType",
+                vec![AttributeValue::Integer(account_type)],
+            )],
+        )
+    }
+
+    fn count(output: impl Serialize) -> u64 {
+        serde_json::to_value(output).unwrap()["meta"]["count"]
+            .as_u64()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_account_type_routing() {
+        // (account type, object, type, user, computer, group)
+        let cases = [
+            (
+                805306368,
+                user_object("alice", 1100, 0x200),
+                ObjectType::User,
+                1,
+                0,
+                0,
+            ),
+            (
+                805306369,
+                computer_object("WS01", 1101, 0x1000),
+                ObjectType::Computer,
+                0,
+                1,
+                0,
+            ),
+            (
+                805306370,
+                user_object("PARTNER$", 1102, 0x800),
+                ObjectType::User,
+                0,
+                0,
+                0,
+            ),
+            (
+                268435456,
+                group_object("Global", 1103, &[]),
+                ObjectType::Group,
+                0,
+                0,
+                1,
+            ),
+            (
+                268435457,
+                group_object("Distribution", 1104, &[]),
+                ObjectType::Group,
+                0,
+                0,
+                1,
+            ),
+            (
+                536870912,
+                group_object("Local", 1105, &[]),
+                ObjectType::Group,
+                0,
+                0,
+                1,
+            ),
+            (
+                536870913,
+                group_object("LocalDistribution", 1106, &[]),
+                ObjectType::Group,
+                0,
+                0,
+                1,
+            ),
+        ];
+
+        for (account_type, obj, object_type, users, computers, groups) in cases {
+            let snapshot = snapshot_with(vec![with_account_type(obj, account_type)]);
+
+            assert_eq!(
+                snapshot.get_type(&snapshot.snapshot.objects[1]),
+                object_type,
+                "{}",
+                account_type
+            );
+            assert_eq!(
+                count(UsersOutput::new(&snapshot)),
+                users,
+                "{}",
+                account_type
+            );
+            assert_eq!(
+                count(ComputersOutput::new(&snapshot)),
+                computers,
+                "{}",
+                account_type
+            );
+            assert_eq!(
+                count(GroupsOutput::new(&snapshot)),
+                groups,
+                "{}",
+                account_type
+            );
+        }
+    }
+
+    #[test]
+    fn test_streamed_outputs_match() {
+        let snapshot = snapshot_with(vec![
+            user_object("alice", 1100, 0x200),
+            user_object("bob", 1101, 0x202),
+            computer_object("WS01", 1102, 0x1000),
+            group_object("Staff", 1103, &[]),
+        ]);
+
+        assert_eq!(
+            serde_json::to_vec(&UsersStream::new(&snapshot)).unwrap(),
+            serde_json::to_vec(&UsersOutput::new(&snapshot)).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_vec(&ComputersStream::new(&snapshot)).unwrap(),
+            serde_json::to_vec(&ComputersOutput::new(&snapshot)).unwrap()
+        );
+        assert_eq!(count(UsersStream::new(&snapshot)), 2);
+    }
+
+    #[test]
+    fn test_snapshot_meta() {
+        let mut snapshot = snapshot_with(vec![user_object("alice", 1100, 0x200)]);
+        snapshot.snapshot.header.server = "DC01.example.com".to_string();
+        // 2024-03-01T12:30:45Z
+        snapshot.snapshot.header.filetime = (1_709_296_245 + 11_644_473_600) * 10_000_000;
+
+        assert_eq!(snapshot.server(), "DC01.example.com");
+        assert_eq!(snapshot.captured_at().timestamp(), 1_709_296_245);
+
+        let users = serde_json::to_value(UsersOutput::new(&snapshot)).unwrap();
+        assert!(users["meta"].get("snapshot").is_none());
+
+        snapshot.include_snapshot_meta = true;
+        let expected = serde_json::json!({
+            "server": "DC01.example.com",
+            "capturedat": 1_709_296_245,
+        });
+        let users = serde_json::to_value(UsersOutput::new(&snapshot)).unwrap();
+        assert_eq!(users["meta"]["snapshot"], expected);
+        let streamed = serde_json::to_value(UsersStream::new(&snapshot)).unwrap();
+        assert_eq!(streamed["meta"]["snapshot"], expected);
+        let groups = serde_json::to_value(GroupsOutput::new(&snapshot)).unwrap();
+        assert_eq!(groups["meta"]["snapshot"], expected);
+    }
+}
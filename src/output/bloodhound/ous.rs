@@ -1,12 +1,14 @@
-use super::common::{get_aces, is_acl_protected, ldap2domain, type_string};
+use super::common::{
+    get_aces, get_domain, get_raw_security_descriptor, get_sid, get_when_created, is_acl_protected,
+    is_noise_object, type_string,
+};
 use super::utils::{Aces, Meta};
 use crate::parser::{ADExplorerSnapshot, AttributeValue, Object, ObjectType};
 use nom::{
-    branch::alt,
-    bytes::complete::{is_not, tag},
-    character::complete::char,
-    combinator::{map, opt, value},
-    multi::separated_list0,
+    bytes::complete::{is_not, tag_no_case},
+    character::complete::{char, digit1},
+    combinator::{map, map_res, opt},
+    multi::many0,
     sequence::{delimited, preceded, tuple},
     IResult,
 };
@@ -25,17 +27,12 @@ impl OUsOutput {
             .snapshot
             .objects
             .iter()
-            .filter(|obj| obj.get_type() == ObjectType::OU)
+            .filter(|obj| snapshot.get_type(obj) == ObjectType::OU)
             .map(|obj| OU::new(obj, snapshot))
             .collect();
 
         Self {
-            meta: Meta {
-                methods: 46067,
-                r#type: "ous".to_string(),
-                count: ous.len() as u64,
-                version: 5,
-            },
+            meta: Meta::new("ous", ous.len(), 5, snapshot),
             ous,
         }
     }
@@ -63,6 +60,12 @@ pub struct OU {
 
     #[serde(rename = "IsACLProtected")]
     is_acl_protected: bool,
+
+    #[serde(
+        rename = "RawSecurityDescriptor",
+        skip_serializing_if = "Option::is_none"
+    )]
+    raw_security_descriptor: Option<String>,
 }
 
 impl OU {
@@ -79,6 +82,7 @@ impl OU {
                 .unwrap_or_default(),
             is_deleted: false, // Assuming this information is not available in the snapshot
             is_acl_protected: is_acl_protected(obj),
+            raw_security_descriptor: get_raw_security_descriptor(obj, snapshot),
         }
     }
 }
@@ -98,11 +102,12 @@ fn process_child_objects(obj: &Object, snapshot: &ADExplorerSnapshot) -> Vec<Chi
 
     for &index in &child_indexes {
         if let Some(child_obj) = snapshot.snapshot.objects.get(index) {
+            if is_noise_object(child_obj) {
+                continue;
+            }
             child_objects.push(ChildObject {
-                object_identifier: child_obj
-                    .get_object_identifier()
-                    .unwrap_or("ERR_UNKNOWN".to_string()),
-                object_type: type_string(child_obj),
+                object_identifier: get_sid(child_obj, snapshot),
+                object_type: type_string(child_obj, snapshot),
             });
         }
     }
@@ -128,7 +133,7 @@ impl OUProperties {
             .and_then(AttributeValue::as_string)
             .map(|v| v.clone())
             .unwrap_or_default();
-        let domain = ldap2domain(&distinguished_name).to_uppercase();
+        let domain = get_domain(&distinguished_name, snapshot).to_uppercase();
         let name = obj
             .get_first("name")
             .and_then(AttributeValue::as_string)
@@ -144,10 +149,7 @@ impl OUProperties {
                 .get_first("description")
                 .and_then(AttributeValue::as_string)
                 .map(|v| v.clone()),
-            whencreated: obj
-                .get_first("whenCreated")
-                .and_then(AttributeValue::as_unix_timestamp)
-                .unwrap_or(0),
+            whencreated: get_when_created(obj),
             blocksinheritance: obj
                 .get_first("gPOptions")
                 .and_then(AttributeValue::as_integer)
@@ -174,27 +176,44 @@ pub struct ChildObject {
 }
 
 impl Link {
-    fn parse_guid(input: &str) -> IResult<&str, String> {
-        map(delimited(char('{'), is_not("}"), char('}')), |s: &str| {
-            s.to_uppercase()
-        })(input)
+    // gPLinkOptions: bit 0 disables the link, bit 1 enforces it
+    const DISABLED: u32 = 1;
+    const ENFORCED: u32 = 2;
+
+    fn parse_guid(dn: &str) -> Option<String> {
+        let rdn = dn.split(',').next()?;
+        let guid = rdn.split_once('{')?.1.split_once('}')?.0;
+        Some(guid.to_uppercase())
     }
 
-    fn parse_gplink_entry(input: &str) -> IResult<&str, Link> {
+    fn parse_gplink_entry(input: &str) -> IResult<&str, Option<Link>> {
         map(
-            tuple((
-                preceded(tag("LDAP://cn="), Self::parse_guid),
-                alt((value(true, tag(";2")), value(false, opt(tag(";0"))))),
-            )),
-            |(guid, is_enforced)| Link { guid, is_enforced },
+            delimited(
+                char('['),
+                tuple((
+                    preceded(tag_no_case("LDAP://"), is_not(";]")),
+                    opt(preceded(char(';'), map_res(digit1, str::parse::<u32>))),
+                )),
+                char(']'),
+            ),
+            |(dn, options): (&str, Option<u32>)| {
+                let options = options.unwrap_or(0);
+                // Links to deleted GPOs keep a mangled DN, and disabled links grant nothing
+                if options & Self::DISABLED != 0 || dn.contains("DEL:") {
+                    return None;
+                }
+                Self::parse_guid(dn).map(|guid| Link {
+                    guid,
+                    is_enforced: options & Self::ENFORCED != 0,
+                })
+            },
         )(input)
     }
 
     fn parse_gplink(input: &str) -> IResult<&str, Vec<Link>> {
-        separated_list0(
-            char(']'),
-            delimited(char('['), Self::parse_gplink_entry, opt(char(']'))),
-        )(input)
+        map(many0(Self::parse_gplink_entry), |links| {
+            links.into_iter().flatten().collect()
+        })(input.trim())
     }
 
     pub fn from_gplink(gplink: &str) -> Vec<Link> {
@@ -205,10 +224,96 @@ impl Link {
     }
 }
 
-fn process_links(obj: &Object) -> Vec<Link> {
+pub(super) fn process_links(obj: &Object) -> Vec<Link> {
     obj.get("gPLink")
         .and_then(|values| values.first())
         .and_then(AttributeValue::as_string)
         .map(|gplink| Link::from_gplink(gplink))
         .unwrap_or_default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::bloodhound::test_utils::{
+        classes, snapshot_with, string, user_object, with_attributes, DOMAIN_DN, DOMAIN_SID,
+    };
+
+    #[test]
+    fn test_noise_children_skipped() {
+        let ou = Object::from_attributes(vec![
+            ("objectClass", classes(&["top", "organizationalUnit"])),
+            (
+                "distinguishedName",
+                vec![string(&format!("OU=Staff,{}", DOMAIN_DN))],
+            ),
+        ]);
+        let noise = Object::from_attributes(vec![
+            ("objectClass", classes(&["top", "orclContext"])),
+            (
+                "distinguishedName",
+                vec![string(&format!("CN=$ORCL$,OU=Staff,{}", DOMAIN_DN))],
+            ),
+        ]);
+        let alice = with_attributes(
+            user_object("alice", 1100, 0x200),
+            vec![(
+                "distinguishedName",
+                vec![string(&format!("CN=alice,OU=Staff,{}", DOMAIN_DN))],
+            )],
+        );
+
+        let snapshot = snapshot_with(vec![ou, noise, alice]);
+        let children = process_child_objects(&snapshot.snapshot.objects[1], &snapshot);
+
+        assert_eq!(children.len(), 1);
+        assert_eq!(
+            children[0].object_identifier,
+            format!("{}-1100", DOMAIN_SID)
+        );
+    }
+
+    const GPO_A: &str = "31B2F340-016D-11D2-945F-00C04FB984F9";
+    const GPO_B: &str = "6AC1786C-016F-11D2-945F-00C04FB984F9";
+
+    fn gplink(guid: &str, options: u32) -> String {
+        format!(
+            "[LDAP://cn={{{}}},cn=policies,cn=system,DC=example,DC=com;{}]",
+            guid.to_lowercase(),
+            options
+        )
+    }
+
+    #[test]
+    fn test_gplink_enabled_and_enforced() {
+        let links = Link::from_gplink(&format!("{}{}", gplink(GPO_A, 0), gplink(GPO_B, 2)));
+
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].guid, GPO_A);
+        assert!(!links[0].is_enforced);
+        assert_eq!(links[1].guid, GPO_B);
+        assert!(links[1].is_enforced);
+    }
+
+    #[test]
+    fn test_gplink_disabled() {
+        let links = Link::from_gplink(&format!("{}{}", gplink(GPO_A, 1), gplink(GPO_B, 0)));
+
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].guid, GPO_B);
+    }
+
+    #[test]
+    fn test_gplink_disabled_and_enforced() {
+        assert!(Link::from_gplink(&gplink(GPO_A, 3)).is_empty());
+    }
+
+    #[test]
+    fn test_gplink_deleted_gpo() {
+        let deleted = format!(
+            "[LDAP://cn={{{}}}\\0ADEL:1234,cn=policies,cn=system,DC=example,DC=com;0]",
+            GPO_A
+        );
+        assert!(Link::from_gplink(&deleted).is_empty());
+    }
+}
@@ -0,0 +1,152 @@
+use crate::parser::{ADExplorerSnapshot, AttributeValue, Object};
+use crate::sid::SID;
+
+pub const DOMAIN_DN: &str = "DC=EXAMPLE,DC=COM";
+pub const DOMAIN_SID: &str = "S-1-5-21-1000-2000-3000";
+
+pub fn string(value: &str) -> AttributeValue {
+    AttributeValue::String(value.to_string())
+}
+
+pub fn sid(value: &str) -> AttributeValue {
+    AttributeValue::OctetString(value.parse::<SID>().unwrap().to_bytes())
+}
+
+pub fn classes(values: &[&str]) -> Vec<AttributeValue> {
+    values.iter().map(|v| string(v)).collect()
+}
+
+pub fn domain_object() -> Object {
+    Object::from_attributes(vec![
+        ("objectClass", classes(&["top", "domain", "domainDNS"])),
+        ("distinguishedName", vec![string(DOMAIN_DN)]),
+        ("name", vec![string("EXAMPLE")]),
+        ("objectSid", vec![sid(DOMAIN_SID)]),
+    ])
+}
+
+pub fn user_object(name: &str, rid: u32, uac: u32) -> Object {
+    Object::from_attributes(vec![
+        (
+            "objectClass",
+            classes(&["top", "person", "organizationalPerson", "user"]),
+        ),
+        (
+            "distinguishedName",
+            vec![string(&format!("CN={},CN=Users,{}", name, DOMAIN_DN))],
+        ),
+        ("name", vec![string(name)]),
+        (
+            "objectCategory",
+            vec![string(
+                "CN=Person,CN=Schema,CN=Configuration,DC=EXAMPLE,DC=COM",
+            )],
+        ),
+        ("sAMAccountName", vec![string(name)]),
+        ("sAMAccountType", vec![AttributeValue::Integer(805306368)]),
+        ("userAccountControl", vec![AttributeValue::Integer(uac)]),
+        ("objectSid", vec![sid(&format!("{}-{}", DOMAIN_SID, rid))]),
+    ])
+}
+
+pub fn computer_object(name: &str, rid: u32, uac: u32) -> Object {
+    Object::from_attributes(vec![
+        (
+            "objectClass",
+            classes(&["top", "person", "organizationalPerson", "user", "computer"]),
+        ),
+        (
+            "distinguishedName",
+            vec![string(&format!("CN={},CN=Computers,{}", name, DOMAIN_DN))],
+        ),
+        ("name", vec![string(name)]),
+        (
+            "objectCategory",
+            vec![string(
+                "CN=Computer,CN=Schema,CN=Configuration,DC=EXAMPLE,DC=COM",
+            )],
+        ),
+        ("sAMAccountName", vec![string(&format!("{}$", name))]),
+        ("sAMAccountType", vec![AttributeValue::Integer(805306369)]),
+        ("userAccountControl", vec![AttributeValue::Integer(uac)]),
+        ("objectSid", vec![sid(&format!("{}-{}", DOMAIN_SID, rid))]),
+    ])
+}
+
+pub fn group_object(name: &str, rid: u32, members: &[&str]) -> Object {
+    Object::from_attributes(vec![
+        ("objectClass", classes(&["top", "group"])),
+        (
+            "distinguishedName",
+            vec![string(&format!("CN={},CN=Users,{}", name, DOMAIN_DN))],
+        ),
+        ("name", vec![string(name)]),
+        ("objectSid", vec![sid(&format!("{}-{}", DOMAIN_SID, rid))]),
+        ("member", members.iter().map(|m| string(m)).collect()),
+    ])
+}
+
+/// Adds `attributes` to `obj`, replacing any of the same name.
+pub fn with_attributes(mut obj: Object, attributes: Vec<(&str, Vec<AttributeValue>)>) -> Object {
+    obj.attributes
+        .extend(Object::from_attributes(attributes).attributes);
+    obj
+}
+
+/// Marks `obj` with adminCount=1 and a DACL that no longer inherits, as SDProp does.
+pub fn admin_protected(obj: Object) -> Object {
+    // Self-relative descriptor header with SE_DACL_PROTECTED and no owner, group or ACLs
+    let mut descriptor = vec![1, 0];
+    descriptor.extend_from_slice(&0x9000u16.to_le_bytes());
+    descriptor.extend(vec![0u8; 16]);
+
+    with_attributes(
+        obj,
+        vec![
+            ("adminCount", vec![AttributeValue::Integer(1)]),
+            (
+                "nTSecurityDescriptor",
+                vec![AttributeValue::NTSecurityDescriptor(descriptor)],
+            ),
+        ],
+    )
+}
+
+/// Self-relative descriptor whose DACL grants GenericAll to each of `sids`.
+pub fn allow_descriptor(sids: &[&str]) -> AttributeValue {
+    allow_mask_descriptor(sids, 0x10000000)
+}
+
+/// Self-relative descriptor whose DACL grants `mask` to each of `sids`.
+pub fn allow_mask_descriptor(sids: &[&str], mask: u32) -> AttributeValue {
+    let aces: Vec<u8> = sids
+        .iter()
+        .flat_map(|value| {
+            let sid = value.parse::<SID>().unwrap().to_bytes();
+            let mut ace = vec![0, 0];
+            ace.extend_from_slice(&(8 + sid.len() as u16).to_le_bytes());
+            ace.extend_from_slice(&mask.to_le_bytes());
+            ace.extend(sid);
+            ace
+        })
+        .collect();
+
+    // SE_SELF_RELATIVE | SE_DACL_PRESENT, with the DACL right after the header
+    let mut descriptor = vec![1, 0];
+    descriptor.extend_from_slice(&0x8004u16.to_le_bytes());
+    descriptor.extend_from_slice(&[0u8; 12]);
+    descriptor.extend_from_slice(&20u32.to_le_bytes());
+    descriptor.extend_from_slice(&[2, 0]);
+    descriptor.extend_from_slice(&(8 + aces.len() as u16).to_le_bytes());
+    descriptor.extend_from_slice(&(sids.len() as u16).to_le_bytes());
+    descriptor.extend_from_slice(&0u16.to_le_bytes());
+    descriptor.extend(aces);
+    AttributeValue::NTSecurityDescriptor(descriptor)
+}
+
+/// Builds a snapshot containing the domain object followed by `objects`.
+pub fn snapshot_with(objects: Vec<Object>) -> ADExplorerSnapshot {
+    let mut all = vec![domain_object()];
+    all.extend(objects);
+    ADExplorerSnapshot::from_objects(all)
+}
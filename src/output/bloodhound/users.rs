@@ -1,9 +1,14 @@
-use super::common::{get_aces, get_sid, is_acl_protected, ldap2domain};
-use super::utils::{Aces, Meta};
+use super::common::{
+    get_aces, get_admin_count, get_domain, get_primary_group_name, get_raw_security_descriptor,
+    get_sid, get_timestamp, get_when_created, is_acl_protected, is_adminsdholder_protected,
+    normalize_sid, resolve_sid, spn_host,
+};
+use super::utils::{serialize_streamed, Aces, Meta};
 use crate::output::bloodhound::common::type_string;
 use crate::parser::Cache;
-use crate::parser::{ADExplorerSnapshot, AttributeValue, Object};
-use serde::{Deserialize, Serialize};
+use crate::parser::{ADExplorerSnapshot, AttributeValue, Object, ObjectType, SamAccountType};
+use crate::security_descriptor::ACE;
+use serde::{Deserialize, Serialize, Serializer};
 use std::sync::Arc;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -18,26 +23,30 @@ impl UsersOutput {
         let snapshot = Arc::new(snapshot);
         let domain_sid = snapshot.caches.domain_sid.as_ref().unwrap().to_string();
 
-        let users: Vec<User> = snapshot
-            .snapshot
-            .objects
-            .iter()
-            .filter(|obj| Self::is_valid_user(obj, &snapshot))
+        let users: Vec<User> = Self::users(&snapshot)
             .map(|obj| User::new(obj, &snapshot, &domain_sid))
             .collect();
 
         Self {
-            meta: Meta {
-                methods: 46067,
-                r#type: "users".to_string(),
-                count: users.len() as u64,
-                version: 5,
-            },
+            meta: Meta::new("users", users.len(), 5, &snapshot),
             users,
         }
     }
 
+    /// Objects the users output covers.
+    fn users(snapshot: &ADExplorerSnapshot) -> impl Iterator<Item = &Object> {
+        snapshot
+            .snapshot
+            .objects
+            .iter()
+            .filter(move |obj| Self::is_valid_user(obj, snapshot))
+    }
+
     fn is_valid_user(obj: &Object, snapshot: &ADExplorerSnapshot) -> bool {
+        if let Some(object_type) = snapshot.type_override(obj) {
+            return matches!(object_type, ObjectType::User | ObjectType::UserDisabled);
+        }
+
         let classes = obj
             .get("objectClass")
             .map(|values| {
@@ -50,16 +59,20 @@ impl UsersOutput {
             .unwrap_or_default();
         let category = Self::get_object_category(obj, snapshot).unwrap_or_default();
 
-        let class_condition = (classes.contains(&"user".to_string()) && category == "person")
-            || classes.contains(&"ms-DS-Group-Managed-Service-Account".to_string());
+        let account_type = obj.sam_account_type();
 
-        let account_type_condition = obj
-            .get_first("sAMAccountType")
-            .and_then(AttributeValue::as_integer)
-            .map(|account_type| account_type != 805306370)
-            .unwrap_or(false);
+        // An empty or missing objectClass matches neither condition. Trust
+        // accounts are user objects too, but aren't users to BloodHound.
+        let user_condition = classes.iter().any(|class| class == "user")
+            && category == "person"
+            && obj.get_first("sAMAccountType").is_some()
+            && !account_type.is_some_and(SamAccountType::is_trust_account);
+
+        // gMSAs are machine accounts, but BloodHound treats them as users
+        let gmsa_condition = classes.iter().any(|class| class == GMSA_CLASS)
+            && account_type.is_some_and(SamAccountType::is_computer);
 
-        class_condition && account_type_condition
+        user_condition || gmsa_condition
     }
 
     fn get_object_category(obj: &Object, snapshot: &ADExplorerSnapshot) -> Option<String> {
@@ -71,6 +84,42 @@ impl UsersOutput {
     }
 }
 
+/// Serializes the same JSON as `UsersOutput`, but builds each `User` as it is
+/// written instead of holding all of them at once. The snapshot's objects
+/// stay loaded, since the records are resolved against all of them.
+pub struct UsersStream<'a> {
+    snapshot: &'a ADExplorerSnapshot,
+    meta: Meta,
+    users: Vec<&'a Object>,
+}
+
+impl<'a> UsersStream<'a> {
+    pub fn new(snapshot: &'a ADExplorerSnapshot) -> Self {
+        let users: Vec<&Object> = UsersOutput::users(snapshot).collect();
+
+        Self {
+            snapshot,
+            meta: Meta::new("users", users.len(), 5, snapshot),
+            users,
+        }
+    }
+}
+
+impl Serialize for UsersStream<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let snapshot = self.snapshot;
+        let domain_sid = snapshot.caches.domain_sid.as_ref().unwrap().to_string();
+        let users = self
+            .users
+            .iter()
+            .map(|obj| User::new(obj, snapshot, &domain_sid));
+
+        serialize_streamed(serializer, &self.meta, users)
+    }
+}
+
+pub(crate) const GMSA_CLASS: &str = "msDS-GroupManagedServiceAccount";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct User {
     #[serde(rename = "Properties")]
@@ -82,6 +131,9 @@ pub struct User {
     #[serde(rename = "PrimaryGroupSID")]
     pub primary_group_sid: String,
 
+    #[serde(rename = "PrimaryGroupName", skip_serializing_if = "Option::is_none")]
+    pub primary_group_name: Option<String>,
+
     #[serde(rename = "HasSIDHistory")]
     pub has_sid_history: Vec<SIDHistoryItem>,
 
@@ -99,24 +151,63 @@ pub struct User {
 
     #[serde(rename = "IsACLProtected")]
     is_acl_protected: bool,
+
+    #[serde(
+        rename = "RawSecurityDescriptor",
+        skip_serializing_if = "Option::is_none"
+    )]
+    raw_security_descriptor: Option<String>,
 }
 
 impl User {
     pub fn new(obj: &Object, snapshot: &ADExplorerSnapshot, domain_sid: &str) -> Self {
+        let primary_group_sid = get_primary_group_sid(obj, domain_sid);
+
         User {
             properties: UserProperties::new(obj, snapshot),
             allowed_to_delegate: process_allowed_to_delegate(obj, snapshot),
-            primary_group_sid: get_primary_group_sid(obj, domain_sid),
-            has_sid_history: process_sid_history(obj),
+            primary_group_name: get_primary_group_name(&primary_group_sid, snapshot),
+            primary_group_sid,
+            has_sid_history: process_sid_history(obj, snapshot),
             spn_targets: process_spn_targets(obj, snapshot),
-            aces: get_aces(obj, snapshot),
-            object_identifier: get_sid(obj),
+            aces: get_aces(obj, snapshot)
+                .into_iter()
+                .chain(process_gmsa_readers(obj, snapshot))
+                .collect(),
+            object_identifier: get_sid(obj, snapshot),
             is_deleted: false, // Assuming this information is not available in the snapshot
             is_acl_protected: is_acl_protected(obj),
+            raw_security_descriptor: get_raw_security_descriptor(obj, snapshot),
         }
     }
 }
 
+/// ReadGMSAPassword edges for the principals allowed by a gMSA's
+/// msDS-GroupMSAMembership descriptor to retrieve its managed password.
+fn process_gmsa_readers(obj: &Object, snapshot: &ADExplorerSnapshot) -> Vec<Aces> {
+    let Some(dacl) = obj
+        .get_first("msDS-GroupMSAMembership")
+        .and_then(AttributeValue::as_nt_security_descriptor)
+        .and_then(|sd| sd.dacl)
+    else {
+        return Vec::new();
+    };
+
+    dacl.aces
+        .iter()
+        .filter(|ace| matches!(ace, ACE::AccessAllowed(_) | ACE::AccessAllowedObject(_)))
+        .filter_map(|ace| {
+            let principal = snapshot.get_sid(ace.sid())?;
+            Some(Aces {
+                principal_sid: normalize_sid(ace.sid().to_string(), snapshot),
+                principal_type: type_string(principal, snapshot),
+                right_name: "ReadGMSAPassword".to_string(),
+                is_inherited: false,
+            })
+        })
+        .collect()
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserProperties {
     pub domain: String,
@@ -139,7 +230,11 @@ pub struct UserProperties {
     pub hasspn: bool,
     pub displayname: Option<String>,
     pub admincount: bool,
+    pub protected: bool,
+    /// Raw SIDs from sIDHistory, see `HasSIDHistory` for the resolved principals
     pub sidhistory: Vec<String>,
+    pub asreproastable: bool,
+    pub kerberoastable: bool,
 }
 
 impl UserProperties {
@@ -149,13 +244,24 @@ impl UserProperties {
             .and_then(AttributeValue::as_string)
             .map(|v| v.clone())
             .unwrap_or_default();
-        let domain = ldap2domain(&distinguished_name).to_uppercase();
+        let domain = get_domain(&distinguished_name, snapshot).to_uppercase();
         let name = obj
             .get_first("name")
             .and_then(AttributeValue::as_string)
             .map(|v| v.clone())
             .unwrap_or_default();
 
+        let uac = obj
+            .get_first("userAccountControl")
+            .and_then(AttributeValue::as_integer)
+            .unwrap_or(0);
+        let enabled = uac & 0x00000002 == 0;
+        let dontreqpreauth = uac & 0x00400000 != 0;
+        let hasspn = !obj
+            .get("servicePrincipalName")
+            .map(|values| values.is_empty())
+            .unwrap_or(true);
+
         UserProperties {
             domain: domain.clone(),
             name: format!("{}@{}", name.to_uppercase(), domain),
@@ -165,50 +271,17 @@ impl UserProperties {
                 .get_first("description")
                 .and_then(AttributeValue::as_string)
                 .map(|v| v.clone()),
-            whencreated: obj
-                .get_first("whenCreated")
-                .and_then(AttributeValue::as_unix_timestamp)
-                .unwrap_or(0),
-            sensitive: obj
-                .get_first("isSensitiveAccount")
-                .and_then(AttributeValue::as_boolean)
-                .unwrap_or(false),
-            dontreqpreauth: obj
-                .get_first("doesNotRequirePreAuth")
-                .and_then(AttributeValue::as_boolean)
-                .unwrap_or(false),
-            passwordnotreqd: obj
-                .get_first("passwordNotRequired")
-                .and_then(AttributeValue::as_boolean)
-                .unwrap_or(false),
-            unconstraineddelegation: obj
-                .get_first("trustToDelegateComputer")
-                .and_then(AttributeValue::as_boolean)
-                .unwrap_or(false),
-            pwdneverexpires: obj
-                .get_first("passwordNeverExpires")
-                .and_then(AttributeValue::as_boolean)
-                .unwrap_or(false),
-            enabled: !obj
-                .get_first("accountDisabled")
-                .and_then(AttributeValue::as_boolean)
-                .unwrap_or(false),
-            trustedtoauth: obj
-                .get_first("trustedToAuthForDelegation")
-                .and_then(AttributeValue::as_boolean)
-                .unwrap_or(false),
-            lastlogon: obj
-                .get_first("lastLogon")
-                .and_then(AttributeValue::as_unix_timestamp)
-                .unwrap_or(0),
-            lastlogontimestamp: obj
-                .get_first("lastLogonTimestamp")
-                .and_then(AttributeValue::as_unix_timestamp)
-                .unwrap_or(-1),
-            pwdlastset: obj
-                .get_first("pwdLastSet")
-                .and_then(AttributeValue::as_unix_timestamp)
-                .unwrap_or(0),
+            whencreated: get_when_created(obj),
+            sensitive: uac & 0x00100000 != 0,
+            dontreqpreauth,
+            passwordnotreqd: uac & 0x00000020 != 0,
+            unconstraineddelegation: uac & 0x00080000 != 0,
+            pwdneverexpires: uac & 0x00010000 != 0,
+            enabled,
+            trustedtoauth: uac & 0x01000000 != 0,
+            lastlogon: get_timestamp(obj, "lastLogon"),
+            lastlogontimestamp: get_timestamp(obj, "lastLogonTimestamp"),
+            pwdlastset: get_timestamp(obj, "pwdLastSet"),
             serviceprincipalnames: obj
                 .get("servicePrincipalName")
                 .map(|values| {
@@ -219,18 +292,13 @@ impl UserProperties {
                         .collect()
                 })
                 .unwrap_or_default(),
-            hasspn: !obj
-                .get("servicePrincipalName")
-                .map(|values| values.is_empty())
-                .unwrap_or(true),
+            hasspn,
             displayname: obj
                 .get_first("displayName")
                 .and_then(AttributeValue::as_string)
                 .map(|v| v.clone()),
-            admincount: obj
-                .get_first("adminCount")
-                .and_then(AttributeValue::as_boolean)
-                .unwrap_or(false),
+            admincount: get_admin_count(obj),
+            protected: is_adminsdholder_protected(obj),
             sidhistory: obj
                 .get("sIDHistory")
                 .map(|values| {
@@ -241,6 +309,8 @@ impl UserProperties {
                         .collect()
                 })
                 .unwrap_or_default(),
+            asreproastable: dontreqpreauth && enabled,
+            kerberoastable: hasspn && enabled && !obj.has_attribute_class("computer"),
         }
     }
 }
@@ -279,14 +349,12 @@ fn process_allowed_to_delegate(
                 .iter()
                 .filter_map(AttributeValue::as_string)
                 .flat_map(|host| {
-                    let target = host.split('/').nth(1).unwrap_or(host);
+                    let target = spn_host(host);
 
-                    if let Some(target_obj) = snapshot.get_computer(&target) {
+                    if let Some(target_obj) = snapshot.resolve_host(target, obj) {
                         vec![DelegationTarget {
-                            object_identifier: target_obj
-                                .get_object_identifier()
-                                .unwrap_or("ERR_UNKNOWN".to_string()),
-                            object_type: type_string(target_obj),
+                            object_identifier: get_sid(target_obj, snapshot),
+                            object_type: type_string(target_obj, snapshot),
                         }]
                     } else if target.contains('.') {
                         vec![DelegationTarget {
@@ -294,7 +362,6 @@ fn process_allowed_to_delegate(
                             object_type: "Computer".to_string(),
                         }]
                     } else {
-                        eprintln!("Invalid delegation target: {}", host);
                         vec![]
                     }
                 })
@@ -312,15 +379,22 @@ fn get_primary_group_sid(obj: &Object, domain_sid: &str) -> String {
     format!("{}-{}", domain_sid, group_id)
 }
 
-fn process_sid_history(obj: &Object) -> Vec<SIDHistoryItem> {
+/// The `sidhistory` property entries resolved to the principals they name.
+/// SIDs from other domains can't be resolved, so they keep the raw SID and are
+/// assumed to be the same kind of principal as `obj`.
+fn process_sid_history(obj: &Object, snapshot: &ADExplorerSnapshot) -> Vec<SIDHistoryItem> {
     obj.get("sIDHistory")
         .map(|values| {
             values
                 .iter()
                 .filter_map(AttributeValue::as_sid)
-                .map(|sid| SIDHistoryItem {
-                    object_identifier: sid.to_string(),
-                    object_type: "User".to_string(),
+                .map(|sid| {
+                    let (object_identifier, object_type) = resolve_sid(&sid, snapshot)
+                        .unwrap_or_else(|| (sid.to_string(), "User".to_string()));
+                    SIDHistoryItem {
+                        object_identifier,
+                        object_type,
+                    }
                 })
                 .collect()
         })
@@ -373,7 +447,7 @@ pub fn process_spn_targets(obj: &Object, snapshot: &ADExplorerSnapshot) -> Vec<S
                             } else if target.contains('.') {
                                 target.to_uppercase()
                             } else {
-                                eprintln!("Invalid SPN target: {} - {}", spn, target);
+                                log::warn!(target: "spn", "Invalid SPN target: {} - {}", spn, target);
                                 return None;
                             };
 
@@ -393,3 +467,251 @@ pub fn process_spn_targets(obj: &Object, snapshot: &ADExplorerSnapshot) -> Vec<S
         })
         .unwrap_or_default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::bloodhound::test_utils::{
+        admin_protected, allow_descriptor, classes, computer_object, group_object, sid,
+        snapshot_with, string, user_object, with_attributes, DOMAIN_SID,
+    };
+
+    fn with_spn(obj: Object) -> Object {
+        with_attributes(
+            obj,
+            vec![("servicePrincipalName", vec![string("HTTP/web.example.com")])],
+        )
+    }
+
+    fn roastable(obj: Object) -> (bool, bool) {
+        let snapshot = snapshot_with(vec![obj]);
+        let props = UserProperties::new(&snapshot.snapshot.objects[1], &snapshot);
+        (props.asreproastable, props.kerberoastable)
+    }
+
+    #[test]
+    fn test_asreproastable() {
+        // DONT_REQ_PREAUTH on an enabled account
+        assert_eq!(roastable(user_object("a", 1100, 0x00400200)), (true, false));
+        // DONT_REQ_PREAUTH on a disabled account
+        assert_eq!(
+            roastable(user_object("b", 1101, 0x00400202)),
+            (false, false)
+        );
+        // Pre-authentication required
+        assert_eq!(
+            roastable(user_object("c", 1102, 0x00000200)),
+            (false, false)
+        );
+    }
+
+    #[test]
+    fn test_kerberoastable() {
+        assert_eq!(
+            roastable(with_spn(user_object("a", 1100, 0x00000200))),
+            (false, true)
+        );
+        assert_eq!(
+            roastable(with_spn(user_object("b", 1101, 0x00000202))),
+            (false, false)
+        );
+        assert_eq!(
+            roastable(with_spn(user_object("c", 1102, 0x00400200))),
+            (true, true)
+        );
+    }
+
+    #[test]
+    fn test_domain_sid_override() {
+        let mut snapshot = ADExplorerSnapshot::from_objects(vec![user_object("a", 1100, 0x200)]);
+        assert!(snapshot.set_domain_sid("not-a-sid").is_err());
+        snapshot.set_domain_sid("S-1-5-21-1-2-3").unwrap();
+
+        let output = UsersOutput::new(&snapshot);
+        assert_eq!(output.users[0].properties.domainsid, "S-1-5-21-1-2-3");
+    }
+
+    #[test]
+    fn test_computer_is_not_kerberoastable() {
+        let computer = with_attributes(
+            with_spn(user_object("ws01", 1103, 0x00001000)),
+            vec![(
+                "objectClass",
+                classes(&["top", "person", "organizationalPerson", "user", "computer"]),
+            )],
+        );
+        assert_eq!(roastable(computer), (false, false));
+    }
+
+    #[test]
+    fn test_user_adminsdholder_protected() {
+        let snapshot = snapshot_with(vec![
+            admin_protected(user_object("admin", 1100, 0x200)),
+            user_object("alice", 1101, 0x200),
+        ]);
+        let objects = &snapshot.snapshot.objects;
+        let admin = UserProperties::new(&objects[1], &snapshot);
+        let alice = UserProperties::new(&objects[2], &snapshot);
+
+        assert!(admin.admincount && admin.protected);
+        assert!(!alice.admincount && !alice.protected);
+    }
+
+    #[test]
+    fn test_delegation_to_user_service_account() {
+        let frontend = with_attributes(
+            user_object("svc_frontend", 1100, 0x1000200),
+            vec![(
+                "msDS-AllowedToDelegateTo",
+                vec![string("MSSQLSvc/svc_sql:1433")],
+            )],
+        );
+        let snapshot = snapshot_with(vec![frontend, user_object("svc_sql", 1101, 0x200)]);
+        let targets = process_allowed_to_delegate(&snapshot.snapshot.objects[1], &snapshot);
+
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].object_identifier, format!("{}-1101", DOMAIN_SID));
+        assert_eq!(targets[0].object_type, "User");
+    }
+
+    #[test]
+    fn test_raw_security_descriptor_opt_in() {
+        let mut snapshot = snapshot_with(vec![admin_protected(user_object("admin", 1100, 0x200))]);
+        let obj = &snapshot.snapshot.objects[1];
+        let json = serde_json::to_value(User::new(obj, &snapshot, DOMAIN_SID)).unwrap();
+        assert!(json.get("RawSecurityDescriptor").is_none());
+
+        snapshot.include_raw_sd = true;
+        let obj = &snapshot.snapshot.objects[1];
+        let json = serde_json::to_value(User::new(obj, &snapshot, DOMAIN_SID)).unwrap();
+        assert_eq!(
+            json["RawSecurityDescriptor"],
+            "AQAAkAAAAAAAAAAAAAAAAAAAAAA="
+        );
+    }
+
+    #[test]
+    fn test_domain_from_header_server() {
+        let alice = with_attributes(
+            user_object("alice", 1100, 0x200),
+            vec![("distinguishedName", vec![string("CN=alice,CN=Users")])],
+        );
+        let mut snapshot = snapshot_with(vec![alice]);
+        snapshot.snapshot.header.server = "dc01.example.com".to_string();
+        let properties = UserProperties::new(&snapshot.snapshot.objects[1], &snapshot);
+
+        assert_eq!(properties.domain, "EXAMPLE.COM");
+        assert_eq!(properties.name, "ALICE@EXAMPLE.COM");
+    }
+
+    #[test]
+    fn test_primary_group_name() {
+        let admin = with_attributes(
+            user_object("admin", 1100, 0x200),
+            vec![("primaryGroupID", vec![AttributeValue::Integer(512)])],
+        );
+        let snapshot = snapshot_with(vec![
+            admin,
+            user_object("alice", 1101, 0x200),
+            group_object("Domain Admins", 512, &[]),
+        ]);
+        let admin = User::new(&snapshot.snapshot.objects[1], &snapshot, DOMAIN_SID);
+        let alice = User::new(&snapshot.snapshot.objects[2], &snapshot, DOMAIN_SID);
+
+        assert_eq!(admin.primary_group_sid, format!("{}-512", DOMAIN_SID));
+        assert_eq!(admin.primary_group_name.as_deref(), Some("Domain Admins"));
+        assert_eq!(alice.primary_group_name, None);
+    }
+
+    #[test]
+    fn test_sid_history_resolves_types() {
+        let old_group_sid = format!("{}-1300", DOMAIN_SID);
+        let foreign_sid = "S-1-5-21-7000-8000-9000-1100";
+        let migrated = with_attributes(
+            user_object("migrated", 1100, 0x200),
+            vec![("sIDHistory", vec![sid(&old_group_sid), sid(foreign_sid)])],
+        );
+        let snapshot = snapshot_with(vec![migrated, group_object("Legacy", 1300, &[])]);
+        let user = User::new(&snapshot.snapshot.objects[1], &snapshot, DOMAIN_SID);
+
+        assert_eq!(
+            user.properties.sidhistory,
+            vec![old_group_sid.clone(), foreign_sid.to_string()]
+        );
+        assert_eq!(user.has_sid_history.len(), 2);
+        assert_eq!(user.has_sid_history[0].object_identifier, old_group_sid);
+        assert_eq!(user.has_sid_history[0].object_type, "Group");
+        assert_eq!(user.has_sid_history[1].object_identifier, foreign_sid);
+        assert_eq!(user.has_sid_history[1].object_type, "User");
+    }
+
+    #[test]
+    fn test_user_filter_class_edge_cases() {
+        let no_classes = with_attributes(
+            user_object("ghost", 1800, 0x200),
+            vec![("objectClass", vec![])],
+        );
+
+        let gmsa = with_attributes(
+            user_object("svc_web$", 1801, 0x1000),
+            vec![
+                (
+                    "objectClass",
+                    classes(&[
+                        "top",
+                        "person",
+                        "organizationalPerson",
+                        "user",
+                        "computer",
+                        GMSA_CLASS,
+                    ]),
+                ),
+                ("sAMAccountType", vec![AttributeValue::Integer(805306369)]),
+            ],
+        );
+
+        let snapshot = snapshot_with(vec![no_classes, gmsa]);
+        let objects = &snapshot.snapshot.objects;
+        assert!(!UsersOutput::is_valid_user(&objects[1], &snapshot));
+        assert!(UsersOutput::is_valid_user(&objects[2], &snapshot));
+    }
+
+    #[test]
+    fn test_read_gmsa_password() {
+        let web_sid = format!("{}-1901", DOMAIN_SID);
+        let gmsa = with_attributes(
+            user_object("svc_web$", 1900, 0x1000),
+            vec![
+                (
+                    "objectClass",
+                    classes(&[
+                        "top",
+                        "person",
+                        "organizationalPerson",
+                        "user",
+                        "computer",
+                        GMSA_CLASS,
+                    ]),
+                ),
+                ("sAMAccountType", vec![AttributeValue::Integer(805306369)]),
+                (
+                    "msDS-GroupMSAMembership",
+                    vec![allow_descriptor(&[&web_sid, "S-1-5-21-9-9-9-1000"])],
+                ),
+            ],
+        );
+        let snapshot = snapshot_with(vec![gmsa, computer_object("WEB01", 1901, 0x1000)]);
+
+        let user = User::new(&snapshot.snapshot.objects[1], &snapshot, DOMAIN_SID);
+        let readers: Vec<&Aces> = user
+            .aces
+            .iter()
+            .filter(|ace| ace.right_name == "ReadGMSAPassword")
+            .collect();
+
+        // The principal from another domain isn't in the snapshot
+        assert_eq!(readers.len(), 1);
+        assert_eq!(readers[0].principal_sid, web_sid);
+        assert_eq!(readers[0].principal_type, "Computer");
+    }
+}
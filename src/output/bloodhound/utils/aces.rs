@@ -1,11 +1,15 @@
 use crate::{
-    output::bloodhound::common::type_string,
+    guid::GUID,
+    output::bloodhound::common::{normalize_sid, type_string, Laps},
     parser::{ADExplorerSnapshot, ObjectType},
-    security_descriptor::{ACEFlags, ACEGuid, AccessMask, ACE, SDDL},
+    security_descriptor::{ACEFlags, ACEGuid, AccessMask, ControlFlag, ACE, SDDL},
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
+/// Owners the default security descriptor assigns, skipped when defaulted.
+const DEFAULT_OWNER_SIDS: &[&str] = &["S-1-5-32-544", "S-1-5-18"];
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Aces {
     #[serde(rename = "PrincipalSID")]
@@ -26,14 +30,23 @@ impl Aces {
         sd: &SDDL,
         snapshot: &ADExplorerSnapshot,
         object_type: &ObjectType,
-        has_laps: bool,
+        class_guids: &[GUID],
+        laps: Laps,
     ) -> Vec<Self> {
+        let laps_password_guids = laps.password_guids(snapshot);
         let mut aces = Vec::new();
-        if let Some(owner) = &sd.owner_sid {
+        // A defaulted owner of BUILTIN\Administrators or SYSTEM comes from the
+        // default descriptor every object gets, not a deliberate grant
+        let default_owner = sd.control_flags.is_set(ControlFlag::OD)
+            && sd
+                .owner_sid
+                .as_ref()
+                .is_some_and(|owner| DEFAULT_OWNER_SIDS.contains(&owner.to_string().as_str()));
+        if let Some(owner) = sd.owner_sid.as_ref().filter(|_| !default_owner) {
             if let Some(obj) = snapshot.get_sid(owner) {
                 let ace = Aces {
-                    principal_sid: owner.to_string(),
-                    principal_type: type_string(obj),
+                    principal_sid: normalize_sid(owner.to_string(), snapshot),
+                    principal_type: type_string(obj, snapshot),
                     right_name: "Owns".to_string(),
                     is_inherited: false,
                 };
@@ -47,14 +60,17 @@ impl Aces {
             for ace in dacl
                 .aces
                 .iter()
-                .filter(|ace| !matches!(ace, ACE::AccessDenied(_) | ACE::AccessDeniedObject(_)))
+                // Callback ACEs only apply when their condition holds, so like
+                // SharpHound only unconditional grants become edges
+                .filter(|ace| matches!(ace, ACE::AccessAllowed(_) | ACE::AccessAllowedObject(_)))
+                .filter(|ace| Self::applies_to_object(ace, class_guids))
             {
-                let rights = Self::rights(ace, object_type, has_laps);
+                let rights = Self::rights(ace, object_type, laps.any(), &laps_password_guids);
                 if let Some(target_obj) = snapshot.get_sid(&ace.sid()) {
                     for right in rights {
                         let ace = Aces {
-                            principal_sid: ace.sid().to_string(),
-                            principal_type: type_string(target_obj),
+                            principal_sid: normalize_sid(ace.sid().to_string(), snapshot),
+                            principal_type: type_string(target_obj, snapshot),
                             right_name: right,
                             is_inherited: Self::is_inherited(ace),
                         };
@@ -67,19 +83,59 @@ impl Aces {
         aces
     }
 
+    /// Like SharpHound, inherit-only ACEs are only there for children, and an
+    /// inherited ACE limited to a class other than the object's own doesn't
+    /// apply. `class_guids` holds the schemaIDGUIDs of the object's classes;
+    /// when none resolved, the class check is skipped.
+    fn applies_to_object(ace: &ACE, class_guids: &[GUID]) -> bool {
+        let flags = &ace.header().ace_flags;
+        if flags.is_set(ACEFlags::INHERIT_ONLY_ACE) {
+            return false;
+        }
+        if !flags.is_set(ACEFlags::INHERITED_ACE) || class_guids.is_empty() {
+            return true;
+        }
+
+        match ace.inherited_object_type() {
+            Some(guid) if ACEGuid::from_guid(guid) != Some(ACEGuid::AllGuid) => {
+                class_guids.contains(guid)
+            }
+            _ => true,
+        }
+    }
+
     fn is_inherited(ace: &ACE) -> bool {
         ace.header().ace_flags.is_set(ACEFlags::INHERITED_ACE)
     }
 
-    fn rights(ace: &ACE, object_type: &ObjectType, has_laps: bool) -> HashSet<String> {
+    fn rights(
+        ace: &ACE,
+        object_type: &ObjectType,
+        has_laps: bool,
+        laps_password_guids: &[GUID],
+    ) -> HashSet<String> {
         let mut rights = HashSet::new();
         let ace_mask = ace.mask();
         let ace_type = ace.object_type_s();
+        // An ACE without an object type and one with the zero GUID are equivalent;
+        // an unrecognized object type must not be treated as either.
+        let applies_to_all = ace.applies_to_all_object_types();
+        // The LAPS attributes are added by schema extensions, so their GUIDs
+        // come from the snapshot rather than ACEGuid
+        let reads_laps_password = object_type == &ObjectType::Computer
+            && has_laps
+            && ace
+                .object_type()
+                .is_some_and(|guid| laps_password_guids.contains(guid));
 
         // GenericAll
-        if ace_mask.has_flag(AccessMask::GENERIC_ALL) {
-            if ace_type.is_none() || ace_type == Some(ACEGuid::AllGuid) {
+        if ace_mask.has_flag(AccessMask::GENERIC_ALL)
+            || ace_mask.has_flag(AccessMask::ADS_RIGHT_GENERIC_ALL)
+        {
+            if applies_to_all {
                 rights.insert("GenericAll".to_string());
+            } else if reads_laps_password {
+                rights.insert("ReadLAPSPassword".to_string());
             }
             return rights; // Early return to avoid other checks
         }
@@ -115,7 +171,7 @@ impl Aces {
                     Some(ACEGuid::DSReplicationGetChangesInFilteredSet) => {
                         rights.insert("GetChangesInFilteredSet".to_string());
                     }
-                    Some(ACEGuid::AllGuid) | None => {
+                    _ if applies_to_all => {
                         rights.insert("AllExtendedRights".to_string());
                     }
                     _ => {}
@@ -124,16 +180,16 @@ impl Aces {
                     Some(ACEGuid::UserForceChangePassword) => {
                         rights.insert("ForceChangePassword".to_string());
                     }
-                    Some(ACEGuid::AllGuid) | None => {
+                    _ if applies_to_all => {
                         rights.insert("AllExtendedRights".to_string());
                     }
                     _ => {}
                 },
                 ObjectType::Computer => {
-                    if has_laps {
-                        if ace_type.is_none() || ace_type == Some(ACEGuid::AllGuid) {
-                            rights.insert("AllExtendedRights".to_string());
-                        }
+                    if has_laps && applies_to_all {
+                        rights.insert("AllExtendedRights".to_string());
+                    } else if reads_laps_password {
+                        rights.insert("ReadLAPSPassword".to_string());
                     }
                 }
                 _ => {}
@@ -146,7 +202,7 @@ impl Aces {
         {
             match object_type {
                 ObjectType::User | ObjectType::Group | ObjectType::Computer | ObjectType::GPO => {
-                    if ace_type.is_none() || ace_type == Some(ACEGuid::AllGuid) {
+                    if applies_to_all {
                         rights.insert("GenericWrite".to_string());
                     }
                 }
@@ -176,3 +232,222 @@ impl Aces {
         rights
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::bloodhound::test_utils::{
+        classes, group_object, sid, snapshot_with, DOMAIN_SID,
+    };
+    use crate::parser::Object;
+    use crate::security_descriptor::{
+        ACEHeader, ACEType, AccessAllowedAce, AccessAllowedObjectAce,
+    };
+    use crate::sid::SID;
+
+    fn allowed(mask: u32) -> ACE {
+        ACE::AccessAllowed(AccessAllowedAce {
+            header: ACEHeader {
+                ace_type: ACEType::AccessAllowed,
+                ace_flags: ACEFlags::new(0),
+                ace_size: 0,
+            },
+            mask: AccessMask::new(mask),
+            sid: "S-1-5-21-1-2-3-1100".parse().unwrap(),
+        })
+    }
+
+    fn allowed_object(mask: u32, object_type: &str) -> ACE {
+        ACE::AccessAllowedObject(AccessAllowedObjectAce {
+            header: ACEHeader {
+                ace_type: ACEType::AccessAllowedObject,
+                ace_flags: ACEFlags::new(0),
+                ace_size: 0,
+            },
+            mask: AccessMask::new(mask),
+            flags: 1,
+            object_type: Some(object_type.parse::<GUID>().unwrap()),
+            inherited_object_type: None,
+            sid: "S-1-5-21-1-2-3-1100".parse().unwrap(),
+        })
+    }
+
+    const ZERO_GUID: &str = "00000000-0000-0000-0000-000000000000";
+    // ms-DS-Supported-Encryption-Types, which none of the rights look at
+    const UNRELATED_GUID: &str = "20119867-1d04-4ab7-9371-cfc3d5df0afd";
+
+    fn sorted(rights: HashSet<String>) -> Vec<String> {
+        let mut rights: Vec<String> = rights.into_iter().collect();
+        rights.sort();
+        rights
+    }
+
+    #[test]
+    fn test_zero_guid_matches_missing_object_type() {
+        let masks = [
+            AccessMask::GENERIC_ALL,
+            AccessMask::ADS_RIGHT_DS_CONTROL_ACCESS,
+            AccessMask::ADS_RIGHT_DS_WRITE_PROP,
+        ];
+        let types = [ObjectType::User, ObjectType::Domain, ObjectType::Group];
+
+        for mask in masks {
+            for object_type in &types {
+                let plain = Aces::rights(&allowed(mask), object_type, false, &[]);
+                let zero = Aces::rights(&allowed_object(mask, ZERO_GUID), object_type, false, &[]);
+                assert_eq!(sorted(plain), sorted(zero));
+            }
+        }
+
+        let zero = allowed_object(AccessMask::GENERIC_ALL, ZERO_GUID);
+        assert_eq!(
+            sorted(Aces::rights(&zero, &ObjectType::User, false, &[])),
+            vec!["GenericAll".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_unrecognized_object_type_is_scoped() {
+        let generic_all = allowed_object(AccessMask::GENERIC_ALL, UNRELATED_GUID);
+        assert!(Aces::rights(&generic_all, &ObjectType::User, false, &[]).is_empty());
+
+        let extended = allowed_object(AccessMask::ADS_RIGHT_DS_CONTROL_ACCESS, UNRELATED_GUID);
+        assert!(Aces::rights(&extended, &ObjectType::Domain, false, &[]).is_empty());
+
+        let write = allowed_object(AccessMask::ADS_RIGHT_DS_WRITE_PROP, UNRELATED_GUID);
+        assert!(Aces::rights(&write, &ObjectType::User, false, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_recognized_object_type() {
+        // Self-membership write on a group
+        let write_member = allowed_object(
+            AccessMask::ADS_RIGHT_DS_WRITE_PROP,
+            "bf9679c0-0de6-11d0-a285-00aa003049e2",
+        );
+        assert_eq!(
+            sorted(Aces::rights(&write_member, &ObjectType::Group, false, &[])),
+            vec!["AddMember".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_gpo_takeover_rights() {
+        let full_control = allowed(AccessMask::ADS_RIGHT_GENERIC_ALL);
+        assert_eq!(
+            sorted(Aces::rights(&full_control, &ObjectType::GPO, false, &[])),
+            vec!["GenericAll".to_string()]
+        );
+
+        // GPMC's "Edit settings, delete, modify security"
+        let edit_security = allowed(0x000F00FF);
+        assert_eq!(
+            sorted(Aces::rights(&edit_security, &ObjectType::GPO, false, &[])),
+            vec![
+                "GenericWrite".to_string(),
+                "WriteDacl".to_string(),
+                "WriteOwner".to_string()
+            ]
+        );
+    }
+
+    fn owned_by(owner: &str, control: u16) -> SDDL {
+        // Self-relative descriptor holding only an owner, right after the header
+        let mut bytes = vec![1, 0];
+        bytes.extend_from_slice(&(0x8000 | control).to_le_bytes());
+        bytes.extend_from_slice(&20u32.to_le_bytes());
+        bytes.extend_from_slice(&[0u8; 12]);
+        bytes.extend(owner.parse::<SID>().unwrap().to_bytes());
+        SDDL::from_bytes(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_defaulted_builtin_owner_skipped() {
+        let administrators = Object::from_attributes(vec![
+            ("objectClass", classes(&["top", "group"])),
+            ("objectSid", vec![sid("S-1-5-32-544")]),
+        ]);
+        let snapshot = snapshot_with(vec![administrators, group_object("Staff", 1100, &[])]);
+        let owns = |sd: SDDL| {
+            Aces::from_security_descriptor(&sd, &snapshot, &ObjectType::Group, &[], Laps::default())
+                .iter()
+                .filter(|ace| ace.right_name == "Owns")
+                .count()
+        };
+        let od = ControlFlag::OD as u16;
+
+        assert_eq!(owns(owned_by("S-1-5-32-544", od)), 0);
+        assert_eq!(owns(owned_by("S-1-5-32-544", 0)), 1);
+        assert_eq!(owns(owned_by(&format!("{}-1100", DOMAIN_SID), od)), 1);
+    }
+
+    #[test]
+    fn test_inheritance_rules() {
+        // schemaIDGUIDs of user and group
+        let user = "bf967aba-0de6-11d0-a285-00aa003049e2"
+            .parse::<GUID>()
+            .unwrap();
+        let group = "bf967a9c-0de6-11d0-a285-00aa003049e2"
+            .parse::<GUID>()
+            .unwrap();
+        let ace = |flags: u8, inherited_object_type: Option<&GUID>| {
+            ACE::AccessAllowedObject(AccessAllowedObjectAce {
+                header: ACEHeader {
+                    ace_type: ACEType::AccessAllowedObject,
+                    ace_flags: ACEFlags::new(flags),
+                    ace_size: 0,
+                },
+                mask: AccessMask::new(AccessMask::GENERIC_ALL),
+                flags: 2,
+                object_type: None,
+                inherited_object_type: inherited_object_type.cloned(),
+                sid: "S-1-5-21-1-2-3-1100".parse().unwrap(),
+            })
+        };
+        let classes = [user.clone()];
+
+        assert!(!Aces::applies_to_object(
+            &ace(ACEFlags::INHERIT_ONLY_ACE, None),
+            &classes
+        ));
+        assert!(Aces::applies_to_object(
+            &ace(ACEFlags::INHERITED_ACE, Some(&user)),
+            &classes
+        ));
+        assert!(!Aces::applies_to_object(
+            &ace(ACEFlags::INHERITED_ACE, Some(&group)),
+            &classes
+        ));
+        assert!(Aces::applies_to_object(
+            &ace(ACEFlags::INHERITED_ACE, None),
+            &classes
+        ));
+        // Explicit ACEs apply whatever children they are also inherited by
+        assert!(Aces::applies_to_object(&ace(0, Some(&group)), &classes));
+        // Without the object's classes there is nothing to compare against
+        assert!(Aces::applies_to_object(
+            &ace(ACEFlags::INHERITED_ACE, Some(&group)),
+            &[]
+        ));
+    }
+
+    #[test]
+    fn test_read_laps_password() {
+        // Per-forest schemaIDGUID of ms-Mcs-AdmPwd
+        let laps_guid = "0c8e7a12-5d0b-4f1e-9b3a-6e2d4c8f1a75";
+        let laps_guids = [laps_guid.parse::<GUID>().unwrap()];
+
+        for mask in [
+            AccessMask::ADS_RIGHT_DS_CONTROL_ACCESS,
+            AccessMask::GENERIC_ALL,
+        ] {
+            let ace = allowed_object(mask, laps_guid);
+            assert_eq!(
+                sorted(Aces::rights(&ace, &ObjectType::Computer, true, &laps_guids)),
+                vec!["ReadLAPSPassword".to_string()]
+            );
+            assert!(Aces::rights(&ace, &ObjectType::Computer, false, &laps_guids).is_empty());
+            assert!(Aces::rights(&ace, &ObjectType::User, true, &laps_guids).is_empty());
+        }
+    }
+}
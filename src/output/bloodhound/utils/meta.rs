@@ -1,3 +1,5 @@
+use super::super::constants::DEFAULT_METHODS;
+use crate::parser::ADExplorerSnapshot;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -6,4 +8,72 @@ pub struct Meta {
     pub r#type: String,
     pub count: u64,
     pub version: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collected: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<SnapshotSource>,
+}
+
+impl Meta {
+    pub fn new(r#type: &str, count: usize, version: u8, snapshot: &ADExplorerSnapshot) -> Self {
+        Meta {
+            methods: DEFAULT_METHODS,
+            r#type: r#type.to_string(),
+            count: count as u64,
+            version,
+            collected: collected(snapshot),
+            snapshot: SnapshotSource::new(snapshot),
+        }
+    }
+}
+
+/// Unix time the data was collected, or None when the header carries no
+/// capture time.
+pub fn collected(snapshot: &ADExplorerSnapshot) -> Option<i64> {
+    (snapshot.snapshot.header.filetime != 0).then(|| snapshot.captured_at().timestamp())
+}
+
+/// Where the data came from, added to `meta` when `include_snapshot_meta` is set.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotSource {
+    pub server: String,
+    /// Unix time the snapshot was taken.
+    pub capturedat: i64,
+}
+
+impl SnapshotSource {
+    pub fn new(snapshot: &ADExplorerSnapshot) -> Option<Self> {
+        snapshot.include_snapshot_meta.then(|| SnapshotSource {
+            server: snapshot.server().to_string(),
+            capturedat: snapshot.captured_at().timestamp(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::bloodhound::test_utils::snapshot_with;
+
+    #[test]
+    fn test_meta_shape() {
+        let mut snapshot = snapshot_with(vec![]);
+        assert_eq!(
+            serde_json::to_value(Meta::new("users", 2, 5, &snapshot)).unwrap(),
+            serde_json::json!({"methods": 46067, "type": "users", "count": 2, "version": 5})
+        );
+
+        // 2024-03-01T12:30:45Z
+        snapshot.snapshot.header.filetime = (1_709_296_245 + 11_644_473_600) * 10_000_000;
+        assert_eq!(
+            serde_json::to_value(Meta::new("users", 2, 5, &snapshot)).unwrap(),
+            serde_json::json!({
+                "methods": 46067,
+                "type": "users",
+                "count": 2,
+                "version": 5,
+                "collected": 1_709_296_245,
+            })
+        );
+    }
 }
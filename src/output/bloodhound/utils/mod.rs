@@ -1,5 +1,7 @@
 mod aces;
 mod meta;
+mod streamed;
 
 pub use aces::Aces;
 pub use meta::Meta;
+pub use streamed::serialize_streamed;
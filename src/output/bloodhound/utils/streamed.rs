@@ -0,0 +1,30 @@
+use super::Meta;
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use std::cell::Cell;
+
+/// Serializes `{"meta": .., "data": [..]}` like the collected outputs, taking
+/// each entry from `data` as it is written rather than from a `Vec`.
+pub fn serialize_streamed<S, I>(serializer: S, meta: &Meta, data: I) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    I: Iterator,
+    I::Item: Serialize,
+{
+    let mut map = serializer.serialize_map(Some(2))?;
+    map.serialize_entry("meta", meta)?;
+    map.serialize_entry("data", &Data(Cell::new(Some(data))))?;
+    map.end()
+}
+
+struct Data<I>(Cell<Option<I>>);
+
+impl<I> Serialize for Data<I>
+where
+    I: Iterator,
+    I::Item: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.take().into_iter().flatten())
+    }
+}
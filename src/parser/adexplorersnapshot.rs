@@ -1,26 +1,94 @@
+use super::parser::readable_value;
+use super::split_dn;
+use super::AttributeValue;
 use super::Caches;
 use super::Object;
+use super::ObjectType;
+use super::Schema;
 use super::Snapshot;
+use crate::guid::GUID;
 use crate::parser::cache::Cache;
 use crate::sid::SID;
-use serde::Serialize;
-use std::io::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Result};
 use std::path::Path;
 
+const CACHE_MAGIC: [u8; 8] = *b"ADXSNAPC";
+// Bump whenever the layout of `Snapshot` or `Caches` changes, and whenever
+// the same layout is filled differently, such as new keys in a cache.
+const CACHE_VERSION: u32 = 12;
+
+#[derive(Serialize, Deserialize)]
+struct CacheHeader {
+    magic: [u8; 8],
+    version: u32,
+}
+
 #[derive(Debug, Serialize)]
 pub struct ADExplorerSnapshot {
     pub snapshot: Snapshot,
     #[serde(skip_serializing)]
     pub caches: Caches,
+    /// Whether outputs carry each object's raw nTSecurityDescriptor.
+    #[serde(skip_serializing)]
+    pub include_raw_sd: bool,
+    /// Whether output `meta` blocks name the server and capture time.
+    #[serde(skip_serializing)]
+    pub include_snapshot_meta: bool,
+    #[serde(skip_serializing)]
+    type_overrides: HashMap<String, ObjectType>,
 }
 
 impl ADExplorerSnapshot {
+    fn new(snapshot: Snapshot, caches: Caches) -> Self {
+        ADExplorerSnapshot {
+            snapshot,
+            caches,
+            include_raw_sd: false,
+            include_snapshot_meta: false,
+            type_overrides: HashMap::new(),
+        }
+    }
+
+    /// Treats objects of `class` as `object_type`, for custom schema classes
+    /// the built-in mapping doesn't know.
+    pub fn with_type_override(mut self, class: &str, object_type: ObjectType) -> Self {
+        self.type_overrides
+            .insert(class.to_lowercase(), object_type);
+        self
+    }
+
+    /// Type of `obj`, honouring any overrides before the built-in mapping.
+    pub fn get_type(&self, obj: &Object) -> ObjectType {
+        self.type_override(obj).unwrap_or_else(|| obj.get_type())
+    }
+
+    /// The overridden type of `obj`, if one of its classes has an override.
+    /// Output filters check this before their own class and account type
+    /// tests, so an override can move an object out of a category too.
+    pub fn type_override(&self, obj: &Object) -> Option<ObjectType> {
+        if self.type_overrides.is_empty() {
+            return None;
+        }
+
+        obj.get_attribute_classes().and_then(|classes| {
+            classes
+                .iter()
+                .find_map(|class| self.type_overrides.get(&class.to_lowercase()))
+                .copied()
+        })
+    }
+
     pub fn snapshot_from_file<P: AsRef<Path>>(path: P) -> Result<ADExplorerSnapshot> {
         let snapshot = Snapshot::snapshot_from_file(path)?;
         let mut caches = Caches::new();
         caches.build_caches(&snapshot);
 
-        Ok(ADExplorerSnapshot { snapshot, caches })
+        Ok(ADExplorerSnapshot::new(snapshot, caches))
     }
 
     pub fn snapshot_from_memory(snapshot: impl AsRef<[u8]>) -> Result<ADExplorerSnapshot> {
@@ -28,7 +96,97 @@ impl ADExplorerSnapshot {
         let mut caches = Caches::new();
         caches.build_caches(&snapshot);
 
-        Ok(ADExplorerSnapshot { snapshot, caches })
+        Ok(ADExplorerSnapshot::new(snapshot, caches))
+    }
+
+    /// Builds the caches for an already parsed snapshot, such as one read
+    /// with `SnapshotReader::into_snapshot_with_progress`.
+    pub fn from_snapshot(snapshot: Snapshot) -> ADExplorerSnapshot {
+        let mut caches = Caches::new();
+        caches.build_caches(&snapshot);
+
+        ADExplorerSnapshot::new(snapshot, caches)
+    }
+
+    /// Fetches a snapshot from `url` and parses it. `file://` URLs are read in
+    /// place; anything else is downloaded to a temporary file first so the
+    /// parser can still memory-map it.
+    #[cfg(feature = "http")]
+    pub fn snapshot_from_url(url: &str) -> Result<ADExplorerSnapshot> {
+        use rand::Rng;
+
+        if let Some(path) = url.strip_prefix("file://") {
+            return Self::snapshot_from_file(path);
+        }
+
+        let suffix: String = rand::thread_rng()
+            .sample_iter(&rand::distributions::Alphanumeric)
+            .take(10)
+            .map(char::from)
+            .collect();
+        let temp_path = std::env::temp_dir().join(format!("snapshot-{}.dat", suffix));
+
+        let download = || -> Result<()> {
+            let mut response = reqwest::blocking::get(url)
+                .and_then(|response| response.error_for_status())
+                .map_err(Error::other)?;
+            let mut file = std::fs::File::create(&temp_path)?;
+            response.copy_to(&mut file).map_err(Error::other)?;
+            Ok(())
+        };
+
+        let result = download().and_then(|_| Self::snapshot_from_file(&temp_path));
+        let _ = std::fs::remove_file(&temp_path);
+        result
+    }
+
+    /// Writes the parsed snapshot and its caches to `path` so later runs can
+    /// skip parsing with `load_cache`.
+    pub fn save_cache<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let header = CacheHeader {
+            magic: CACHE_MAGIC,
+            version: CACHE_VERSION,
+        };
+        bincode::serialize_into(&mut writer, &header).map_err(Error::other)?;
+        bincode::serialize_into(&mut writer, &(&self.snapshot, &self.caches))
+            .map_err(Error::other)?;
+        Ok(())
+    }
+
+    pub fn load_cache<P: AsRef<Path>>(path: P) -> Result<ADExplorerSnapshot> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let header: CacheHeader = bincode::deserialize_from(&mut reader)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        if header.magic != CACHE_MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a snapshot cache"));
+        }
+        if header.version != CACHE_VERSION {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Stale snapshot cache: version {}, expected {}",
+                    header.version, CACHE_VERSION
+                ),
+            ));
+        }
+
+        let (snapshot, caches) = bincode::deserialize_from(&mut reader)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        Ok(ADExplorerSnapshot::new(snapshot, caches))
+    }
+
+    /// Overrides the domain SID used by the outputs, for snapshots that don't
+    /// contain a domain object.
+    pub fn set_domain_sid(&mut self, domain_sid: &str) -> Result<()> {
+        let sid = domain_sid.parse::<SID>().map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid domain SID {}: {}", domain_sid, e),
+            )
+        })?;
+        self.caches.domain_sid = Some(sid);
+        Ok(())
     }
 
     pub fn build_caches(&mut self, caches: Caches) {
@@ -41,18 +199,364 @@ impl ADExplorerSnapshot {
         self.snapshot.objects.get(*root_domain_index)
     }
 
+    pub fn get_domains(&self) -> impl Iterator<Item = &Object> {
+        self.caches
+            .domains
+            .iter()
+            .filter_map(|idx| self.snapshot.objects.get(*idx))
+    }
+
     pub fn get_sid(&self, sid: &SID) -> Option<&Object> {
         let sid_index = self.caches.sid_cache.get(sid)?;
         self.snapshot.objects.get(*sid_index)
     }
 
+    pub fn get_guid(&self, guid: &GUID) -> Option<&Object> {
+        let guid_index = self.caches.guid_cache.get(guid)?;
+        self.snapshot.objects.get(*guid_index)
+    }
+
     pub fn get_computer(&self, computer: &str) -> Option<&Object> {
         let computer_index = self.caches.computer_cache.get(&computer.to_string())?;
         self.snapshot.objects.get(*computer_index)
     }
 
+    pub fn get_sam_account(&self, sam_account_name: &str) -> Option<&Object> {
+        let sam_index = self.caches.sam_cache.get(sam_account_name)?;
+        self.snapshot.objects.get(*sam_index)
+    }
+
+    /// Resolves the host part of an SPN found on `from`, trying computer names,
+    /// then account names, then the short name of a DNS host name. Short names
+    /// only match objects in the domain of `from`, and only when one does.
+    pub fn resolve_host(&self, host: &str, from: &Object) -> Option<&Object> {
+        let host = host.split(':').next().unwrap_or(host);
+        let short_name = host.split('.').next().unwrap_or(host);
+
+        self.get_computer(host)
+            .or_else(|| self.get_sam_account(host))
+            .or_else(|| self.get_sam_account(&format!("{}$", host)))
+            .or_else(|| self.resolve_short_name(short_name, from))
+    }
+
+    fn resolve_short_name(&self, short_name: &str, from: &Object) -> Option<&Object> {
+        let domain = dn_domain(from);
+        let mut candidates = self
+            .caches
+            .short_name_cache
+            .get(short_name)
+            .iter()
+            .filter_map(|idx| self.snapshot.objects.get(*idx))
+            .filter(|obj| dn_domain(obj) == domain);
+
+        let candidate = candidates.next()?;
+        if candidates.next().is_some() {
+            log::warn!(target: "host", "Ambiguous host name: {}", short_name);
+            return None;
+        }
+        Some(candidate)
+    }
+
+    /// Forest functional level, which lives on the Partitions crossRefContainer
+    /// rather than on the domain object.
+    pub fn forest_functional_level(&self) -> Option<String> {
+        self.snapshot
+            .objects
+            .iter()
+            .find(|obj| obj.has_attribute_class("crossRefContainer"))
+            .and_then(Object::functional_level)
+    }
+
+    /// ADSTYPE the snapshot schema declares for attribute `name`, which decides
+    /// how its values were decoded.
+    pub fn attribute_ads_type(&self, name: &str) -> Option<u32> {
+        self.snapshot
+            .properties
+            .iter()
+            .find(|property| property.prop_name.eq_ignore_ascii_case(name))
+            .map(|property| property.ads_type)
+    }
+
+    /// When the snapshot was taken, from its header.
+    pub fn captured_at(&self) -> DateTime<Utc> {
+        self.snapshot.header.captured_at()
+    }
+
+    /// Name of the domain controller the snapshot was taken from.
+    pub fn server(&self) -> &str {
+        &self.snapshot.header.server
+    }
+
+    /// Typed access to the schema classes and attributes in the snapshot.
+    pub fn schema(&self) -> Schema<'_> {
+        Schema::new(self)
+    }
+
     pub fn get_dn(&self, dn: &str) -> Option<&Object> {
         let dn_index = self.caches.dn_cache.get(&dn.to_string())?;
         self.snapshot.objects.get(*dn_index)
     }
+
+    /// Finds the object named by a SID, GUID, DN or sAMAccountName.
+    pub fn find_object(&self, identifier: &str) -> Option<&Object> {
+        if let Ok(sid) = identifier.parse::<SID>() {
+            return self.get_sid(&sid);
+        }
+        if let Ok(guid) = identifier.parse::<GUID>() {
+            return self.get_guid(&guid);
+        }
+
+        self.get_dn(identifier)
+            .or_else(|| self.get_sam_account(identifier))
+    }
+
+    /// Every attribute of the object named by `identifier`, decoded into
+    /// readable JSON for triage. Single values are unwrapped from their list.
+    pub fn dump_object(&self, identifier: &str) -> Option<Value> {
+        let obj = self.find_object(identifier)?;
+        let names = self.snapshot.attribute_names();
+        let attributes: BTreeMap<&str, Value> = obj
+            .attributes
+            .iter()
+            .map(|(name, attribute)| {
+                let mut values: Vec<Value> = attribute
+                    .values
+                    .iter()
+                    .map(|value| readable_value(name, value))
+                    .collect();
+                let value = if values.len() == 1 {
+                    values.remove(0)
+                } else {
+                    Value::Array(values)
+                };
+                (names.get(name).copied().unwrap_or(name), value)
+            })
+            .collect();
+
+        Some(json!(attributes))
+    }
+}
+
+/// The DC= components of the object's DN, uppercased.
+fn dn_domain(obj: &Object) -> Vec<String> {
+    obj.get_first("distinguishedName")
+        .and_then(AttributeValue::as_str)
+        .map(|dn| {
+            split_dn(dn)
+                .into_iter()
+                .filter(|rdn| rdn.len() > 3 && rdn[..3].eq_ignore_ascii_case("dc="))
+                .map(str::to_uppercase)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+impl ADExplorerSnapshot {
+    pub(crate) fn from_objects(objects: Vec<Object>) -> ADExplorerSnapshot {
+        let snapshot = Snapshot::from_objects(objects);
+        let mut caches = Caches::new();
+        caches.build_caches(&snapshot);
+
+        ADExplorerSnapshot::new(snapshot, caches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::bloodhound::test_utils::{
+        classes, computer_object, group_object, snapshot_with, string, user_object,
+        with_attributes, DOMAIN_SID,
+    };
+    use crate::output::bloodhound::{ComputersOutput, GroupsOutput, UsersOutput};
+    use crate::parser::Property;
+
+    // Header with no objects whose properties section starts right after it,
+    // followed by empty property, class and right tables.
+    #[cfg(feature = "http")]
+    fn empty_snapshot_bytes(server: &str) -> Vec<u8> {
+        let mut bytes = b"win-ad-ob\0".to_vec();
+        bytes.extend_from_slice(&0i32.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend(vec![0u8; 520]);
+        let mut server_field: Vec<u8> = server.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        server_field.resize(520, 0);
+        bytes.extend(server_field);
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&0x43eu32.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&0i32.to_le_bytes());
+        bytes.extend(vec![0u8; 12]);
+        bytes
+    }
+
+    #[test]
+    #[cfg(feature = "http")]
+    fn test_snapshot_from_file_url() {
+        let path = std::env::temp_dir().join("adexplorersnapshot-file-url-test.dat");
+        std::fs::write(&path, empty_snapshot_bytes("dc01.example.com")).unwrap();
+
+        let url = format!("file://{}", path.display());
+        let snapshot = ADExplorerSnapshot::snapshot_from_url(&url).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(snapshot.snapshot.header.server, "dc01.example.com");
+        assert!(snapshot.snapshot.objects.is_empty());
+    }
+
+    #[test]
+    fn test_cache_round_trip() {
+        let path = std::env::temp_dir().join("adexplorersnapshot-cache-round-trip.bin");
+        let snapshot = snapshot_with(vec![user_object("alice", 1100, 0x200)]);
+        snapshot.save_cache(&path).unwrap();
+
+        let loaded = ADExplorerSnapshot::load_cache(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.snapshot.objects.len(), 2);
+        assert_eq!(
+            loaded.caches.domain_sid.as_ref().map(SID::to_string),
+            Some(DOMAIN_SID.to_string())
+        );
+        let alice_sid = format!("{}-1100", DOMAIN_SID).parse().unwrap();
+        let alice = loaded.get_sid(&alice_sid).unwrap();
+        assert_eq!(
+            alice.get_first("name").and_then(|v| v.as_str()),
+            Some("alice")
+        );
+    }
+
+    #[test]
+    fn test_cache_rejects_stale_version() {
+        let path = std::env::temp_dir().join("adexplorersnapshot-cache-stale.bin");
+        let header = CacheHeader {
+            magic: CACHE_MAGIC,
+            version: CACHE_VERSION + 1,
+        };
+        std::fs::write(&path, bincode::serialize(&header).unwrap()).unwrap();
+
+        let err = ADExplorerSnapshot::load_cache(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_attribute_ads_type() {
+        let mut snapshot = snapshot_with(vec![]);
+        snapshot.snapshot.properties.push(Property {
+            prop_name: "userAccountControl".to_string(),
+            unk1: 0,
+            ads_type: 7,
+            dn: "CN=User-Account-Control,CN=Schema,CN=Configuration,DC=EXAMPLE,DC=COM".to_string(),
+            schema_id_guid: "bf967a68-0de6-11d0-a285-00aa003049e2".parse().unwrap(),
+            attribute_security_guid: "00000000-0000-0000-0000-000000000000".parse().unwrap(),
+        });
+
+        assert_eq!(snapshot.attribute_ads_type("userAccountControl"), Some(7));
+        assert_eq!(snapshot.attribute_ads_type("useraccountcontrol"), Some(7));
+        assert_eq!(snapshot.attribute_ads_type("missingAttribute"), None);
+    }
+
+    #[test]
+    fn test_forest_functional_level() {
+        let partitions = Object::from_attributes(vec![
+            ("objectClass", classes(&["top", "crossRefContainer"])),
+            (
+                "distinguishedName",
+                vec![string("CN=Partitions,CN=Configuration,DC=EXAMPLE,DC=COM")],
+            ),
+            ("msDS-Behavior-Version", vec![AttributeValue::Integer(6)]),
+        ]);
+        let snapshot = snapshot_with(vec![partitions]);
+
+        assert_eq!(
+            snapshot.forest_functional_level().as_deref(),
+            Some("2012 R2")
+        );
+        assert_eq!(snapshot_with(vec![]).forest_functional_level(), None);
+    }
+
+    #[test]
+    fn test_type_override() {
+        let team = Object::from_attributes(vec![
+            ("objectClass", classes(&["top", "contosoTeam"])),
+            (
+                "distinguishedName",
+                vec![string("CN=Red Team,DC=EXAMPLE,DC=COM")],
+            ),
+        ]);
+        let snapshot = snapshot_with(vec![team]);
+        let team = &snapshot.snapshot.objects[1];
+        assert_eq!(snapshot.get_type(team), ObjectType::Unknown);
+
+        let snapshot = snapshot.with_type_override("contosoteam", ObjectType::Group);
+        let team = &snapshot.snapshot.objects[1];
+        assert_eq!(snapshot.get_type(team), ObjectType::Group);
+    }
+
+    #[test]
+    fn test_type_override_leaves_builtin_category() {
+        let role = with_attributes(
+            group_object("Red Role", 1200, &[]),
+            vec![("objectClass", classes(&["top", "group", "contosoRole"]))],
+        );
+        let kiosk = with_attributes(
+            computer_object("KIOSK01", 1201, 0x1000),
+            vec![(
+                "objectClass",
+                classes(&["top", "person", "user", "computer", "contosoKiosk"]),
+            )],
+        );
+        let snapshot = snapshot_with(vec![role, kiosk])
+            .with_type_override("contosorole", ObjectType::Container)
+            .with_type_override("contosokiosk", ObjectType::User);
+        let count = |output: serde_json::Value| output["meta"]["count"].clone();
+
+        assert_eq!(
+            count(serde_json::to_value(GroupsOutput::new(&snapshot)).unwrap()),
+            0
+        );
+        assert_eq!(
+            count(serde_json::to_value(ComputersOutput::new(&snapshot)).unwrap()),
+            0
+        );
+        assert_eq!(
+            count(serde_json::to_value(UsersOutput::new(&snapshot)).unwrap()),
+            1
+        );
+    }
+
+    #[test]
+    fn test_dump_object_by_dn() {
+        let mut snapshot = snapshot_with(vec![user_object("alice", 1104, 0x200)]);
+        snapshot.snapshot.properties = [
+            "objectClass",
+            "sAMAccountName",
+            "userAccountControl",
+            "objectSid",
+        ]
+        .iter()
+        .map(|name| Property::from_name(name))
+        .collect();
+
+        let dump = snapshot
+            .dump_object("cn=alice,cn=Users,DC=EXAMPLE,DC=COM")
+            .unwrap();
+        assert_eq!(dump["sAMAccountName"], "alice");
+        assert_eq!(dump["userAccountControl"], 0x200);
+        assert_eq!(dump["objectSid"], format!("{}-1104", DOMAIN_SID));
+        assert_eq!(dump["objectClass"].as_array().unwrap().len(), 4);
+
+        assert_eq!(
+            snapshot.dump_object(&format!("{}-1104", DOMAIN_SID)),
+            Some(dump)
+        );
+        assert!(snapshot
+            .dump_object("CN=bob,CN=Users,DC=EXAMPLE,DC=COM")
+            .is_none());
+    }
 }
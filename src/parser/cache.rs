@@ -1,16 +1,20 @@
 use crate::guid::GUID;
-use crate::parser::{AttributeValue, Object};
+use crate::parser::{AttributeValue, Object, SamAccountType};
 use crate::sid::SID;
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 use super::parser::Snapshot;
 
+const LEGACY_LAPS_PASSWORD_ATTRIBUTES: &[&str] = &["ms-Mcs-AdmPwd"];
+const WINDOWS_LAPS_PASSWORD_ATTRIBUTES: &[&str] = &["msLAPS-Password", "msLAPS-EncryptedPassword"];
+
 pub trait Cache<K, V> {
     fn get(&self, key: &K) -> Option<&V>;
     fn insert(&mut self, key: K, value: V);
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct SIDCache {
     cache: HashMap<SID, usize>,
 }
@@ -25,7 +29,7 @@ impl Cache<SID, usize> for SIDCache {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DNCache {
     cache: HashMap<String, usize>,
 }
@@ -41,15 +45,19 @@ impl DNCache {
 
     pub fn get_ou_children(&self, ou_dn: &str) -> Vec<usize> {
         let ou_dn_upper = ou_dn.to_uppercase();
-        let ou_prefix = format!(",{}", ou_dn_upper);
-        let mut children = HashSet::new();
+        let ou_rdns = split_dn(&ou_dn_upper);
+        // Ordered, so outputs list children the same way on every run
+        let mut children = BTreeSet::new();
 
         for (dn, &index) in &self.cache {
-            if dn != &ou_dn_upper && (dn.ends_with(&ou_prefix) || dn == &ou_dn_upper) {
-                let relative_dn = &dn[..dn.len() - ou_dn_upper.len()];
-                if relative_dn.matches(',').count() <= 1 {
-                    children.insert(index);
-                }
+            // A suffix match alone isn't enough: the comma before the suffix
+            // may be escaped, and escaped commas don't add depth
+            if !dn.ends_with(&ou_dn_upper) {
+                continue;
+            }
+            let rdns = split_dn(dn);
+            if rdns.len() == ou_rdns.len() + 1 && rdns[1..] == ou_rdns[..] {
+                children.insert(index);
             }
         }
 
@@ -57,7 +65,28 @@ impl DNCache {
     }
 }
 
-#[derive(Debug)]
+/// Splits a DN into its RDNs, keeping backslash-escaped commas inside them.
+pub(crate) fn split_dn(dn: &str) -> Vec<&str> {
+    let mut rdns = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+
+    for (i, c) in dn.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ',' => {
+                rdns.push(dn[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    rdns.push(dn[start..].trim());
+    rdns
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ComputerCache {
     cache: HashMap<String, usize>,
 }
@@ -76,7 +105,61 @@ impl ComputerCache {
     }
 }
 
-#[derive(Debug)]
+/// Objects by the first label of their host names and by their account name
+/// without the trailing `$`. Accounts in different domains may share one.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShortNameCache {
+    cache: HashMap<String, Vec<usize>>,
+}
+
+impl ShortNameCache {
+    pub fn get(&self, key: &str) -> &[usize] {
+        self.cache
+            .get(&key.to_uppercase())
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn insert(&mut self, key: &str, value: usize) {
+        let key = key.split('.').next().unwrap_or(key).trim_end_matches('$');
+        let entries = self.cache.entry(key.to_uppercase()).or_default();
+        if !entries.contains(&value) {
+            entries.push(value);
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GUIDCache {
+    cache: HashMap<GUID, usize>,
+}
+
+impl Cache<GUID, usize> for GUIDCache {
+    fn get(&self, key: &GUID) -> Option<&usize> {
+        self.cache.get(key)
+    }
+
+    fn insert(&mut self, key: GUID, value: usize) {
+        self.cache.insert(key, value);
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SAMCache {
+    cache: HashMap<String, usize>,
+}
+
+impl SAMCache {
+    pub fn get(&self, key: &str) -> Option<&usize> {
+        self.cache.get(&key.to_uppercase())
+    }
+
+    pub fn insert(&mut self, key: String, value: usize) {
+        self.cache.insert(key.to_uppercase(), value);
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ObjectTypeGUIDCache {
     cache: HashMap<usize, GUID>,
 }
@@ -91,7 +174,7 @@ impl Cache<usize, GUID> for ObjectTypeGUIDCache {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ClassCache {
     cache: HashMap<String, usize>,
 }
@@ -106,7 +189,7 @@ impl Cache<String, usize> for ClassCache {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct DomainCache {
     domains: HashMap<String, usize>,
 }
@@ -129,7 +212,7 @@ impl DomainCache {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct CertificateTemplateCache {
     templates: HashMap<String, HashSet<String>>,
 }
@@ -153,17 +236,27 @@ impl CertificateTemplateCache {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Caches {
     pub root_domain: Option<String>,
     pub domain_sid: Option<SID>,
+    /// Every domain in the snapshot, as indices of their naming context heads
+    pub domains: Vec<usize>,
     pub sid_cache: SIDCache,
+    pub guid_cache: GUIDCache,
     pub dn_cache: DNCache,
     pub computer_cache: ComputerCache,
+    pub sam_cache: SAMCache,
+    pub short_name_cache: ShortNameCache,
     pub object_type_guid_cache: ObjectTypeGUIDCache,
     pub class_cache: ClassCache,
     pub domain_cache: DomainCache,
     pub domain_controllers: Vec<usize>,
+    pub read_only_domain_controllers: Vec<usize>,
+    /// schemaIDGUIDs of the legacy LAPS password attribute, which differ per forest
+    pub legacy_laps_password_guids: Vec<GUID>,
+    /// schemaIDGUIDs of the Windows LAPS password attributes
+    pub windows_laps_password_guids: Vec<GUID>,
     pub certificate_template_cache: CertificateTemplateCache,
 }
 
@@ -172,15 +265,25 @@ impl Caches {
         Caches {
             root_domain: None,
             domain_sid: None,
+            domains: Vec::new(),
             sid_cache: SIDCache {
                 cache: HashMap::new(),
             },
+            guid_cache: GUIDCache {
+                cache: HashMap::new(),
+            },
             dn_cache: DNCache {
                 cache: HashMap::new(),
             },
             computer_cache: ComputerCache {
                 cache: HashMap::new(),
             },
+            sam_cache: SAMCache {
+                cache: HashMap::new(),
+            },
+            short_name_cache: ShortNameCache {
+                cache: HashMap::new(),
+            },
             object_type_guid_cache: ObjectTypeGUIDCache {
                 cache: HashMap::new(),
             },
@@ -189,6 +292,9 @@ impl Caches {
             },
             domain_cache: DomainCache::new(),
             domain_controllers: Vec::new(),
+            read_only_domain_controllers: Vec::new(),
+            legacy_laps_password_guids: Vec::new(),
+            windows_laps_password_guids: Vec::new(),
             certificate_template_cache: CertificateTemplateCache::new(),
         }
     }
@@ -196,9 +302,27 @@ impl Caches {
     pub fn build_caches(&mut self, snapshot: &Snapshot) {
         self.build_object_type_guid_cache(snapshot);
         self.build_class_cache(snapshot);
+        self.build_laps_password_guids(snapshot);
         self.build_object_caches(snapshot);
     }
 
+    fn build_laps_password_guids(&mut self, snapshot: &Snapshot) {
+        let guids = |names: &[&str]| -> Vec<GUID> {
+            snapshot
+                .properties
+                .iter()
+                .filter(|p| {
+                    names
+                        .iter()
+                        .any(|name| p.prop_name.eq_ignore_ascii_case(name))
+                })
+                .map(|p| p.schema_id_guid.clone())
+                .collect()
+        };
+        self.legacy_laps_password_guids = guids(LEGACY_LAPS_PASSWORD_ATTRIBUTES);
+        self.windows_laps_password_guids = guids(WINDOWS_LAPS_PASSWORD_ATTRIBUTES);
+    }
+
     fn build_object_type_guid_cache(&mut self, snapshot: &Snapshot) {
         // Build cache from classes
         for (i, cl) in snapshot.classes.iter().enumerate() {
@@ -234,12 +358,30 @@ impl Caches {
     }
 
     fn build_object_caches(&mut self, snapshot: &Snapshot) {
+        // Rank of the current root domain candidate, lowest wins
+        let mut root_domain_rank = None;
         for (idx, obj) in snapshot.objects.iter().enumerate() {
             // Build SID cache
             let sid = Self::get_object_sid(obj);
             if let Some(sid) = sid.as_ref() {
                 self.sid_cache.insert(sid.clone(), idx);
             }
+            // A trust names the trusted domain's SID, so ACEs and memberships
+            // referencing that domain resolve to it unless the domain object
+            // itself is in the snapshot
+            if let Some(trust_sid) = Self::get_trust_sid(obj) {
+                if self.sid_cache.get(&trust_sid).is_none() {
+                    self.sid_cache.insert(trust_sid, idx);
+                }
+            }
+
+            // Build GUID cache
+            if let Some(guid) = obj
+                .get_first("objectGUID")
+                .and_then(AttributeValue::as_guid)
+            {
+                self.guid_cache.insert(guid, idx);
+            }
 
             // Build DN cache
             if let Some(dn) = Self::get_object_dn(obj) {
@@ -252,8 +394,23 @@ impl Caches {
 
                 // Build Domain cache
                 if lowercase_classes.contains(&"domain".to_string()) {
-                    self.root_domain = Self::get_object_dn(obj);
-                    self.domain_sid = sid.clone();
+                    // The forest root heads its naming context and has the
+                    // shortest DN of the domains that do. Ties keep the first,
+                    // so the choice doesn't depend on object order.
+                    let is_nc_head = obj.is_naming_context_head();
+                    if is_nc_head {
+                        self.domains.push(idx);
+                    }
+                    let dn = Self::get_object_dn(obj);
+                    let rank = (
+                        !is_nc_head,
+                        dn.as_deref().map_or(usize::MAX, |dn| split_dn(dn).len()),
+                    );
+                    if root_domain_rank.is_none_or(|root_rank| rank < root_rank) {
+                        self.root_domain = dn;
+                        self.domain_sid = sid.clone();
+                        root_domain_rank = Some(rank);
+                    }
                     if let Some(dn) = Self::get_object_dn(obj) {
                         self.domain_cache.insert_domain(dn, idx);
                     }
@@ -289,32 +446,59 @@ impl Caches {
 
             if Self::is_computer(obj) {
                 if let Some(dnshostname) = Self::get_object_dnshostname(obj) {
+                    self.short_name_cache.insert(&dnshostname, idx);
                     self.computer_cache.insert(dnshostname, idx);
                 }
                 if let Some(name) = Self::get_object_name(obj) {
+                    self.short_name_cache.insert(&name, idx);
                     self.computer_cache.insert(name, idx);
                 }
+                // Alternate names that SPNs and delegation targets may use
+                for hostname in obj
+                    .get("msDS-AdditionalDnsHostName")
+                    .into_iter()
+                    .flatten()
+                    .filter_map(AttributeValue::as_string)
+                {
+                    self.short_name_cache.insert(hostname, idx);
+                    self.computer_cache.insert(hostname.clone(), idx);
+                }
+            }
+
+            if let Some(sam_account_name) =
+                self.get_attribute_value::<String>(obj, "sAMAccountName")
+            {
+                self.short_name_cache.insert(&sam_account_name, idx);
+                self.sam_cache.insert(sam_account_name, idx);
             }
 
             if let Some(uac) = self.get_attribute_value::<u32>(obj, "userAccountControl") {
+                // SERVER_TRUST_ACCOUNT
                 if uac & 0x2000 == 0x2000 {
                     self.domain_controllers.push(idx);
                 }
+                // PARTIAL_SECRETS_ACCOUNT, set on RODCs instead of SERVER_TRUST_ACCOUNT
+                if uac & 0x04000000 == 0x04000000 {
+                    self.read_only_domain_controllers.push(idx);
+                }
+            }
+        }
+
+        // Snapshots without instanceType still have the root domain
+        if self.domains.is_empty() {
+            if let Some(idx) = self
+                .root_domain
+                .as_ref()
+                .and_then(|dn| self.dn_cache.get(dn))
+            {
+                self.domains.push(*idx);
             }
         }
     }
 
     fn is_computer(obj: &Object) -> bool {
-        obj.attributes
-            .get("sAMAccountType")
-            .and_then(|attr| {
-                if let Some(AttributeValue::Integer(account_type)) = attr.values.first() {
-                    Some(*account_type == 805306369)
-                } else {
-                    None
-                }
-            })
-            .unwrap_or(false)
+        obj.sam_account_type()
+            .is_some_and(SamAccountType::is_computer)
     }
 
     fn get_attribute_value<T: FromAttributeValue>(
@@ -322,16 +506,15 @@ impl Caches {
         obj: &Object,
         attr_name: &str,
     ) -> Option<T> {
-        obj.attributes.get(attr_name).and_then(|attr| {
-            attr.values
-                .first()
+        obj.get(attr_name).and_then(|attr| {
+            attr.first()
                 .and_then(|value| T::from_attribute_value(value))
         })
     }
 
     fn get_object_sid(obj: &Object) -> Option<SID> {
-        obj.attributes.get("objectSid").and_then(|attr| {
-            if let Some(AttributeValue::OctetString(octet_string)) = attr.values.first() {
+        obj.get("objectSid").and_then(|attr| {
+            if let Some(AttributeValue::OctetString(octet_string)) = attr.first() {
                 SID::from_bytes(octet_string).ok()
             } else {
                 None
@@ -339,9 +522,17 @@ impl Caches {
         })
     }
 
+    fn get_trust_sid(obj: &Object) -> Option<SID> {
+        if !obj.has_attribute_class("trustedDomain") {
+            return None;
+        }
+        obj.get_first("securityIdentifier")
+            .and_then(AttributeValue::as_sid)
+    }
+
     fn get_object_dn(obj: &Object) -> Option<String> {
-        obj.attributes.get("distinguishedName").and_then(|attr| {
-            if let Some(AttributeValue::String(dn)) = attr.values.first() {
+        obj.get("distinguishedName").and_then(|attr| {
+            if let Some(AttributeValue::String(dn)) = attr.first() {
                 Some(dn.clone())
             } else {
                 None
@@ -350,8 +541,8 @@ impl Caches {
     }
 
     fn get_object_dnshostname(obj: &Object) -> Option<String> {
-        obj.attributes.get("dNSHostName").and_then(|attr| {
-            if let Some(AttributeValue::String(hostname)) = attr.values.first() {
+        obj.get("dNSHostName").and_then(|attr| {
+            if let Some(AttributeValue::String(hostname)) = attr.first() {
                 Some(hostname.clone())
             } else {
                 None
@@ -360,8 +551,8 @@ impl Caches {
     }
 
     fn get_object_name(obj: &Object) -> Option<String> {
-        obj.attributes.get("name").and_then(|attr| {
-            if let Some(AttributeValue::String(name)) = attr.values.first() {
+        obj.get("name").and_then(|attr| {
+            if let Some(AttributeValue::String(name)) = attr.first() {
                 Some(name.clone())
             } else {
                 None
@@ -420,3 +611,124 @@ impl FromAttributeValue for Vec<String> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::output::bloodhound::test_utils::{
+        classes, sid, snapshot_with, string, DOMAIN_DN, DOMAIN_SID,
+    };
+    use crate::parser::{ADExplorerSnapshot, AttributeValue, Object, ObjectType};
+    use crate::sid::SID;
+
+    #[test]
+    fn test_split_dn_escaped_commas() {
+        assert_eq!(
+            super::split_dn(r"CN=Smith\, John,OU=Sales,DC=EXAMPLE"),
+            vec![r"CN=Smith\, John", "OU=Sales", "DC=EXAMPLE"]
+        );
+        assert_eq!(
+            super::split_dn(r"CN=Back\\,OU=Sales"),
+            vec![r"CN=Back\\", "OU=Sales"]
+        );
+    }
+
+    #[test]
+    fn test_ou_children_with_escaped_commas() {
+        let object = |dn: &str| {
+            Object::from_attributes(vec![
+                ("objectClass", classes(&["top", "user"])),
+                ("distinguishedName", vec![string(dn)]),
+            ])
+        };
+        let ou = format!("OU=Sales,{}", DOMAIN_DN);
+        let snapshot = snapshot_with(vec![
+            object(&format!(r"CN=Smith\, John,{}", ou)),
+            object(&format!(r"OU=East\, North,{}", ou)),
+            object(&format!(r"CN=Grandchild,OU=East\, North,{}", ou)),
+            object(&format!(r"CN=Not a child\,{}", ou)),
+        ]);
+
+        let mut children = snapshot.caches.dn_cache.get_ou_children(&ou);
+        children.sort();
+        assert_eq!(children, vec![1, 2]);
+    }
+
+    fn domain(dn: &str, domain_sid: &str, instance_type: u32) -> Object {
+        Object::from_attributes(vec![
+            ("objectClass", classes(&["top", "domain", "domainDNS"])),
+            ("distinguishedName", vec![string(dn)]),
+            ("objectSid", vec![sid(domain_sid)]),
+            ("instanceType", vec![AttributeValue::Integer(instance_type)]),
+        ])
+    }
+
+    #[test]
+    fn test_root_domain_prefers_naming_context_head() {
+        let snapshot = ADExplorerSnapshot::from_objects(vec![
+            domain(DOMAIN_DN, DOMAIN_SID, 5),
+            domain("DC=OTHER,DC=COM", "S-1-5-21-4000-5000-6000", 0),
+        ]);
+
+        assert_eq!(snapshot.caches.root_domain.as_deref(), Some(DOMAIN_DN));
+        assert_eq!(
+            snapshot.caches.domain_sid.as_ref().unwrap().to_string(),
+            DOMAIN_SID
+        );
+    }
+
+    #[test]
+    fn test_root_domain_is_forest_root() {
+        let child = || domain("DC=CHILD,DC=EXAMPLE,DC=COM", "S-1-5-21-4000-5000-6000", 5);
+        let root_first =
+            ADExplorerSnapshot::from_objects(vec![domain(DOMAIN_DN, DOMAIN_SID, 5), child()]);
+        let child_first =
+            ADExplorerSnapshot::from_objects(vec![child(), domain(DOMAIN_DN, DOMAIN_SID, 5)]);
+
+        for snapshot in [root_first, child_first] {
+            assert_eq!(snapshot.caches.root_domain.as_deref(), Some(DOMAIN_DN));
+            assert_eq!(
+                snapshot.caches.domain_sid.as_ref().unwrap().to_string(),
+                DOMAIN_SID
+            );
+        }
+    }
+
+    #[test]
+    fn test_every_naming_context_head_is_a_domain() {
+        let snapshot = ADExplorerSnapshot::from_objects(vec![
+            domain(DOMAIN_DN, DOMAIN_SID, 5),
+            domain("DC=CHILD,DC=EXAMPLE,DC=COM", "S-1-5-21-4000-5000-6000", 5),
+            domain("DC=OTHER,DC=COM", "S-1-5-21-7000-8000-9000", 0),
+        ]);
+        assert_eq!(snapshot.caches.domains, vec![0, 1]);
+
+        let snapshot = snapshot_with(Vec::new());
+        assert_eq!(snapshot.caches.domains, vec![0]);
+    }
+
+    #[test]
+    fn test_builtin_sid_resolves_to_group() {
+        let administrators = Object::from_attributes(vec![
+            ("objectClass", classes(&["top", "group"])),
+            (
+                "distinguishedName",
+                vec![string(&format!(
+                    "CN=Administrators,CN=Builtin,{}",
+                    DOMAIN_DN
+                ))],
+            ),
+            ("objectSid", vec![sid("S-1-5-32-544")]),
+            ("sAMAccountType", vec![AttributeValue::Integer(536870912)]),
+        ]);
+        let snapshot = snapshot_with(vec![administrators]);
+        let builtin_admins: SID = "S-1-5-32-544".parse().unwrap();
+
+        let obj = snapshot.get_sid(&builtin_admins).unwrap();
+        assert_eq!(snapshot.get_type(obj), ObjectType::Group);
+        assert_eq!(
+            obj.get_first("distinguishedName")
+                .and_then(AttributeValue::as_str),
+            Some("CN=Administrators,CN=Builtin,DC=EXAMPLE,DC=COM")
+        );
+    }
+}
@@ -1,8 +1,13 @@
 mod adexplorersnapshot;
 mod cache;
 mod parser;
+mod schema;
 
 pub use adexplorersnapshot::ADExplorerSnapshot;
+pub(crate) use cache::split_dn;
 pub use cache::{Cache, Caches};
-use parser::Snapshot;
-pub use parser::{AttributeValue, Object, ObjectType};
+pub use parser::{
+    AttributeValue, Class, Header, Object, ObjectType, ParseError, Property, Right, SamAccountType,
+    Snapshot, SnapshotReader,
+};
+pub use schema::Schema;
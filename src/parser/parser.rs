@@ -1,15 +1,18 @@
 use crate::guid::GUID;
 use crate::security_descriptor::SDDL;
 use crate::sid::SID;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use byteorder::{LittleEndian, ReadBytesExt};
-use chrono::{TimeZone, Utc};
+use chrono::{DateTime, TimeZone, Utc};
 use memmap2::Mmap;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use std::char;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::Result;
-use std::io::{Cursor, Error, ErrorKind, Read, Seek, SeekFrom};
+use std::io::{BufReader, Cursor, Error, ErrorKind, Read, Seek, SeekFrom};
 use std::path::Path;
 
 fn read_wstring_exact(reader: &mut impl Read, num_chars: usize) -> Result<String> {
@@ -49,25 +52,20 @@ fn read_next_wstring(file: &mut impl Read) -> Result<String> {
 
 fn read_wstring<T: Read>(reader: &mut T) -> Result<String> {
     let len = reader.read_u32::<LittleEndian>()? as usize;
-    let mut result = String::with_capacity(len / 2);
-    let mut word_buf = [0u8; 2];
-
-    for _ in 0..len / 2 {
-        reader.read_exact(&mut word_buf)?;
-        let word = u16::from_le_bytes(word_buf);
+    let mut buffer = vec![0u8; len / 2 * 2];
+    reader.read_exact(&mut buffer)?;
 
-        if word == 0 {
-            break;
-        }
+    let mut utf16_chars: Vec<u16> = buffer
+        .chunks_exact(2)
+        .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
+        .collect();
 
-        if let Some(ch) = char::from_u32(word as u32) {
-            result.push(ch);
-        } else {
-            result.push(char::REPLACEMENT_CHARACTER);
-        }
+    // The declared length covers the terminator, but embedded nulls are data
+    while utf16_chars.last() == Some(&0) {
+        utf16_chars.pop();
     }
 
-    Ok(result)
+    Ok(String::from_utf16_lossy(&utf16_chars))
 }
 
 fn read_guid<T: Read>(reader: &mut T) -> Result<GUID> {
@@ -81,7 +79,7 @@ fn read_guid<T: Read>(reader: &mut T) -> Result<GUID> {
     })
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Header {
     pub win_ad_sig: String,
     pub marker: i32,
@@ -97,6 +95,17 @@ pub struct Header {
 }
 
 impl Header {
+    /// When the snapshot was taken. `filetime` is a FILETIME, counting 100ns
+    /// intervals since 1601; a time before 1970 comes back as the epoch.
+    pub fn captured_at(&self) -> DateTime<Utc> {
+        let ticks = self.filetime.saturating_sub(116444736000000000);
+        DateTime::from_timestamp(
+            (ticks / 10_000_000) as i64,
+            (ticks % 10_000_000) as u32 * 100,
+        )
+        .unwrap_or_default()
+    }
+
     fn parse(reader: &mut impl Read) -> Result<Self> {
         let mut win_ad_sig = [0u8; 10];
         reader.read_exact(&mut win_ad_sig)?;
@@ -117,7 +126,7 @@ impl Header {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Property {
     pub prop_name: String,
     pub unk1: i32,
@@ -150,13 +159,13 @@ impl Property {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct MappingEntry {
     attr_index: u32,
     attr_offset: i32,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
 pub enum ObjectType {
     Computer,
     User,
@@ -166,24 +175,103 @@ pub enum ObjectType {
     OU,
     Container,
     GPO,
+    /// A trustedDomain object, which BloodHound references as the Domain it
+    /// trusts.
+    TrustedDomain,
     Unknown,
 }
 
-#[derive(Debug, Serialize)]
+/// The `sAMAccountType` values AD assigns, see MS-SAMR 2.2.1.9.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum SamAccountType {
+    GroupObject,
+    NonSecurityGroupObject,
+    AliasObject,
+    NonSecurityAliasObject,
+    UserObject,
+    MachineAccount,
+    TrustAccount,
+    AppBasicGroup,
+    AppQueryGroup,
+}
+
+impl SamAccountType {
+    pub fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0x10000000 => Some(SamAccountType::GroupObject),
+            0x10000001 => Some(SamAccountType::NonSecurityGroupObject),
+            0x20000000 => Some(SamAccountType::AliasObject),
+            0x20000001 => Some(SamAccountType::NonSecurityAliasObject),
+            0x30000000 => Some(SamAccountType::UserObject),
+            0x30000001 => Some(SamAccountType::MachineAccount),
+            0x30000002 => Some(SamAccountType::TrustAccount),
+            0x40000000 => Some(SamAccountType::AppBasicGroup),
+            0x40000001 => Some(SamAccountType::AppQueryGroup),
+            _ => None,
+        }
+    }
+
+    pub fn is_user(self) -> bool {
+        self == SamAccountType::UserObject
+    }
+
+    /// gMSAs are machine accounts too.
+    pub fn is_computer(self) -> bool {
+        self == SamAccountType::MachineAccount
+    }
+
+    /// Interdomain trust accounts, which end in `$` like computers.
+    pub fn is_trust_account(self) -> bool {
+        self == SamAccountType::TrustAccount
+    }
+
+    /// Global, universal and domain local groups, security or not.
+    pub fn is_group(self) -> bool {
+        matches!(
+            self,
+            SamAccountType::GroupObject
+                | SamAccountType::NonSecurityGroupObject
+                | SamAccountType::AliasObject
+                | SamAccountType::NonSecurityAliasObject
+        )
+    }
+}
+
+/// Attributes that were renamed between schema versions, canonical name first.
+/// Only true renames belong here: legacy LAPS and Windows LAPS attributes are
+/// distinct attributes with their own data and access rights, so they aren't
+/// aliases of each other.
+const ATTRIBUTE_ALIASES: &[&[&str]] = &[];
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Object {
     pub obj_size: u32,
     pub table_size: u32,
     mapping_table: Vec<MappingEntry>,
+    /// Keyed by the lowercased attribute name, since snapshots don't agree on
+    /// attribute name case. `Snapshot::properties` has the schema spelling.
     pub attributes: HashMap<String, Attribute>,
 }
 
 impl Object {
-    fn parse(reader: &mut (impl Read + Seek), properties: &[Property]) -> Result<Self> {
+    /// Parses the object at the reader's position. When `keep` is given, only
+    /// attributes whose property index it marks are decoded.
+    fn parse(
+        reader: &mut (impl Read + Seek),
+        properties: &[Property],
+        keep: Option<&[bool]>,
+    ) -> Result<Self> {
         let start_pos = reader.stream_position()?;
         let obj_size = reader.read_u32::<LittleEndian>()?;
+        if obj_size < 8 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("object size {} is too small", obj_size),
+            ));
+        }
         let table_size = reader.read_u32::<LittleEndian>()?;
 
-        let mapping_table = (0..table_size)
+        let mut mapping_table = (0..table_size)
             .map(|_| {
                 Ok(MappingEntry {
                     attr_index: reader.read_u32::<LittleEndian>()?,
@@ -191,6 +279,13 @@ impl Object {
                 })
             })
             .collect::<Result<Vec<_>>>()?;
+        if let Some(keep) = keep {
+            mapping_table.retain(|entry| {
+                keep.get(entry.attr_index as usize)
+                    .copied()
+                    .unwrap_or(false)
+            });
+        }
 
         let attributes = mapping_table
             .iter()
@@ -207,7 +302,7 @@ impl Object {
                 let attribute = Attribute::parse(reader, property.ads_type).ok()?;
                 reader.seek(SeekFrom::Start(current_pos)).ok()?;
 
-                Some((property.prop_name.clone(), attribute))
+                Some((property.prop_name.to_ascii_lowercase(), attribute))
             })
             .collect();
 
@@ -221,12 +316,31 @@ impl Object {
         })
     }
 
+    /// Names of the attributes the object holds, lowercased.
     pub fn get_attribute_names(&self) -> Vec<String> {
         self.attributes.keys().cloned().collect()
     }
 
+    /// Values of `attr_name`, matched case-insensitively. Attributes listed in
+    /// `ATTRIBUTE_ALIASES` are also found under any of their aliases.
     pub fn get(&self, attr_name: &str) -> Option<&Vec<AttributeValue>> {
-        self.attributes.get(attr_name).map(|attr| &attr.values)
+        if let Some(attr) = self.attributes.get(&attr_name.to_ascii_lowercase()) {
+            return Some(&attr.values);
+        }
+
+        ATTRIBUTE_ALIASES
+            .iter()
+            .find(|names| {
+                names
+                    .iter()
+                    .any(|name| name.eq_ignore_ascii_case(attr_name))
+            })
+            .and_then(|names| {
+                names
+                    .iter()
+                    .find_map(|alias| self.attributes.get(&alias.to_ascii_lowercase()))
+            })
+            .map(|attr| &attr.values)
     }
 
     pub fn get_first(&self, attr_name: &str) -> Option<&AttributeValue> {
@@ -243,18 +357,80 @@ impl Object {
         )
     }
 
+    pub fn sam_account_type(&self) -> Option<SamAccountType> {
+        self.get_first("sAMAccountType")
+            .and_then(AttributeValue::as_integer)
+            .and_then(SamAccountType::from_u32)
+    }
+
     pub fn has_attribute_class(&self, class: &str) -> bool {
         self.get_attribute_classes()
             .map(|classes| classes.iter().any(|c| c == class))
             .unwrap_or(false)
     }
 
+    /// Functional level named by `msDS-Behavior-Version`, on domain and
+    /// crossRefContainer objects.
+    pub fn functional_level(&self) -> Option<String> {
+        self.get_first("msDS-Behavior-Version")
+            .and_then(AttributeValue::as_integer)
+            .map(|level| {
+                match level {
+                    0 => "2000 Mixed/Native",
+                    1 => "2003 Interim",
+                    2 => "2003",
+                    3 => "2008",
+                    4 => "2008 R2",
+                    5 => "2012",
+                    6 => "2012 R2",
+                    // Server 2019 and 2022 added no functional level
+                    7 => "2016",
+                    10 => "2025",
+                    _ => "Unknown",
+                }
+                .to_string()
+            })
+    }
+
+    /// Whether `instanceType` marks this object as the writable head of a
+    /// naming context (IT_NC_HEAD | IT_WRITE).
+    pub fn is_naming_context_head(&self) -> bool {
+        self.get_first("instanceType")
+            .and_then(AttributeValue::as_integer)
+            .map(|instance_type| instance_type & 5 == 5)
+            .unwrap_or(false)
+    }
+
     pub fn get_object_identifier(&self) -> Option<String> {
-        match self.get_type() {
-            ObjectType::Computer | ObjectType::User | ObjectType::Group => self
+        self.identifier_for(self.get_type())
+    }
+
+    /// Identifier of this object when treated as `object_type`.
+    pub fn identifier_for(&self, object_type: ObjectType) -> Option<String> {
+        match object_type {
+            // Principals without a SID, such as partially replicated objects,
+            // are still identifiable by their GUID
+            ObjectType::Computer
+            | ObjectType::User
+            | ObjectType::UserDisabled
+            | ObjectType::Group => self
+                .get_first("objectSid")
+                .and_then(AttributeValue::as_sid)
+                .map(|sid| sid.to_string())
+                .or_else(|| {
+                    self.get_first("objectGUID")
+                        .and_then(AttributeValue::as_guid)
+                        .map(|guid| guid.to_string())
+                }),
+            // BloodHound identifies domains by their SID, never their GUID
+            ObjectType::Domain => self
                 .get_first("objectSid")
                 .and_then(AttributeValue::as_sid)
                 .map(|sid| sid.to_string()),
+            ObjectType::TrustedDomain => self
+                .get_first("securityIdentifier")
+                .and_then(AttributeValue::as_sid)
+                .map(|sid| sid.to_string()),
             ObjectType::OU | ObjectType::Container | ObjectType::GPO => self
                 .get_first("objectGUID")
                 .and_then(AttributeValue::as_guid)
@@ -269,6 +445,15 @@ impl Object {
             return ObjectType::GPO;
         }
 
+        // Computers are also of class user, so they have to be told apart
+        // first. gMSAs are computers as well, but BloodHound types them as users.
+        if self.has_attribute_class("msDS-GroupManagedServiceAccount") {
+            return self.user_type();
+        }
+        if self.has_attribute_class("computer") {
+            return ObjectType::Computer;
+        }
+
         if self.has_attribute_class("user") {
             if let Some(uac) = self
                 .get_first("userAccountControl")
@@ -303,15 +488,36 @@ impl Object {
                     "organizationalUnit" => return ObjectType::OU,
                     "container" => return ObjectType::Container,
                     "groupPolicyContainer" => return ObjectType::GPO,
+                    "trustedDomain" => return ObjectType::TrustedDomain,
                     _ => continue,
                 }
             }
         }
-        ObjectType::Unknown
+
+        // Objects with a stripped objectClass can still be typed by their
+        // account type. Trust accounts aren't principals BloodHound knows.
+        match self.sam_account_type() {
+            Some(account_type) if account_type.is_user() => self.user_type(),
+            Some(account_type) if account_type.is_computer() => ObjectType::Computer,
+            Some(account_type) if account_type.is_group() => ObjectType::Group,
+            _ => ObjectType::Unknown,
+        }
+    }
+
+    fn user_type(&self) -> ObjectType {
+        let uac = self
+            .get_first("userAccountControl")
+            .and_then(AttributeValue::as_integer)
+            .unwrap_or(0);
+        if uac & 0x00000002 != 0 {
+            ObjectType::UserDisabled
+        } else {
+            ObjectType::User
+        }
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Attribute {
     pub num_values: u32,
     pub values: Vec<AttributeValue>,
@@ -349,6 +555,7 @@ impl Attribute {
                 })
                 .collect::<Result<Vec<_>>>()?,
             9 => Self::parse_utc_time_values(reader, num_values)?,
+            11 => Self::parse_dn_with_binary_values(reader, num_values, attribute_start)?,
             25 => Self::parse_nt_security_descriptor(reader)?,
             _ => {
                 return Err(Error::new(
@@ -381,6 +588,31 @@ impl Attribute {
         Ok(result)
     }
 
+    /// Each value is at an offset from the attribute start, like strings, and
+    /// holds a byte count, that many bytes of binary data and then the DN.
+    fn parse_dn_with_binary_values<T: Read + Seek>(
+        reader: &mut T,
+        num_values: u32,
+        attribute_start: u64,
+    ) -> Result<Vec<AttributeValue>> {
+        let mut result = Vec::with_capacity(num_values as usize);
+        let mut offset_buf = vec![0u32; num_values as usize];
+        reader.read_u32_into::<LittleEndian>(&mut offset_buf)?;
+
+        for &offset in &offset_buf {
+            let current_pos = reader.stream_position()?;
+            reader.seek(SeekFrom::Start(attribute_start + offset as u64))?;
+            let count = reader.read_u32::<LittleEndian>()?;
+            let mut binary = vec![0u8; count as usize];
+            reader.read_exact(&mut binary)?;
+            let dn = read_next_wstring(reader)?;
+            reader.seek(SeekFrom::Start(current_pos))?;
+            result.push(AttributeValue::DNWithBinary { count, binary, dn });
+        }
+
+        Ok(result)
+    }
+
     fn parse_octet_string_values<T: Read>(
         reader: &mut T,
         num_values: u32,
@@ -434,7 +666,7 @@ impl Attribute {
     }
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum AttributeValue {
     String(String),
     OctetString(Vec<u8>),
@@ -443,9 +675,29 @@ pub enum AttributeValue {
     LargeInteger(i64),
     UTCTime(i64),
     NTSecurityDescriptor(Vec<u8>),
+    /// `count` is the length of `binary` in bytes.
+    DNWithBinary {
+        count: u32,
+        binary: Vec<u8>,
+        dn: String,
+    },
 }
 
 impl AttributeValue {
+    /// Name of the variant, the type tag in `Snapshot::to_debug_json`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            AttributeValue::String(_) => "String",
+            AttributeValue::OctetString(_) => "OctetString",
+            AttributeValue::Boolean(_) => "Boolean",
+            AttributeValue::Integer(_) => "Integer",
+            AttributeValue::LargeInteger(_) => "LargeInteger",
+            AttributeValue::UTCTime(_) => "UTCTime",
+            AttributeValue::NTSecurityDescriptor(_) => "NTSecurityDescriptor",
+            AttributeValue::DNWithBinary { .. } => "DNWithBinary",
+        }
+    }
+
     pub fn as_string(&self) -> Option<&String> {
         if let AttributeValue::String(s) = self {
             Some(s)
@@ -494,6 +746,15 @@ impl AttributeValue {
         }
     }
 
+    /// Binary data and DN of a DN-with-binary value, such as `wellKnownObjects`.
+    pub fn as_dn_with_binary(&self) -> Option<(&[u8], &str)> {
+        if let AttributeValue::DNWithBinary { binary, dn, .. } = self {
+            Some((binary, dn))
+        } else {
+            None
+        }
+    }
+
     pub fn as_nt_security_descriptor(&self) -> Option<SDDL> {
         if let AttributeValue::NTSecurityDescriptor(o) = self {
             SDDL::from_bytes(&o).ok()
@@ -502,19 +763,24 @@ impl AttributeValue {
         }
     }
 
+    /// Both SIDs and GUIDs are octet strings, so the value must be exactly one
+    /// well-formed SID, not just start with one.
     pub fn as_sid(&self) -> Option<SID> {
         if let AttributeValue::OctetString(o) = self {
-            SID::from_bytes(&o).ok()
+            match SID::from_next_bytes(o) {
+                Ok(([], sid)) => Some(sid),
+                _ => None,
+            }
         } else {
             None
         }
     }
 
+    /// Any 16 bytes make a GUID, so only the length can be checked.
     pub fn as_guid(&self) -> Option<GUID> {
-        if let AttributeValue::OctetString(o) = self {
-            GUID::from_bytes(&o).ok()
-        } else {
-            None
+        match self {
+            AttributeValue::OctetString(o) if o.len() == 16 => GUID::from_bytes(o).ok(),
+            _ => None,
         }
     }
 
@@ -525,6 +791,11 @@ impl AttributeValue {
                     return Some(0);
                 }
 
+                // "Never" is stored as the max value, and nothing predates 1601
+                if *t < 0 || *t == i64::MAX {
+                    return Some(-1);
+                }
+
                 Some((*t - 116444736000000000) / 10000000)
             }
             AttributeValue::UTCTime(t) => Some(*t),
@@ -533,7 +804,7 @@ impl AttributeValue {
     }
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SystemTime {
     year: u16,
     month: u16,
@@ -560,17 +831,17 @@ impl SystemTime {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct SystemPossSuperior {
     system_poss_superior: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct AuxiliaryClasses {
     auxiliary_class: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct Block {
     unk1: u32,
     unk2: u32,
@@ -587,7 +858,7 @@ impl Block {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Class {
     pub class_name: String,
     pub dn: String,
@@ -666,14 +937,29 @@ pub fn parse_classes<T: Read + Seek>(reader: &mut T) -> Result<Vec<Class>> {
     (0..num_classes).map(|_| Class::parse(reader)).collect()
 }
 
-#[derive(Debug, Serialize)]
-struct Right {
+/// An entry of the extended rights table.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Right {
     name: String,
     desc: String,
     blob: [u8; 20],
 }
 
 impl Right {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn desc(&self) -> &str {
+        &self.desc
+    }
+
+    /// The rightsGuid that control access ACEs reference as their object
+    /// type, stored in the first 16 bytes of the blob.
+    pub fn rights_guid(&self) -> Option<GUID> {
+        GUID::from_bytes(&self.blob[..16]).ok()
+    }
+
     pub fn parse<T: Read + Seek>(reader: &mut T) -> Result<Self> {
         Ok(Right {
             name: read_wstring(reader)?,
@@ -694,60 +980,1011 @@ fn parse_rights<T: Read + Seek>(reader: &mut T) -> Result<Vec<Right>> {
     (0..num_rights).map(|_| Right::parse(reader)).collect()
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Snapshot {
     pub header: Header,
     pub properties: Vec<Property>,
     pub objects: Vec<Object>,
     pub classes: Vec<Class>,
     rights: Vec<Right>,
+    /// Objects that failed to parse when reading leniently. Not kept in caches.
+    #[serde(skip)]
+    pub errors: Vec<ParseError>,
+}
+
+/// An object `SnapshotReader::lenient` skipped over instead of failing on.
+#[derive(Debug)]
+pub struct ParseError {
+    /// Position of the object in the snapshot.
+    pub index: usize,
+    /// File offset the object starts at.
+    pub offset: u64,
+    pub error: Error,
 }
 
 impl Snapshot {
     pub fn snapshot_from_file<P: AsRef<Path>>(path: P) -> Result<Snapshot> {
-        let file = File::open(path)?;
-        let mmap = unsafe { Mmap::map(&file)? };
-        Self::snapshot_from_memory(&mmap[..])
+        SnapshotReader::from_file(path)?.into_snapshot()
     }
 
     pub fn snapshot_from_memory(snapshot: impl AsRef<[u8]>) -> Result<Snapshot> {
-        let mut cursor = Cursor::new(snapshot.as_ref());
+        SnapshotReader::new(snapshot)?.into_snapshot()
+    }
 
-        let header = Header::parse(&mut cursor)?;
+    /// Like `snapshot_from_memory`, reporting `(objects_parsed, total_objects)`
+    /// to `progress` as parsing goes.
+    pub fn snapshot_from_memory_with_progress(
+        snapshot: impl AsRef<[u8]>,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> Result<Snapshot> {
+        SnapshotReader::new(snapshot)?.into_snapshot_with_progress(progress)
+    }
 
-        cursor.seek(SeekFrom::Start(
-            (header.fileoffset_high as u64) << 32 | header.fileoffset_low as u64,
-        ))?;
+    /// Reads a snapshot from any seekable source, such as a network stream,
+    /// without spooling it to a file first.
+    pub fn snapshot_from_reader<R: Read + Seek>(reader: R) -> Result<Snapshot> {
+        SnapshotReader::from_reader(BufReader::new(reader))?.into_snapshot()
+    }
 
-        let num_properties = cursor.read_u32::<LittleEndian>()?;
+    /// A stable view of every object for diffing two captures, unlike the
+    /// derived `Serialize`, which carries parsing internals. Objects are keyed
+    /// by distinguishedName (or `#<index>` without one) and attributes by
+    /// name, both sorted. Each attribute is `{"type": ..., "values": [...]}`,
+    /// where `type` names the `AttributeValue` variant and the values are
+    /// decoded as `--dump` prints them.
+    pub fn to_debug_json(&self) -> Value {
+        let names = self.attribute_names();
+        let objects: BTreeMap<String, Value> = self
+            .objects
+            .iter()
+            .enumerate()
+            .map(|(index, obj)| {
+                let key = obj
+                    .get_first("distinguishedName")
+                    .and_then(AttributeValue::as_string)
+                    .cloned()
+                    .unwrap_or_else(|| format!("#{}", index));
+                let attributes: BTreeMap<&str, Value> = obj
+                    .attributes
+                    .iter()
+                    .map(|(name, attribute)| {
+                        let values: Vec<Value> = attribute
+                            .values
+                            .iter()
+                            .map(|value| readable_value(name, value))
+                            .collect();
+                        let type_name = attribute.values.first().map(AttributeValue::type_name);
+                        (
+                            names.get(name).copied().unwrap_or(name),
+                            json!({ "type": type_name, "values": values }),
+                        )
+                    })
+                    .collect();
+                (key, json!(attributes))
+            })
+            .collect();
 
-        let mut properties = Vec::new();
-        for _ in 0..num_properties {
-            properties.push(Property::parse(&mut cursor)?);
-        }
+        json!({
+            "server": self.header.server,
+            "capturedat": self.header.captured_at().to_rfc3339(),
+            "objects": objects,
+        })
+    }
 
-        let offset_properties = cursor.position();
+    /// Schema spelling of each attribute, by the lowercased name objects key
+    /// their attributes with.
+    pub(crate) fn attribute_names(&self) -> HashMap<String, &str> {
+        self.properties
+            .iter()
+            .map(|property| {
+                (
+                    property.prop_name.to_ascii_lowercase(),
+                    property.prop_name.as_str(),
+                )
+            })
+            .collect()
+    }
 
-        cursor.seek(SeekFrom::Start(0x43e))?;
+    /// The extended rights table, mapping control access rights to names.
+    pub fn rights(&self) -> &[Right] {
+        &self.rights
+    }
 
-        let mut objects = Vec::new();
-        for _ in 0..header.num_objects {
-            objects.push(Object::parse(&mut cursor, &properties)?);
-        }
+    /// Parses only the header and the attribute definitions, skipping objects,
+    /// classes and rights, to quickly see which attributes a snapshot holds.
+    pub fn metadata_only<P: AsRef<Path>>(path: P) -> Result<(Header, Vec<Property>)> {
+        read_metadata(&mut BufReader::new(File::open(path)?))
+    }
+}
 
-        cursor.seek(SeekFrom::Start(offset_properties))?;
+const OBJECTS_OFFSET: u64 = 0x43e;
+/// How many objects are parsed between progress reports.
+const PROGRESS_INTERVAL: usize = 10_000;
+
+/// Reads the header and the properties section, leaving `reader` at the
+/// start of the classes.
+fn read_metadata<R: Read + Seek>(reader: &mut R) -> Result<(Header, Vec<Property>)> {
+    let header = Header::parse(reader)?;
+
+    // An interrupted save never writes the definitions, and without them no
+    // attribute can be decoded
+    let tables_offset = (header.fileoffset_high as u64) << 32 | header.fileoffset_low as u64;
+    let len = reader.seek(SeekFrom::End(0))?;
+    if tables_offset < OBJECTS_OFFSET || tables_offset >= len {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            format!(
+                "snapshot is truncated: its attribute definitions at offset {:#x} are missing",
+                tables_offset
+            ),
+        ));
+    }
+    reader.seek(SeekFrom::Start(tables_offset))?;
+
+    let num_properties = reader.read_u32::<LittleEndian>()?;
 
-        let classes = parse_classes(&mut cursor)?;
-        let rights = parse_rights(&mut cursor)?;
+    let mut properties = Vec::new();
+    for _ in 0..num_properties {
+        properties.push(Property::parse(reader)?);
+    }
 
-        let result = Snapshot {
+    Ok((header, properties))
+}
+
+/// Parses objects on demand instead of loading them all, for snapshots too
+/// large to hold in memory. The header, properties, classes and rights are
+/// small and read up front.
+pub struct SnapshotReader<R: Read + Seek> {
+    reader: R,
+    pub header: Header,
+    pub properties: Vec<Property>,
+    pub classes: Vec<Class>,
+    rights: Vec<Right>,
+    keep: Option<Vec<bool>>,
+    lenient: bool,
+}
+
+impl SnapshotReader<Cursor<Mmap>> {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path)?;
+        let mmap = unsafe { Mmap::map(&file)? };
+        Self::new(mmap)
+    }
+}
+
+impl<B: AsRef<[u8]>> SnapshotReader<Cursor<B>> {
+    pub fn new(data: B) -> Result<Self> {
+        Self::from_reader(Cursor::new(data))
+    }
+}
+
+impl<R: Read + Seek> SnapshotReader<R> {
+    pub fn from_reader(mut reader: R) -> Result<Self> {
+        let (header, properties) = read_metadata(&mut reader)?;
+        let classes = parse_classes(&mut reader)?;
+        let rights = parse_rights(&mut reader)?;
+
+        Ok(SnapshotReader {
+            reader,
             header,
             properties,
-            objects,
             classes,
             rights,
+            keep: None,
+            lenient: false,
+        })
+    }
+
+    /// Parses each object as it is requested, without keeping earlier ones.
+    /// Iteration stops after the first error, since the offset of the next
+    /// object is unknown once one fails to parse.
+    pub fn objects_iter(&mut self) -> impl Iterator<Item = Result<Object>> + '_ {
+        let mut pending = self.reader.seek(SeekFrom::Start(OBJECTS_OFFSET)).err();
+        let reader = &mut self.reader;
+        let properties = &self.properties;
+        let keep = self.keep.as_deref();
+        let mut failed = false;
+
+        (0..self.header.num_objects).map_while(move |_| {
+            if failed {
+                return None;
+            }
+            let object = match pending.take() {
+                Some(error) => Err(error),
+                None => Object::parse(reader, properties, keep),
+            };
+            failed = object.is_err();
+            Some(object)
+        })
+    }
+
+    /// Only decodes the named attributes, compared case-insensitively, so
+    /// objects don't hold attributes the caller will never read.
+    pub fn retain_attributes(mut self, names: &[&str]) -> Self {
+        self.keep = Some(
+            self.properties
+                .iter()
+                .map(|property| {
+                    names
+                        .iter()
+                        .any(|name| name.eq_ignore_ascii_case(&property.prop_name))
+                })
+                .collect(),
+        );
+        self
+    }
+
+    /// Makes `into_snapshot` skip objects that fail to parse, recording them
+    /// in `Snapshot::errors`, so a snapshot with corrupt or missing objects
+    /// still yields the ones that could be read. Reading stops at the end of
+    /// the objects or once an object's size can't be trusted to find the next
+    /// one. The attribute definitions at the end of the file are read when the
+    /// reader is created, so a file cut short by an interrupted save still
+    /// fails there.
+    pub fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
+    /// Reads every object into a `Snapshot` for callers that need random access.
+    pub fn into_snapshot(self) -> Result<Snapshot> {
+        self.into_snapshot_with_progress(&mut |_, _| {})
+    }
+
+    /// Like `into_snapshot`, calling `progress` with the number of objects
+    /// parsed so far and the total every `PROGRESS_INTERVAL` objects and
+    /// once more when done.
+    pub fn into_snapshot_with_progress(
+        mut self,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> Result<Snapshot> {
+        let total = self.header.num_objects as usize;
+        let mut objects = Vec::with_capacity(total);
+        let mut errors = Vec::new();
+        if self.lenient {
+            self.read_objects_lenient(&mut objects, &mut errors, progress)?;
+        } else {
+            for object in self.objects_iter() {
+                objects.push(object?);
+                if objects.len() % PROGRESS_INTERVAL == 0 && objects.len() != total {
+                    progress(objects.len(), total);
+                }
+            }
+            progress(objects.len(), total);
+        }
+
+        Ok(Snapshot {
+            header: self.header,
+            properties: self.properties,
+            objects,
+            classes: self.classes,
+            rights: self.rights,
+            errors,
+        })
+    }
+
+    fn read_objects_lenient(
+        &mut self,
+        objects: &mut Vec<Object>,
+        errors: &mut Vec<ParseError>,
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> Result<()> {
+        let total = self.header.num_objects as usize;
+        let len = self.reader.seek(SeekFrom::End(0))?;
+        self.reader.seek(SeekFrom::Start(OBJECTS_OFFSET))?;
+
+        // The objects are followed by the properties the header points at
+        let tables_offset =
+            (self.header.fileoffset_high as u64) << 32 | self.header.fileoffset_low as u64;
+        let end = match tables_offset {
+            offset if offset > OBJECTS_OFFSET => offset.min(len),
+            _ => len,
         };
 
-        Ok(result)
+        let mut index = 0;
+        while index < total {
+            let offset = self.reader.stream_position()?;
+            if offset >= end {
+                errors.push(ParseError {
+                    index,
+                    offset,
+                    error: Error::new(
+                        ErrorKind::UnexpectedEof,
+                        format!("snapshot ends after {} of {} objects", index, total),
+                    ),
+                });
+                break;
+            }
+
+            match Object::parse(&mut self.reader, &self.properties, self.keep.as_deref()) {
+                Ok(object) => objects.push(object),
+                Err(error) => {
+                    errors.push(ParseError {
+                        index,
+                        offset,
+                        error,
+                    });
+                    // An object too small to hold its own size fields, or one
+                    // running past the end, leaves nothing to resume from
+                    self.reader.seek(SeekFrom::Start(offset))?;
+                    let obj_size = self.reader.read_u32::<LittleEndian>().ok();
+                    match obj_size.map(u64::from) {
+                        Some(size) if size >= 8 && offset + size <= end => {
+                            self.reader.seek(SeekFrom::Start(offset + size))?;
+                        }
+                        _ => break,
+                    }
+                }
+            }
+
+            index += 1;
+            if index % PROGRESS_INTERVAL == 0 && index != total {
+                progress(index, total);
+            }
+        }
+        progress(index, total);
+        Ok(())
+    }
+}
+
+/// `value` in the readable form `--dump` prints. SIDs and GUIDs are decoded
+/// for attributes named like one, other binary values are base64.
+pub(crate) fn readable_value(name: &str, value: &AttributeValue) -> Value {
+    match value {
+        AttributeValue::String(s) => json!(s),
+        AttributeValue::Boolean(b) => json!(b),
+        AttributeValue::Integer(i) => json!(i),
+        AttributeValue::LargeInteger(i) | AttributeValue::UTCTime(i) => json!(i),
+        AttributeValue::OctetString(bytes) => {
+            let lowercase = name.to_lowercase();
+            let decoded = if lowercase.ends_with("sid") || lowercase == "sidhistory" {
+                value.as_sid().map(|sid| sid.to_string())
+            } else if lowercase.ends_with("guid") {
+                value.as_guid().map(|guid| guid.to_string())
+            } else {
+                None
+            };
+            json!(decoded.unwrap_or_else(|| STANDARD.encode(bytes)))
+        }
+        AttributeValue::NTSecurityDescriptor(bytes) => {
+            json!(STANDARD.encode(bytes))
+        }
+        // LDAP string form, B:<hex digit count>:<hex>:<dn>
+        AttributeValue::DNWithBinary { binary, dn, .. } => {
+            let hex: String = binary.iter().map(|b| format!("{:02X}", b)).collect();
+            json!(format!("B:{}:{}:{}", hex.len(), hex, dn))
+        }
+    }
+}
+
+#[cfg(test)]
+impl Object {
+    pub(crate) fn from_attributes(attributes: Vec<(&str, Vec<AttributeValue>)>) -> Self {
+        Object {
+            obj_size: 0,
+            table_size: 0,
+            mapping_table: Vec::new(),
+            attributes: attributes
+                .into_iter()
+                .map(|(name, values)| {
+                    (
+                        name.to_ascii_lowercase(),
+                        Attribute {
+                            num_values: values.len() as u32,
+                            values,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Snapshot {
+    pub(crate) fn from_objects(objects: Vec<Object>) -> Self {
+        Snapshot {
+            header: Header {
+                win_ad_sig: "win-ad-ob".to_string(),
+                marker: 0,
+                filetime: 0,
+                optional_description: String::new(),
+                server: String::new(),
+                num_objects: objects.len() as u32,
+                num_attributes: 0,
+                fileoffset_low: 0,
+                fileoffset_high: 0,
+                fileoffset_end: 0,
+                unk0x43a: 0,
+            },
+            properties: Vec::new(),
+            objects,
+            classes: ["person", "computer", "group"]
+                .iter()
+                .map(|name| Class::from_name(name))
+                .collect(),
+            rights: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Property {
+    pub(crate) fn from_name(name: &str) -> Self {
+        Property {
+            prop_name: name.to_string(),
+            unk1: 0,
+            ads_type: 1,
+            dn: format!("CN={},CN=Schema,CN=Configuration,DC=EXAMPLE,DC=COM", name),
+            schema_id_guid: GUID::from_bytes(&[0u8; 16]).unwrap(),
+            attribute_security_guid: GUID::from_bytes(&[0u8; 16]).unwrap(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Class {
+    pub(crate) fn from_name(name: &str) -> Self {
+        let mut cn = name.to_string();
+        cn[..1].make_ascii_uppercase();
+        Class {
+            class_name: name.to_string(),
+            dn: format!("CN={},CN=Schema,CN=Configuration,DC=EXAMPLE,DC=COM", cn),
+            common_class_name: cn,
+            sub_class_of: "top".to_string(),
+            schema_id_guid: GUID::from_bytes(&[0u8; 16]).unwrap(),
+            unk2: Vec::new(),
+            blocks: Vec::new(),
+            unknown: Vec::new(),
+            system_poss_superiors: Vec::new(),
+            auxiliary_classes: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_wstring_embedded_null() {
+        let chars: Vec<u16> = "ab\0cd\0".encode_utf16().collect();
+        let mut bytes = ((chars.len() * 2) as u32).to_le_bytes().to_vec();
+        bytes.extend(chars.iter().flat_map(|c| c.to_le_bytes()));
+        bytes.extend_from_slice(&[0xff, 0xff]);
+        let mut reader = Cursor::new(bytes);
+
+        assert_eq!(read_wstring(&mut reader).unwrap(), "ab\0cd");
+        assert_eq!(reader.position(), 4 + 12);
+    }
+
+    #[test]
+    fn test_unix_timestamp_sentinels() {
+        assert_eq!(
+            AttributeValue::LargeInteger(i64::MAX).as_unix_timestamp(),
+            Some(-1)
+        );
+        assert_eq!(
+            AttributeValue::LargeInteger(-1).as_unix_timestamp(),
+            Some(-1)
+        );
+        assert_eq!(
+            AttributeValue::LargeInteger(116444736000000000).as_unix_timestamp(),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_identifier_falls_back_to_guid() {
+        let guid: GUID = "6f2e4c1a-3b5d-4e7f-8a9b-0c1d2e3f4a5b".parse().unwrap();
+        let user = Object::from_attributes(vec![
+            (
+                "objectClass",
+                vec![
+                    AttributeValue::String("top".to_string()),
+                    AttributeValue::String("user".to_string()),
+                ],
+            ),
+            ("userAccountControl", vec![AttributeValue::Integer(0x200)]),
+            (
+                "objectGUID",
+                vec![AttributeValue::OctetString(guid.to_bytes())],
+            ),
+        ]);
+
+        assert_eq!(user.get_object_identifier(), Some(guid.to_string()));
+    }
+
+    #[test]
+    fn test_domain_identifier_is_sid() {
+        let sid = "S-1-5-21-1000-2000-3000";
+        let guid: GUID = "6f2e4c1a-3b5d-4e7f-8a9b-0c1d2e3f4a5b".parse().unwrap();
+        let domain = Object::from_attributes(vec![
+            (
+                "objectClass",
+                vec![
+                    AttributeValue::String("top".to_string()),
+                    AttributeValue::String("domain".to_string()),
+                    AttributeValue::String("domainDNS".to_string()),
+                ],
+            ),
+            (
+                "objectSid",
+                vec![AttributeValue::OctetString(
+                    sid.parse::<SID>().unwrap().to_bytes(),
+                )],
+            ),
+            (
+                "objectGUID",
+                vec![AttributeValue::OctetString(guid.to_bytes())],
+            ),
+        ]);
+
+        assert_eq!(domain.get_type(), ObjectType::Domain);
+        assert_eq!(domain.get_object_identifier(), Some(sid.to_string()));
+    }
+
+    #[test]
+    fn test_functional_levels() {
+        let level = |version| {
+            Object::from_attributes(vec![(
+                "msDS-Behavior-Version",
+                vec![AttributeValue::Integer(version)],
+            )])
+            .functional_level()
+            .unwrap()
+        };
+
+        assert_eq!(level(7), "2016");
+        assert_eq!(level(10), "2025");
+        assert_eq!(level(8), "Unknown");
+    }
+
+    #[test]
+    fn test_get_type_from_account_type() {
+        let computer = Object::from_attributes(vec![(
+            "sAMAccountType",
+            vec![AttributeValue::Integer(805306369)],
+        )]);
+        let group = Object::from_attributes(vec![(
+            "sAMAccountType",
+            vec![AttributeValue::Integer(536870912)],
+        )]);
+        let disabled_user = Object::from_attributes(vec![
+            ("sAMAccountType", vec![AttributeValue::Integer(805306368)]),
+            ("userAccountControl", vec![AttributeValue::Integer(0x202)]),
+        ]);
+
+        assert_eq!(computer.get_type(), ObjectType::Computer);
+        assert_eq!(group.get_type(), ObjectType::Group);
+        assert_eq!(disabled_user.get_type(), ObjectType::UserDisabled);
+        let trust_account = Object::from_attributes(vec![
+            ("sAMAccountType", vec![AttributeValue::Integer(0x30000002)]),
+            ("userAccountControl", vec![AttributeValue::Integer(0x820)]),
+        ]);
+        assert_eq!(trust_account.get_type(), ObjectType::Unknown);
+        assert_eq!(
+            Object::from_attributes(vec![]).get_type(),
+            ObjectType::Unknown
+        );
+    }
+
+    // Header followed by objects given as (obj_size, table_size) with no
+    // mapping entries, then the (name, ADSTYPE) properties and empty class
+    // and right tables.
+    fn snapshot_bytes(
+        num_objects: u32,
+        objects: &[(u32, u32)],
+        properties: &[(&str, u32)],
+    ) -> Vec<u8> {
+        let tables_offset = OBJECTS_OFFSET as u32 + objects.iter().map(|o| o.0).sum::<u32>();
+        let mut bytes = b"win-ad-ob\0".to_vec();
+        bytes.extend_from_slice(&0i32.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend(vec![0u8; 1040]);
+        bytes.extend_from_slice(&num_objects.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&tables_offset.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&0i32.to_le_bytes());
+        for (obj_size, table_size) in objects {
+            bytes.extend_from_slice(&obj_size.to_le_bytes());
+            bytes.extend_from_slice(&table_size.to_le_bytes());
+            bytes.extend(vec![0u8; *obj_size as usize - 8]);
+        }
+        bytes.extend_from_slice(&(properties.len() as u32).to_le_bytes());
+        for (name, ads_type) in properties {
+            bytes.extend(wstring(name));
+            bytes.extend_from_slice(&0i32.to_le_bytes());
+            bytes.extend_from_slice(&ads_type.to_le_bytes());
+            bytes.extend(wstring(&format!("CN={},CN=Schema,CN=Configuration", name)));
+            bytes.extend(vec![0u8; 36]);
+        }
+        bytes.extend(vec![0u8; 8]);
+        bytes
+    }
+
+    fn wstring(value: &str) -> Vec<u8> {
+        let chars: Vec<u8> = format!("{}\0", value)
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let mut bytes = (chars.len() as u32).to_le_bytes().to_vec();
+        bytes.extend(chars);
+        bytes
+    }
+
+    #[test]
+    fn test_objects_iter() {
+        let mut reader = SnapshotReader::new(snapshot_bytes(2, &[(8, 0), (24, 0)], &[])).unwrap();
+        let sizes: Vec<u32> = reader
+            .objects_iter()
+            .map(|object| object.unwrap().obj_size)
+            .collect();
+        assert_eq!(sizes, vec![8, 24]);
+
+        let snapshot = reader.into_snapshot().unwrap();
+        assert_eq!(snapshot.objects.len(), 2);
+    }
+
+    #[test]
+    fn test_snapshot_from_reader() {
+        let mut bytes = snapshot_bytes(2, &[(8, 0), (24, 1)], &[("userAccountControl", 7)]);
+        // Mapping entry for property 0 at offset 16, then one Integer value
+        let fields: Vec<u8> = [0u32, 16, 1, 42]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let start = OBJECTS_OFFSET as usize + 16;
+        bytes[start..start + 16].copy_from_slice(&fields);
+
+        let from_memory = Snapshot::snapshot_from_memory(&bytes).unwrap();
+        let from_reader = Snapshot::snapshot_from_reader(Cursor::new(&bytes)).unwrap();
+        assert_eq!(from_reader.objects.len(), 2);
+        assert_eq!(
+            from_reader.objects[1]
+                .get_first("userAccountControl")
+                .and_then(AttributeValue::as_integer),
+            Some(42)
+        );
+        assert_eq!(
+            serde_json::to_value(&from_reader).unwrap(),
+            serde_json::to_value(&from_memory).unwrap()
+        );
+
+        // The header promises a third object where the properties start
+        let missing = snapshot_bytes(3, &[(8, 0), (8, 0)], &[]);
+        assert!(Snapshot::snapshot_from_reader(Cursor::new(&missing)).is_err());
+
+        let snapshot = SnapshotReader::from_reader(BufReader::new(Cursor::new(missing)))
+            .unwrap()
+            .lenient()
+            .into_snapshot()
+            .unwrap();
+        assert_eq!(snapshot.objects.len(), 2);
+        assert_eq!(snapshot.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_progress_reports() {
+        let count = PROGRESS_INTERVAL + 1;
+        let bytes = snapshot_bytes(count as u32, &vec![(8, 0); count], &[]);
+        let mut reports = Vec::new();
+        let snapshot = Snapshot::snapshot_from_memory_with_progress(bytes, &mut |parsed, total| {
+            reports.push((parsed, total))
+        })
+        .unwrap();
+
+        assert_eq!(snapshot.objects.len(), count);
+        assert_eq!(reports, vec![(PROGRESS_INTERVAL, count), (count, count)]);
+    }
+
+    #[test]
+    fn test_lenient_skips_corrupt_objects() {
+        // The second object's mapping table runs past the end of the file, but
+        // its size still locates the third
+        let bytes = snapshot_bytes(3, &[(8, 0), (16, 1000), (24, 0)], &[]);
+        assert!(SnapshotReader::new(bytes.clone())
+            .unwrap()
+            .into_snapshot()
+            .is_err());
+
+        let snapshot = SnapshotReader::new(bytes)
+            .unwrap()
+            .lenient()
+            .into_snapshot()
+            .unwrap();
+        let sizes: Vec<u32> = snapshot
+            .objects
+            .iter()
+            .map(|object| object.obj_size)
+            .collect();
+        assert_eq!(sizes, vec![8, 24]);
+        assert_eq!(snapshot.errors.len(), 1);
+        assert_eq!(snapshot.errors[0].index, 1);
+        assert_eq!(snapshot.errors[0].offset, OBJECTS_OFFSET + 8);
+    }
+
+    #[test]
+    fn test_lenient_stops_at_missing_objects() {
+        // The header promises more objects than were written
+        let bytes = snapshot_bytes(4, &[(8, 0), (8, 0)], &[]);
+        let snapshot = SnapshotReader::new(bytes)
+            .unwrap()
+            .lenient()
+            .into_snapshot()
+            .unwrap();
+
+        assert_eq!(snapshot.objects.len(), 2);
+        assert_eq!(snapshot.errors.len(), 1);
+        assert_eq!(snapshot.errors[0].index, 2);
+        assert_eq!(snapshot.errors[0].error.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_objects_iter_stops_after_error() {
+        // The second object's mapping table runs past the end of the file
+        let mut reader = SnapshotReader::new(snapshot_bytes(5, &[(8, 0), (8, 1000)], &[])).unwrap();
+        let objects: Vec<Result<Object>> = reader.objects_iter().collect();
+
+        assert_eq!(objects.len(), 2);
+        assert!(objects[0].is_ok());
+        assert!(objects[1].is_err());
+        assert!(reader.into_snapshot().is_err());
+    }
+
+    #[test]
+    fn test_dn_with_binary_values() {
+        let dn: Vec<u8> = "CN=Users,DC=EXAMPLE,DC=COM\0"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let binary = [0xa9u8, 0xd1, 0xca, 0x15];
+
+        let mut bytes = 1u32.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&8u32.to_le_bytes());
+        bytes.extend_from_slice(&(binary.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&binary);
+        bytes.extend(dn);
+
+        let attribute = Attribute::parse(&mut Cursor::new(bytes), 11).unwrap();
+        assert_eq!(
+            attribute.values[0].as_dn_with_binary(),
+            Some((&binary[..], "CN=Users,DC=EXAMPLE,DC=COM"))
+        );
+    }
+
+    #[test]
+    fn test_metadata_only_matches_full_parse() {
+        let bytes = snapshot_bytes(
+            1,
+            &[(8, 0)],
+            &[("cn", 3), ("objectSid", 8), ("wellKnownObjects", 11)],
+        );
+        let path = std::env::temp_dir().join("adexplorersnapshot-metadata-test.dat");
+        std::fs::write(&path, &bytes).unwrap();
+        let (header, properties) = Snapshot::metadata_only(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let snapshot = Snapshot::snapshot_from_memory(&bytes).unwrap();
+        let summary = |properties: &[Property]| -> Vec<(String, u32)> {
+            properties
+                .iter()
+                .map(|p| (p.prop_name.clone(), p.ads_type))
+                .collect()
+        };
+        assert_eq!(header.num_objects, snapshot.header.num_objects);
+        assert_eq!(summary(&properties), summary(&snapshot.properties));
+        assert_eq!(properties.len(), 3);
+    }
+
+    #[test]
+    fn test_right_accessors() {
+        let guid: GUID = "00299570-246d-11d0-a768-00aa006e0529".parse().unwrap();
+        let mut bytes = wstring("User-Force-Change-Password");
+        bytes.extend(wstring("Reset Password"));
+        bytes.extend(guid.to_bytes());
+        bytes.extend_from_slice(&[0u8; 4]);
+
+        let right = Right::parse(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(right.name(), "User-Force-Change-Password");
+        assert_eq!(right.desc(), "Reset Password");
+        assert_eq!(right.rights_guid(), Some(guid));
+    }
+
+    #[test]
+    fn test_laps_attributes_are_not_aliases() {
+        let obj = Object::from_attributes(vec![
+            (
+                "msLAPS-PasswordExpirationTime",
+                vec![AttributeValue::LargeInteger(133000000000000000)],
+            ),
+            (
+                "msLAPS-Password",
+                vec![AttributeValue::String("hunter2".to_string())],
+            ),
+        ]);
+
+        assert!(obj.get("ms-Mcs-AdmPwdExpirationTime").is_none());
+        assert!(obj.get("ms-Mcs-AdmPwd").is_none());
+        assert_eq!(
+            obj.get_first("mslaps-password")
+                .and_then(AttributeValue::as_str),
+            Some("hunter2")
+        );
+    }
+
+    #[test]
+    fn test_get_is_case_insensitive() {
+        let obj = Object::from_attributes(vec![
+            ("useraccountcontrol", vec![AttributeValue::Integer(0x200)]),
+            (
+                "ms-mcs-admpwdexpirationtime",
+                vec![AttributeValue::LargeInteger(133000000000000000)],
+            ),
+        ]);
+
+        assert_eq!(
+            obj.get_first("userAccountControl")
+                .and_then(AttributeValue::as_integer),
+            Some(0x200)
+        );
+        assert_eq!(
+            obj.get_first("ms-Mcs-AdmPwdExpirationTime")
+                .and_then(AttributeValue::as_large_integer),
+            Some(133000000000000000)
+        );
+        assert!(obj.get("userAccountControl2").is_none());
+    }
+
+    #[test]
+    fn test_sam_account_types() {
+        let cases = [
+            (
+                0x30000000,
+                SamAccountType::UserObject,
+                true,
+                false,
+                false,
+                false,
+            ),
+            (
+                0x30000001,
+                SamAccountType::MachineAccount,
+                false,
+                true,
+                false,
+                false,
+            ),
+            (
+                0x30000002,
+                SamAccountType::TrustAccount,
+                false,
+                false,
+                true,
+                false,
+            ),
+            (
+                0x10000000,
+                SamAccountType::GroupObject,
+                false,
+                false,
+                false,
+                true,
+            ),
+            (
+                0x20000001,
+                SamAccountType::NonSecurityAliasObject,
+                false,
+                false,
+                false,
+                true,
+            ),
+            (
+                0x40000000,
+                SamAccountType::AppBasicGroup,
+                false,
+                false,
+                false,
+                false,
+            ),
+        ];
+
+        for (value, expected, user, computer, trust, group) in cases {
+            let account_type = SamAccountType::from_u32(value).unwrap();
+            assert_eq!(account_type, expected);
+            assert_eq!(account_type.is_user(), user);
+            assert_eq!(account_type.is_computer(), computer);
+            assert_eq!(account_type.is_trust_account(), trust);
+            assert_eq!(account_type.is_group(), group);
+        }
+        assert_eq!(SamAccountType::from_u32(0), None);
+
+        let object = Object::from_attributes(vec![(
+            "sAMAccountType",
+            vec![AttributeValue::Integer(0x30000001)],
+        )]);
+        assert_eq!(
+            object.sam_account_type(),
+            Some(SamAccountType::MachineAccount)
+        );
+    }
+
+    #[test]
+    fn test_debug_json() {
+        let sid_bytes = "S-1-5-21-1000-2000-3000-1100"
+            .parse::<SID>()
+            .unwrap()
+            .to_bytes();
+        let mut snapshot = Snapshot::from_objects(vec![
+            Object::from_attributes(vec![
+                (
+                    "distinguishedName",
+                    vec![AttributeValue::String(
+                        "CN=alice,DC=EXAMPLE,DC=COM".to_string(),
+                    )],
+                ),
+                ("objectSid", vec![AttributeValue::OctetString(sid_bytes)]),
+                (
+                    "servicePrincipalName",
+                    vec![
+                        AttributeValue::String("HTTP/a".to_string()),
+                        AttributeValue::String("HTTP/b".to_string()),
+                    ],
+                ),
+            ]),
+            Object::from_attributes(vec![(
+                "userAccountControl",
+                vec![AttributeValue::Integer(512)],
+            )]),
+        ]);
+
+        // Names the schema doesn't declare keep the lowercased key
+        snapshot.properties = ["distinguishedName", "objectSid", "userAccountControl"]
+            .iter()
+            .map(|name| Property::from_name(name))
+            .collect();
+
+        let debug = snapshot.to_debug_json();
+        assert_eq!(debug["capturedat"], "1970-01-01T00:00:00+00:00");
+        assert_eq!(
+            debug["objects"],
+            serde_json::json!({
+                "#1": {
+                    "userAccountControl": {"type": "Integer", "values": [512]},
+                },
+                "CN=alice,DC=EXAMPLE,DC=COM": {
+                    "distinguishedName": {
+                        "type": "String",
+                        "values": ["CN=alice,DC=EXAMPLE,DC=COM"],
+                    },
+                    "objectSid": {
+                        "type": "OctetString",
+                        "values": ["S-1-5-21-1000-2000-3000-1100"],
+                    },
+                    "serviceprincipalname": {"type": "String", "values": ["HTTP/a", "HTTP/b"]},
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_sid_and_guid_octet_strings() {
+        let guid: GUID = "bf967aba-0de6-11d0-a285-00aa003049e2".parse().unwrap();
+        let guid_value = AttributeValue::OctetString(guid.to_bytes());
+        assert_eq!(guid_value.as_sid(), None);
+        assert_eq!(guid_value.as_guid(), Some(guid));
+
+        let sid: SID = "S-1-5-21-1000-2000-3000-1100".parse().unwrap();
+        let sid_value = AttributeValue::OctetString(sid.to_bytes());
+        assert_eq!(sid_value.as_sid(), Some(sid.clone()));
+        assert_eq!(sid_value.as_guid(), None);
+
+        let mut trailing = sid.to_bytes();
+        trailing.push(0);
+        assert_eq!(AttributeValue::OctetString(trailing).as_sid(), None);
     }
 }
@@ -0,0 +1,90 @@
+use super::{ADExplorerSnapshot, Cache, Class, Property};
+use crate::guid::GUID;
+
+/// Read-only view of the schema classes and attributes stored in a snapshot.
+pub struct Schema<'a> {
+    snapshot: &'a ADExplorerSnapshot,
+}
+
+impl<'a> Schema<'a> {
+    pub(crate) fn new(snapshot: &'a ADExplorerSnapshot) -> Self {
+        Schema { snapshot }
+    }
+
+    /// Class named by its lDAPDisplayName, CN or DN. Lookups are
+    /// case-insensitive, like LDAP itself.
+    pub fn class_by_name(&self, name: &str) -> Option<&'a Class> {
+        let classes = &self.snapshot.snapshot.classes;
+        match self.snapshot.caches.class_cache.get(&name.to_string()) {
+            Some(index) => classes.get(*index),
+            None => classes.iter().find(|class| {
+                class.class_name.eq_ignore_ascii_case(name)
+                    || class.common_class_name.eq_ignore_ascii_case(name)
+                    || class.dn.eq_ignore_ascii_case(name)
+            }),
+        }
+    }
+
+    /// Attribute named by its lDAPDisplayName, case-insensitively.
+    pub fn property_by_name(&self, name: &str) -> Option<&'a Property> {
+        self.snapshot
+            .snapshot
+            .properties
+            .iter()
+            .find(|property| property.prop_name.eq_ignore_ascii_case(name))
+    }
+
+    /// schemaIDGUID of attribute `name`, which ACEs use as their object type.
+    pub fn guid_for_attribute(&self, name: &str) -> Option<&'a GUID> {
+        self.property_by_name(name)
+            .map(|property| &property.schema_id_guid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn property(name: &str, guid: &str) -> Property {
+        Property {
+            prop_name: name.to_string(),
+            unk1: 0,
+            ads_type: 1,
+            dn: format!("CN={},CN=Schema,CN=Configuration,DC=EXAMPLE,DC=COM", name),
+            schema_id_guid: guid.parse().unwrap(),
+            attribute_security_guid: GUID::from_bytes(&[0u8; 16]).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_schema_lookups() {
+        let mut snapshot = ADExplorerSnapshot::from_objects(Vec::new());
+        let member = "bf9679c0-0de6-11d0-a285-00aa003049e2";
+        snapshot
+            .snapshot
+            .properties
+            .push(property("member", member));
+        let schema = snapshot.schema();
+
+        assert_eq!(
+            schema.class_by_name("computer").unwrap().class_name,
+            "computer"
+        );
+        assert_eq!(
+            schema.class_by_name("Computer").unwrap().class_name,
+            "computer"
+        );
+        assert_eq!(schema.class_by_name("GROUP").unwrap().class_name, "group");
+        assert!(schema.class_by_name("printQueue").is_none());
+
+        assert_eq!(
+            schema.property_by_name("Member").unwrap().prop_name,
+            "member"
+        );
+        assert_eq!(
+            schema.guid_for_attribute("member"),
+            Some(&member.parse::<GUID>().unwrap())
+        );
+        assert!(schema.guid_for_attribute("unknownAttribute").is_none());
+    }
+}
@@ -27,6 +27,12 @@ impl AccessMask {
     pub const ADS_RIGHT_DS_READ_PROP: u32 = 0x00000010;
     pub const ADS_RIGHT_DS_WRITE_PROP: u32 = 0x00000020;
     pub const ADS_RIGHT_DS_SELF: u32 = 0x00000008;
+    pub const ADS_RIGHT_ACTRL_DS_LIST: u32 = 0x00000004;
+    pub const ADS_RIGHT_DS_DELETE_TREE: u32 = 0x00000040;
+    pub const ADS_RIGHT_DS_LIST_OBJECT: u32 = 0x00000080;
+    /// Full control as AD writes it: every DS right plus the standard rights,
+    /// rather than the GENERIC_ALL bit.
+    pub const ADS_RIGHT_GENERIC_ALL: u32 = 0x000F01FF;
 
     // Object-specific rights are represented by the lower 16 bits (0-15)
     pub const OBJECT_SPECIFIC_RIGHTS_MASK: u32 = 0x0000FFFF;
@@ -78,6 +84,9 @@ impl AccessMask {
             AccessMask::ADS_RIGHT_DS_READ_PROP,
             AccessMask::ADS_RIGHT_DS_WRITE_PROP,
             AccessMask::ADS_RIGHT_DS_SELF,
+            AccessMask::ADS_RIGHT_ACTRL_DS_LIST,
+            AccessMask::ADS_RIGHT_DS_DELETE_TREE,
+            AccessMask::ADS_RIGHT_DS_LIST_OBJECT,
             AccessMask::GENERIC_READ,
             AccessMask::GENERIC_WRITE,
             AccessMask::GENERIC_EXECUTE,
@@ -134,7 +143,7 @@ impl Iterator for AccessMaskIter {
     type Item = u32;
 
     fn next(&mut self) -> Option<Self::Item> {
-        const RIGHTS: [u32; 11] = [
+        const RIGHTS: [u32; 20] = [
             AccessMask::GENERIC_READ,
             AccessMask::GENERIC_WRITE,
             AccessMask::GENERIC_EXECUTE,
@@ -146,6 +155,15 @@ impl Iterator for AccessMaskIter {
             AccessMask::WRITE_DACL,
             AccessMask::READ_CONTROL,
             AccessMask::DELETE,
+            AccessMask::ADS_RIGHT_DS_CONTROL_ACCESS,
+            AccessMask::ADS_RIGHT_DS_LIST_OBJECT,
+            AccessMask::ADS_RIGHT_DS_DELETE_TREE,
+            AccessMask::ADS_RIGHT_DS_WRITE_PROP,
+            AccessMask::ADS_RIGHT_DS_READ_PROP,
+            AccessMask::ADS_RIGHT_DS_SELF,
+            AccessMask::ADS_RIGHT_ACTRL_DS_LIST,
+            AccessMask::ADS_RIGHT_DS_DELETE_CHILD,
+            AccessMask::ADS_RIGHT_DS_CREATE_CHILD,
         ];
 
         while self.index < RIGHTS.len() {
@@ -190,4 +208,32 @@ mod tests {
         let mask_u32: u32 = mask.into();
         assert_eq!(mask_u32, object_rights);
     }
+
+    #[test]
+    fn test_iter_includes_ad_rights() {
+        let rights: Vec<u32> = AccessMask::new(AccessMask::ADS_RIGHT_GENERIC_ALL)
+            .into_iter()
+            .collect();
+        assert_eq!(rights.len(), 13);
+        assert_eq!(
+            rights.iter().fold(0, |mask, right| mask | right),
+            AccessMask::ADS_RIGHT_GENERIC_ALL
+        );
+
+        let rights: Vec<u32> = AccessMask::new(
+            AccessMask::READ_CONTROL
+                | AccessMask::ADS_RIGHT_DS_READ_PROP
+                | AccessMask::ADS_RIGHT_ACTRL_DS_LIST,
+        )
+        .into_iter()
+        .collect();
+        assert_eq!(
+            rights,
+            vec![
+                AccessMask::READ_CONTROL,
+                AccessMask::ADS_RIGHT_DS_READ_PROP,
+                AccessMask::ADS_RIGHT_ACTRL_DS_LIST
+            ]
+        );
+    }
 }
@@ -16,6 +16,10 @@ pub enum ACE {
     AccessDenied(AccessDeniedAce),
     SystemAuditObject(SystemAuditObjectAce),
     AccessDeniedObject(AccessDeniedObjectAce),
+    AccessAllowedCallback(AccessAllowedCallbackAce),
+    AccessDeniedCallback(AccessDeniedCallbackAce),
+    AccessAllowedCallbackObject(AccessAllowedCallbackObjectAce),
+    AccessDeniedCallbackObject(AccessDeniedCallbackObjectAce),
 }
 
 impl ACE {
@@ -26,6 +30,10 @@ impl ACE {
             ACE::AccessDenied(ace) => &ace.header,
             ACE::SystemAuditObject(ace) => &ace.header,
             ACE::AccessDeniedObject(ace) => &ace.header,
+            ACE::AccessAllowedCallback(ace) => &ace.header,
+            ACE::AccessDeniedCallback(ace) => &ace.header,
+            ACE::AccessAllowedCallbackObject(ace) => &ace.header,
+            ACE::AccessDeniedCallbackObject(ace) => &ace.header,
         }
     }
 
@@ -36,6 +44,10 @@ impl ACE {
             ACE::AccessDenied(ace) => &ace.sid,
             ACE::SystemAuditObject(ace) => &ace.sid,
             ACE::AccessDeniedObject(ace) => &ace.sid,
+            ACE::AccessAllowedCallback(ace) => &ace.sid,
+            ACE::AccessDeniedCallback(ace) => &ace.sid,
+            ACE::AccessAllowedCallbackObject(ace) => &ace.sid,
+            ACE::AccessDeniedCallbackObject(ace) => &ace.sid,
         }
     }
 
@@ -46,6 +58,10 @@ impl ACE {
             ACE::AccessDenied(ace) => ace.mask,
             ACE::SystemAuditObject(ace) => ace.mask,
             ACE::AccessDeniedObject(ace) => ace.mask,
+            ACE::AccessAllowedCallback(ace) => ace.mask,
+            ACE::AccessDeniedCallback(ace) => ace.mask,
+            ACE::AccessAllowedCallbackObject(ace) => ace.mask,
+            ACE::AccessDeniedCallbackObject(ace) => ace.mask,
         }
     }
 
@@ -54,20 +70,37 @@ impl ACE {
             ACE::AccessAllowedObject(ace) => ace.object_type.as_ref(),
             ACE::SystemAuditObject(ace) => ace.object_type.as_ref(),
             ACE::AccessDeniedObject(ace) => ace.object_type.as_ref(),
+            ACE::AccessAllowedCallbackObject(ace) => ace.object_type.as_ref(),
+            ACE::AccessDeniedCallbackObject(ace) => ace.object_type.as_ref(),
             _ => None,
         }
     }
 
+    /// Returns the recognized object type of the ACE. `None` means either that
+    /// no object type is present or that it isn't one we know about; use
+    /// `applies_to_all_object_types` to tell those apart.
     pub fn object_type_s(&self) -> Option<ACEGuid> {
         let ot = self.object_type()?;
         ACEGuid::from_guid(ot)
     }
 
+    /// Whether the ACE grants its rights on every property/extended right of the
+    /// object: either no object type is present, or it is the all-zero GUID.
+    /// An unrecognized object type is scoped and therefore returns false.
+    pub fn applies_to_all_object_types(&self) -> bool {
+        match self.object_type() {
+            None => true,
+            Some(ot) => ACEGuid::from_guid(ot) == Some(ACEGuid::AllGuid),
+        }
+    }
+
     pub fn inherited_object_type(&self) -> Option<&GUID> {
         match self {
             ACE::AccessAllowedObject(ace) => ace.inherited_object_type.as_ref(),
             ACE::SystemAuditObject(ace) => ace.inherited_object_type.as_ref(),
             ACE::AccessDeniedObject(ace) => ace.inherited_object_type.as_ref(),
+            ACE::AccessAllowedCallbackObject(ace) => ace.inherited_object_type.as_ref(),
+            ACE::AccessDeniedCallbackObject(ace) => ace.inherited_object_type.as_ref(),
             _ => None,
         }
     }
@@ -94,7 +127,7 @@ pub enum ACEGuid {
 impl ACEGuid {
     pub fn from_guid(guid: &GUID) -> Option<ACEGuid> {
         // https://github.com/BloodHoundAD/SharpHoundCommon/blob/ea6b097927c5bb795adb8589e9a843293d36ae37/src/CommonLib/Processors/ACEGuids.cs#L4
-        match guid.to_string().as_str() {
+        match guid.to_string().to_lowercase().as_str() {
             "1131f6aa-9c07-11d1-f79f-00c04fc2dcd2" => Some(ACEGuid::DSReplicationGetChanges),
             "1131f6ad-9c07-11d1-f79f-00c04fc2dcd2" => Some(ACEGuid::DSReplicationGetChangesAll),
             "89e95b76-444d-4c62-991a-0facbeda640c" => {
@@ -117,6 +150,7 @@ impl ACEGuid {
 }
 
 pub fn parse_ace(input: &[u8]) -> IResult<&[u8], ACE> {
+    let ace_start = input;
     let (input, header) = parse_ace_header(input)?;
     match header.ace_type {
         ACEType::AccessAllowed => {
@@ -139,10 +173,87 @@ pub fn parse_ace(input: &[u8]) -> IResult<&[u8], ACE> {
             let (input, ace) = parse_access_denied_object_ace(input, header)?;
             Ok((input, ACE::AccessDeniedObject(ace)))
         }
-        _ => unimplemented!("ACE type not implemented: {:?}", header.ace_type),
+        ACEType::AccessAllowedCallback => {
+            let (input, (mask, sid)) = tuple((parse_access_mask, SID::from_next_bytes))(input)?;
+            let (input, application_data) = application_data(ace_start, input, &header)?;
+            Ok((
+                input,
+                ACE::AccessAllowedCallback(AccessAllowedCallbackAce {
+                    header,
+                    mask,
+                    sid,
+                    application_data,
+                }),
+            ))
+        }
+        ACEType::AccessDeniedCallback => {
+            let (input, (mask, sid)) = tuple((parse_access_mask, SID::from_next_bytes))(input)?;
+            let (input, application_data) = application_data(ace_start, input, &header)?;
+            Ok((
+                input,
+                ACE::AccessDeniedCallback(AccessDeniedCallbackAce {
+                    header,
+                    mask,
+                    sid,
+                    application_data,
+                }),
+            ))
+        }
+        ACEType::AccessAllowedCallbackObject => {
+            let (input, ace) = parse_access_allowed_object_ace(input, header)?;
+            let (input, application_data) = application_data(ace_start, input, &ace.header)?;
+            Ok((
+                input,
+                ACE::AccessAllowedCallbackObject(AccessAllowedCallbackObjectAce {
+                    header: ace.header,
+                    mask: ace.mask,
+                    flags: ace.flags,
+                    object_type: ace.object_type,
+                    inherited_object_type: ace.inherited_object_type,
+                    sid: ace.sid,
+                    application_data,
+                }),
+            ))
+        }
+        ACEType::AccessDeniedCallbackObject => {
+            let (input, ace) = parse_access_denied_object_ace(input, header)?;
+            let (input, application_data) = application_data(ace_start, input, &ace.header)?;
+            Ok((
+                input,
+                ACE::AccessDeniedCallbackObject(AccessDeniedCallbackObjectAce {
+                    header: ace.header,
+                    mask: ace.mask,
+                    flags: ace.flags,
+                    object_type: ace.object_type,
+                    inherited_object_type: ace.inherited_object_type,
+                    sid: ace.sid,
+                    application_data,
+                }),
+            ))
+        }
+        // Audit, alarm, label and policy ACEs belong in a SACL, which isn't
+        // parsed. Fail the descriptor rather than the run.
+        _ => Err(unsupported(ace_start)),
     }
 }
 
+fn unsupported(input: &[u8]) -> nom::Err<nom::error::Error<&[u8]>> {
+    nom::Err::Failure(nom::error::Error::new(input, nom::error::ErrorKind::Switch))
+}
+
+/// Takes whatever `header.ace_size` covers past the fixed fields parsed so
+/// far, which for callback ACEs is the conditional expression.
+fn application_data<'a>(
+    ace_start: &[u8],
+    input: &'a [u8],
+    header: &ACEHeader,
+) -> IResult<&'a [u8], Vec<u8>> {
+    let consumed = ace_start.len() - input.len();
+    let remaining = (header.ace_size as usize).saturating_sub(consumed);
+    let (input, data) = take(remaining)(input)?;
+    Ok((input, data.to_vec()))
+}
+
 #[derive(Debug, PartialEq, Eq, Serialize, Clone)]
 pub struct AccessAllowedAce {
     pub header: ACEHeader,
@@ -317,6 +428,44 @@ fn parse_access_denied_object_ace(
     ))
 }
 
+#[derive(Debug, PartialEq, Eq, Serialize, Clone)]
+pub struct AccessAllowedCallbackAce {
+    pub header: ACEHeader,
+    pub mask: AccessMask,
+    pub sid: SID,
+    pub application_data: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Clone)]
+pub struct AccessDeniedCallbackAce {
+    pub header: ACEHeader,
+    pub mask: AccessMask,
+    pub sid: SID,
+    pub application_data: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Clone)]
+pub struct AccessAllowedCallbackObjectAce {
+    pub header: ACEHeader,
+    pub mask: AccessMask,
+    pub flags: u32,
+    pub object_type: Option<GUID>,
+    pub inherited_object_type: Option<GUID>,
+    pub sid: SID,
+    pub application_data: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Clone)]
+pub struct AccessDeniedCallbackObjectAce {
+    pub header: ACEHeader,
+    pub mask: AccessMask,
+    pub flags: u32,
+    pub object_type: Option<GUID>,
+    pub inherited_object_type: Option<GUID>,
+    pub sid: SID,
+    pub application_data: Vec<u8>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum ACEType {
     AccessAllowed = 0x00,
@@ -341,9 +490,11 @@ pub enum ACEType {
     SystemScopedPolicyId = 0x13,
 }
 
-impl From<u8> for ACEType {
-    fn from(value: u8) -> Self {
-        match value {
+impl TryFrom<u8> for ACEType {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
             0x00 => ACEType::AccessAllowed,
             0x01 => ACEType::AccessDenied,
             0x02 => ACEType::SystemAudit,
@@ -364,8 +515,8 @@ impl From<u8> for ACEType {
             0x11 => ACEType::SystemMandatoryLabel,
             0x12 => ACEType::SystemResourceAttribute,
             0x13 => ACEType::SystemScopedPolicyId,
-            _ => panic!("Invalid ACE type"),
-        }
+            _ => return Err(value),
+        })
     }
 }
 
@@ -398,12 +549,14 @@ pub struct ACEHeader {
 }
 
 pub fn parse_ace_header(input: &[u8]) -> IResult<&[u8], ACEHeader> {
+    let header_start = input;
     let (input, (ace_type, ace_flags, ace_size)) = tuple((le_u8, le_u8, le_u16))(input)?;
+    let ace_type = ACEType::try_from(ace_type).map_err(|_| unsupported(header_start))?;
 
     Ok((
         input,
         ACEHeader {
-            ace_type: ACEType::from(ace_type),
+            ace_type,
             ace_flags: ACEFlags::new(ace_flags),
             ace_size,
         },
@@ -504,4 +657,59 @@ mod tests {
             assert!(false, "Failed to parse ACE");
         }
     }
+
+    // S-1-5-32-544
+    const ADMINISTRATORS: [u8; 16] = [1, 2, 0, 0, 0, 0, 0, 5, 32, 0, 0, 0, 32, 2, 0, 0];
+
+    #[test]
+    fn test_access_allowed_callback_ace() {
+        let mut bytes = vec![0x09, 0x00, 32, 0];
+        bytes.extend_from_slice(&0x10000000u32.to_le_bytes());
+        bytes.extend_from_slice(&ADMINISTRATORS);
+        bytes.extend_from_slice(b"artx\x00\x00\x00\x00");
+        bytes.extend_from_slice(&[0xAA, 0xBB]);
+
+        let (rest, ace) = parse_ace(&bytes).unwrap();
+        assert_eq!(rest, &[0xAA, 0xBB]);
+        assert_eq!(ace.sid().to_string(), "S-1-5-32-544");
+        assert_eq!(ace.header().ace_type, ACEType::AccessAllowedCallback);
+        match ace {
+            ACE::AccessAllowedCallback(ace) => {
+                assert_eq!(ace.application_data, b"artx\x00\x00\x00\x00")
+            }
+            other => panic!("unexpected ACE: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_access_denied_callback_object_ace() {
+        let object_type: GUID = "bf9679c0-0de6-11d0-a285-00aa003049e2".parse().unwrap();
+        let mut bytes = vec![0x0C, 0x00, 48, 0];
+        bytes.extend_from_slice(&0x20u32.to_le_bytes());
+        bytes.extend_from_slice(&ACE_OBJECT_TYPE_PRESENT.to_le_bytes());
+        bytes.extend(object_type.to_bytes());
+        bytes.extend_from_slice(&ADMINISTRATORS);
+        bytes.extend_from_slice(b"artx");
+
+        let (rest, ace) = parse_ace(&bytes).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(ace.object_type(), Some(&object_type));
+        assert_eq!(ace.object_type_s(), Some(ACEGuid::WriteMember));
+        match ace {
+            ACE::AccessDeniedCallbackObject(ace) => assert_eq!(ace.application_data, b"artx"),
+            other => panic!("unexpected ACE: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_unsupported_ace_types_fail() {
+        // SYSTEM_MANDATORY_LABEL_ACE, then a type past the last defined one
+        for ace_type in [0x11, 0x14] {
+            let mut bytes = vec![ace_type, 0x00, 24, 0];
+            bytes.extend_from_slice(&0x1u32.to_le_bytes());
+            bytes.extend_from_slice(&ADMINISTRATORS);
+
+            assert!(matches!(parse_ace(&bytes), Err(nom::Err::Failure(_))));
+        }
+    }
 }
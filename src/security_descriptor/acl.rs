@@ -1,4 +1,5 @@
 use nom::{
+    bytes::complete::take,
     multi::count,
     number::complete::{le_u16, le_u8},
     sequence::tuple,
@@ -22,23 +23,22 @@ pub fn parse_acl(input: &[u8]) -> IResult<&[u8], ACL> {
     let (input, (acl_revision, sbz1, acl_size, ace_count, sbz2)) =
         tuple((le_u8, le_u8, le_u16, le_u16, le_u16))(input)?;
 
-    // TODO: Handle these errors instead of panicking
-    if acl_revision != 2 && acl_revision != 4 {
-        panic!("ACL revision must be 2 or 4. Got: {}", acl_revision);
+    // Malformed or vendor-specific descriptors show up in real snapshots. Fail
+    // the descriptor rather than the run so callers can skip the object.
+    if (acl_revision != 2 && acl_revision != 4) || sbz1 != 0 || sbz2 != 0 || acl_size < 8 {
+        return Err(nom::Err::Failure(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Verify,
+        )));
     }
 
-    if sbz1 != 0 {
-        panic!("sbz1 must be 0. Got: {}", sbz1);
-    }
-
-    if sbz2 != 0 {
-        panic!("sbz2 must be 0. Got: {}", sbz2);
-    }
-
-    let (input, aces) = count(parse_ace, ace_count as usize)(input)?;
+    // acl_size covers the 8-byte header, the ACEs and any trailing padding.
+    // Consume all of it so whatever follows the ACL starts at the right offset.
+    let (rest, body) = take(acl_size as usize - 8)(input)?;
+    let (_, aces) = count(parse_ace, ace_count as usize)(body)?;
 
     Ok((
-        input,
+        rest,
         ACL {
             acl_revision,
             sbz1,
@@ -49,3 +49,41 @@ pub fn parse_acl(input: &[u8]) -> IResult<&[u8], ACL> {
         },
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_padded_acl_consumes_acl_size() {
+        // ACCESS_ALLOWED_ACE granting GenericAll to BUILTIN\Administrators
+        let mut ace = vec![0, 0, 24, 0];
+        ace.extend_from_slice(&0x10000000u32.to_le_bytes());
+        ace.extend_from_slice(&[1, 2, 0, 0, 0, 0, 0, 5, 32, 0, 0, 0, 32, 2, 0, 0]);
+
+        let mut input = vec![2, 0];
+        input.extend_from_slice(&40u16.to_le_bytes());
+        input.extend_from_slice(&1u16.to_le_bytes());
+        input.extend_from_slice(&0u16.to_le_bytes());
+        input.extend(ace);
+        input.extend(vec![0u8; 8]);
+        // Start of the next security descriptor component
+        input.extend_from_slice(&[1, 1, 0, 0, 0, 0, 0, 5]);
+
+        let (rest, acl) = parse_acl(&input).unwrap();
+        assert_eq!(acl.aces.len(), 1);
+        assert_eq!(acl.aces[0].sid().to_string(), "S-1-5-32-544");
+        assert_eq!(rest, &[1, 1, 0, 0, 0, 0, 0, 5]);
+    }
+
+    #[test]
+    fn test_invalid_acl_header_fails() {
+        for header in [[3u8, 0], [2, 1]] {
+            let mut input = header.to_vec();
+            input.extend_from_slice(&8u16.to_le_bytes());
+            input.extend_from_slice(&[0, 0, 0, 0]);
+
+            assert!(matches!(parse_acl(&input), Err(nom::Err::Failure(_))));
+        }
+    }
+}
@@ -1,5 +1,6 @@
-use crate::security_descriptor::ControlFlags;
+use crate::guid::GUID;
 use crate::security_descriptor::{parse_acl, ACL};
+use crate::security_descriptor::{ACEFlags, AccessMask, ControlFlag, ControlFlags, ACE};
 use crate::sid::SID;
 use serde::Serialize;
 
@@ -28,6 +29,172 @@ impl SDDL {
         let (_, sddl) = parse_sddl(input)?;
         Ok(sddl)
     }
+
+    /// Renders the owner, group and DACL in SDDL notation, as `Get-Acl`
+    /// shows them. Domain-relative aliases such as `DA` depend on the
+    /// reader's domain, so those SIDs are written out in full, and the
+    /// conditions of callback ACEs are left out.
+    pub fn to_sddl_string(&self) -> String {
+        let mut sddl = String::new();
+        if let Some(owner) = &self.owner_sid {
+            sddl.push_str("O:");
+            sddl.push_str(&sid_string(owner));
+        }
+        if let Some(group) = &self.group_sid {
+            sddl.push_str("G:");
+            sddl.push_str(&sid_string(group));
+        }
+        if let Some(dacl) = &self.dacl {
+            sddl.push_str("D:");
+            for (flag, code) in [
+                (ControlFlag::PD, "P"),
+                (ControlFlag::DI, "AI"),
+                (ControlFlag::DC, "AR"),
+            ] {
+                if self.control_flags.is_set(flag) {
+                    sddl.push_str(code);
+                }
+            }
+            for ace in &dacl.aces {
+                sddl.push_str(&ace_string(ace));
+            }
+        }
+        sddl
+    }
+}
+
+// https://learn.microsoft.com/en-us/windows/win32/secauthz/sid-strings
+const SID_ALIASES: &[(&str, &str)] = &[
+    ("S-1-1-0", "WD"),
+    ("S-1-3-0", "CO"),
+    ("S-1-3-1", "CG"),
+    ("S-1-3-4", "OW"),
+    ("S-1-5-2", "NU"),
+    ("S-1-5-4", "IU"),
+    ("S-1-5-6", "SU"),
+    ("S-1-5-7", "AN"),
+    ("S-1-5-9", "ED"),
+    ("S-1-5-10", "PS"),
+    ("S-1-5-11", "AU"),
+    ("S-1-5-12", "RC"),
+    ("S-1-5-18", "SY"),
+    ("S-1-5-19", "LS"),
+    ("S-1-5-20", "NS"),
+    ("S-1-5-32-544", "BA"),
+    ("S-1-5-32-545", "BU"),
+    ("S-1-5-32-546", "BG"),
+    ("S-1-5-32-547", "PU"),
+    ("S-1-5-32-548", "AO"),
+    ("S-1-5-32-549", "SO"),
+    ("S-1-5-32-550", "PO"),
+    ("S-1-5-32-551", "BO"),
+    ("S-1-5-32-552", "RE"),
+    ("S-1-5-32-554", "RU"),
+    ("S-1-5-32-555", "RD"),
+    ("S-1-5-32-556", "NO"),
+];
+
+// Exact masks with their own abbreviation, checked before single rights
+const MASK_ALIASES: &[(u32, &str)] = &[
+    (0x001F01FF, "FA"),
+    (0x00120089, "FR"),
+    (0x00120116, "FW"),
+    (0x001200A0, "FX"),
+    (0x000F003F, "KA"),
+    (0x00020019, "KR"),
+    (0x00020006, "KW"),
+];
+
+const MASK_RIGHTS: &[(u32, &str)] = &[
+    (AccessMask::GENERIC_ALL, "GA"),
+    (AccessMask::GENERIC_READ, "GR"),
+    (AccessMask::GENERIC_WRITE, "GW"),
+    (AccessMask::GENERIC_EXECUTE, "GX"),
+    (AccessMask::ADS_RIGHT_DS_CREATE_CHILD, "CC"),
+    (AccessMask::ADS_RIGHT_DS_DELETE_CHILD, "DC"),
+    (AccessMask::ADS_RIGHT_ACTRL_DS_LIST, "LC"),
+    (AccessMask::ADS_RIGHT_DS_SELF, "SW"),
+    (AccessMask::ADS_RIGHT_DS_READ_PROP, "RP"),
+    (AccessMask::ADS_RIGHT_DS_WRITE_PROP, "WP"),
+    (AccessMask::ADS_RIGHT_DS_DELETE_TREE, "DT"),
+    (AccessMask::ADS_RIGHT_DS_LIST_OBJECT, "LO"),
+    (AccessMask::ADS_RIGHT_DS_CONTROL_ACCESS, "CR"),
+    (AccessMask::DELETE, "SD"),
+    (AccessMask::READ_CONTROL, "RC"),
+    (AccessMask::WRITE_DACL, "WD"),
+    (AccessMask::WRITE_OWNER, "WO"),
+];
+
+fn sid_string(sid: &SID) -> String {
+    let sid = sid.to_string();
+    SID_ALIASES
+        .iter()
+        .find(|(full, _)| *full == sid)
+        .map(|(_, alias)| alias.to_string())
+        .unwrap_or(sid)
+}
+
+/// Rights as abbreviations, or the whole mask in hex when some bit has none.
+fn mask_string(mask: AccessMask) -> String {
+    let mask = mask.as_u32();
+    if let Some((_, alias)) = MASK_ALIASES.iter().find(|(value, _)| *value == mask) {
+        return alias.to_string();
+    }
+
+    let known = MASK_RIGHTS.iter().fold(0, |known, (bit, _)| known | bit);
+    if mask & !known != 0 {
+        return format!("0x{:x}", mask);
+    }
+    MASK_RIGHTS
+        .iter()
+        .filter(|(bit, _)| mask & bit != 0)
+        .map(|(_, code)| *code)
+        .collect()
+}
+
+fn ace_string(ace: &ACE) -> String {
+    let ace_type = match ace {
+        ACE::AccessAllowed(_) => "A",
+        ACE::AccessDenied(_) => "D",
+        ACE::AccessAllowedObject(_) => "OA",
+        ACE::AccessDeniedObject(_) => "OD",
+        ACE::SystemAuditObject(_) => "OU",
+        ACE::AccessAllowedCallback(_) => "XA",
+        ACE::AccessDeniedCallback(_) => "XD",
+        ACE::AccessAllowedCallbackObject(_) => "ZA",
+        // SDDL has no abbreviation for this type; ZD follows ZA
+        ACE::AccessDeniedCallbackObject(_) => "ZD",
+    };
+
+    let ace_flags = ace.header().ace_flags;
+    let flags: String = [
+        (ACEFlags::OBJECT_INHERIT_ACE, "OI"),
+        (ACEFlags::CONTAINER_INHERIT_ACE, "CI"),
+        (ACEFlags::NO_PROPAGATE_INHERIT_ACE, "NP"),
+        (ACEFlags::INHERIT_ONLY_ACE, "IO"),
+        (ACEFlags::INHERITED_ACE, "ID"),
+        (ACEFlags::SUCCESSFUL_ACCESS_ACE_FLAG, "SA"),
+        (ACEFlags::FAILED_ACCESS_ACE_FLAG, "FA"),
+    ]
+    .iter()
+    .filter(|(flag, _)| ace_flags.is_set(*flag))
+    .map(|(_, code)| *code)
+    .collect();
+
+    let guid = |guid: Option<&GUID>| {
+        guid.map(|guid| guid.to_string().to_lowercase())
+            .unwrap_or_default()
+    };
+
+    format!(
+        "({};{};{};{};{};{})",
+        ace_type,
+        flags,
+        mask_string(ace.mask()),
+        guid(ace.object_type()),
+        guid(ace.inherited_object_type()),
+        sid_string(ace.sid())
+    )
 }
 
 fn parse_sddl(input: &[u8]) -> IResult<&[u8], SDDL> {
@@ -98,6 +265,10 @@ mod tests {
             231, 253, 164, 49, 227, 252, 93, 36, 9, 237, 3, 0, 0,
         ];
         let sddl = SDDL::from_bytes(&sddl_bytes).unwrap();
+        assert_eq!(
+            sddl.to_sddl_string(),
+            "O:S-1-5-21-3890413604-3811681533-153378300-1005G:S-1-5-21-3890413604-3811681533-153378300-513D:(A;ID;FA;;;SY)(A;ID;FA;;;BA)(A;ID;FA;;;S-1-5-21-3890413604-3811681533-153378300-1005)"
+        );
 
         // We'll add assertions here to check the parsed values
         assert_eq!(sddl.revision, 1);
@@ -161,9 +332,9 @@ mod tests {
         ```
         */
 
-        /*
-        O:BAG:BAD:AI(A;;RP;;;WD)(A;;LCRPLORC;;;ED)(A;;LCRPLORC;;;AU)(A;;CCDCLCSWRPWPDTLOCRSDRCWDWO;;;SY)(A;CI;CCLCSWRPWPLOCRSDRCWDWO;;;BA)(A;;RPRC;;;RU)(A;CI;LC;;;RU)(A;;CCLCSWRPWPLOCRRCWDWO;;;DA)(A;CI;CCDCLCSWRPWPDTLOCRSDRCWDWO;;;S-1-5-21-1935163693-1572912069-975596842-519)(OA;CIIO;SW;9b026da6-0d3c-465c-8bee-5199d7165cba;bf967a86-0de6-11d0-a285-00aa003049e2;CO)(OA;;CR;1131f6ac-9c07-11d1-f79f-00c04fc2dcd2;;ED)(OA;;CR;1131f6ab-9c07-11d1-f79f-00c04fc2dcd2;;ED)(OA;;CR;1131f6aa-9c07-11d1-f79f-00c04fc2dcd2;;ED)(OA;;CR;89e95b76-444d-4c62-991a-0facbeda640c;;ED)(OA;CIIO;RP;b7c69e6d-2cc7-11d2-854e-00a0c983f608;bf967aba-0de6-11d0-a285-00aa003049e2;ED)(OA;;CR;1131f6ae-9c07-11d1-f79f-00c04fc2dcd2;;ED)(OA;CIIO;RP;b7c69e6d-2cc7-11d2-854e-00a0c983f608;bf967a9c-0de6-11d0-a285-00aa003049e2;ED)(OA;CIIO;RP;b7c69e6d-2cc7-11d2-854e-00a0c983f608;bf967a86-0de6-11d0-a285-00aa003049e2;ED)(OA;CIIO;SW;9b026da6-0d3c-465c-8bee-5199d7165cba;bf967a86-0de6-11d0-a285-00aa003049e2;PS)(OA;CIIO;RPWPCR;91e647de-d96f-4b70-9557-d63ff4f3ccd8;;PS)(OA;CIIO;WP;ea1b7b93-5e48-46d5-bc6c-4df4fda78a35;bf967a86-0de6-11d0-a285-00aa003049e2;PS)(OA;OICI;RPWP;3f78c3e5-f79a-46bd-a0b8-9d18116ddc79;;PS)(OA;;CR;05c74c5e-4deb-43b4-bd9f-86664c2a7fd5;;AU)(OA;;CR;ccc2dc7d-a6ad-4a7a-8846-c04e3cc53501;;AU)(OA;;CR;280f369c-67c7-438e-ae98-1d46f3c6f541;;AU)(OA;;RP;b8119fd0-04f6-4762-ab7a-4986c76b3f9a;;AU)(OA;;CR;1131f6ae-9c07-11d1-f79f-00c04fc2dcd2;;BA)(OA;;CR;1131f6ad-9c07-11d1-f79f-00c04fc2dcd2;;BA)(OA;;CR;1131f6ac-9c07-11d1-f79f-00c04fc2dcd2;;BA)(OA;;CR;1131f6ab-9c07-11d1-f79f-00c04fc2dcd2;;BA)(OA;;CR;1131f6aa-9c07-11d1-f79f-00c04fc2dcd2;;BA)(OA;;CR;89e95b76-444d-4c62-991a-0facbeda640c;;BA)(OA;CIIO;LCRPLORC;;bf967aba-0de6-11d0-a285-00aa003049e2;RU)(OA;CIIO;LCRPLORC;;bf967a9c-0de6-11d0-a285-00aa003049e2;RU)(OA;CIIO;RP;59ba2f42-79a2-11d0-9020-00c04fc2d3cf;bf967aba-0de6-11d0-a285-00aa003049e2;RU)(OA;CIIO;LCRPLORC;;4828cc14-1437-45bc-9b07-ad6f015e5f28;RU)(OA;;RP;b8119fd0-04f6-4762-ab7a-4986c76b3f9a;;RU)(OA;CIIO;RP;037088f8-0ae1-11d2-b422-00a0c968f939;4828cc14-1437-45bc-9b07-ad6f015e5f28;RU)(OA;CIIO;RP;4c164200-20c0-11d0-a768-00aa006e0529;bf967aba-0de6-11d0-a285-00aa003049e2;RU)(OA;CIIO;RP;5f202010-79a5-11d0-9020-00c04fc2d4cf;4828cc14-1437-45bc-9b07-ad6f015e5f28;RU)(OA;CIIO;RP;5f202010-79a5-11d0-9020-00c04fc2d4cf;bf967aba-0de6-11d0-a285-00aa003049e2;RU)(OA;;RP;c7407360-20bf-11d0-a768-00aa006e0529;;RU)(OA;CIIO;RP;59ba2f42-79a2-11d0-9020-00c04fc2d3cf;4828cc14-1437-45bc-9b07-ad6f015e5f28;RU)(OA;CIIO;RP;037088f8-0ae1-11d2-b422-00a0c968f939;bf967aba-0de6-11d0-a285-00aa003049e2;RU)(OA;CIIO;RP;bc0ac240-79a9-11d0-9020-00c04fc2d4cf;bf967aba-0de6-11d0-a285-00aa003049e2;RU)(OA;CIIO;RP;bc0ac240-79a9-11d0-9020-00c04fc2d4cf;4828cc14-1437-45bc-9b07-ad6f015e5f28;RU)(OA;CIIO;RP;4c164200-20c0-11d0-a768-00aa006e0529;4828cc14-1437-45bc-9b07-ad6f015e5f28;RU)(OA;;CR;e2a36dc9-ae17-47c3-b58b-be34c55ba633;;S-1-5-32-557)(OA;;CR;1131f6aa-9c07-11d1-f79f-00c04fc2dcd2;;S-1-5-21-1935163693-1572912069-975596842-498)(OA;;CR;1131f6ad-9c07-11d1-f79f-00c04fc2dcd2;;DD)(OA;;CR;3e0f7e18-2c7a-4c10-ba82-4d926db99a3e;;S-1-5-21-1935163693-1572912069-975596842-522)(OA;CI;RPWP;5b47d60f-6090-40b2-9f37-2a4de88f3063;;S-1-5-21-1935163693-1572912069-975596842-526)(OA;CI;RPWP;5b47d60f-6090-40b2-9f37-2a4de88f3063;;S-1-5-21-1935163693-1572912069-975596842-527)
-        */
+        // Get-Acl output for the bytes below
+        let get_acl_sddl = "O:BAG:BAD:AI(A;;RP;;;WD)(A;;LCRPLORC;;;ED)(A;;LCRPLORC;;;AU)(A;;CCDCLCSWRPWPDTLOCRSDRCWDWO;;;SY)(A;CI;CCLCSWRPWPLOCRSDRCWDWO;;;BA)(A;;RPRC;;;RU)(A;CI;LC;;;RU)(A;;CCLCSWRPWPLOCRRCWDWO;;;DA)(A;CI;CCDCLCSWRPWPDTLOCRSDRCWDWO;;;S-1-5-21-1935163693-1572912069-975596842-519)(OA;CIIO;SW;9b026da6-0d3c-465c-8bee-5199d7165cba;bf967a86-0de6-11d0-a285-00aa003049e2;CO)(OA;;CR;1131f6ac-9c07-11d1-f79f-00c04fc2dcd2;;ED)(OA;;CR;1131f6ab-9c07-11d1-f79f-00c04fc2dcd2;;ED)(OA;;CR;1131f6aa-9c07-11d1-f79f-00c04fc2dcd2;;ED)(OA;;CR;89e95b76-444d-4c62-991a-0facbeda640c;;ED)(OA;CIIO;RP;b7c69e6d-2cc7-11d2-854e-00a0c983f608;bf967aba-0de6-11d0-a285-00aa003049e2;ED)(OA;;CR;1131f6ae-9c07-11d1-f79f-00c04fc2dcd2;;ED)(OA;CIIO;RP;b7c69e6d-2cc7-11d2-854e-00a0c983f608;bf967a9c-0de6-11d0-a285-00aa003049e2;ED)(OA;CIIO;RP;b7c69e6d-2cc7-11d2-854e-00a0c983f608;bf967a86-0de6-11d0-a285-00aa003049e2;ED)(OA;CIIO;SW;9b026da6-0d3c-465c-8bee-5199d7165cba;bf967a86-0de6-11d0-a285-00aa003049e2;PS)(OA;CIIO;RPWPCR;91e647de-d96f-4b70-9557-d63ff4f3ccd8;;PS)(OA;CIIO;WP;ea1b7b93-5e48-46d5-bc6c-4df4fda78a35;bf967a86-0de6-11d0-a285-00aa003049e2;PS)(OA;OICI;RPWP;3f78c3e5-f79a-46bd-a0b8-9d18116ddc79;;PS)(OA;;CR;05c74c5e-4deb-43b4-bd9f-86664c2a7fd5;;AU)(OA;;CR;ccc2dc7d-a6ad-4a7a-8846-c04e3cc53501;;AU)(OA;;CR;280f369c-67c7-438e-ae98-1d46f3c6f541;;AU)(OA;;RP;b8119fd0-04f6-4762-ab7a-4986c76b3f9a;;AU)(OA;;CR;1131f6ae-9c07-11d1-f79f-00c04fc2dcd2;;BA)(OA;;CR;1131f6ad-9c07-11d1-f79f-00c04fc2dcd2;;BA)(OA;;CR;1131f6ac-9c07-11d1-f79f-00c04fc2dcd2;;BA)(OA;;CR;1131f6ab-9c07-11d1-f79f-00c04fc2dcd2;;BA)(OA;;CR;1131f6aa-9c07-11d1-f79f-00c04fc2dcd2;;BA)(OA;;CR;89e95b76-444d-4c62-991a-0facbeda640c;;BA)(OA;CIIO;LCRPLORC;;bf967aba-0de6-11d0-a285-00aa003049e2;RU)(OA;CIIO;LCRPLORC;;bf967a9c-0de6-11d0-a285-00aa003049e2;RU)(OA;CIIO;RP;59ba2f42-79a2-11d0-9020-00c04fc2d3cf;bf967aba-0de6-11d0-a285-00aa003049e2;RU)(OA;CIIO;LCRPLORC;;4828cc14-1437-45bc-9b07-ad6f015e5f28;RU)(OA;;RP;b8119fd0-04f6-4762-ab7a-4986c76b3f9a;;RU)(OA;CIIO;RP;037088f8-0ae1-11d2-b422-00a0c968f939;4828cc14-1437-45bc-9b07-ad6f015e5f28;RU)(OA;CIIO;RP;4c164200-20c0-11d0-a768-00aa006e0529;bf967aba-0de6-11d0-a285-00aa003049e2;RU)(OA;CIIO;RP;5f202010-79a5-11d0-9020-00c04fc2d4cf;4828cc14-1437-45bc-9b07-ad6f015e5f28;RU)(OA;CIIO;RP;5f202010-79a5-11d0-9020-00c04fc2d4cf;bf967aba-0de6-11d0-a285-00aa003049e2;RU)(OA;;RP;c7407360-20bf-11d0-a768-00aa006e0529;;RU)(OA;CIIO;RP;59ba2f42-79a2-11d0-9020-00c04fc2d3cf;4828cc14-1437-45bc-9b07-ad6f015e5f28;RU)(OA;CIIO;RP;037088f8-0ae1-11d2-b422-00a0c968f939;bf967aba-0de6-11d0-a285-00aa003049e2;RU)(OA;CIIO;RP;bc0ac240-79a9-11d0-9020-00c04fc2d4cf;bf967aba-0de6-11d0-a285-00aa003049e2;RU)(OA;CIIO;RP;bc0ac240-79a9-11d0-9020-00c04fc2d4cf;4828cc14-1437-45bc-9b07-ad6f015e5f28;RU)(OA;CIIO;RP;4c164200-20c0-11d0-a768-00aa006e0529;4828cc14-1437-45bc-9b07-ad6f015e5f28;RU)(OA;;CR;e2a36dc9-ae17-47c3-b58b-be34c55ba633;;S-1-5-32-557)(OA;;CR;1131f6aa-9c07-11d1-f79f-00c04fc2dcd2;;S-1-5-21-1935163693-1572912069-975596842-498)(OA;;CR;1131f6ad-9c07-11d1-f79f-00c04fc2dcd2;;DD)(OA;;CR;3e0f7e18-2c7a-4c10-ba82-4d926db99a3e;;S-1-5-21-1935163693-1572912069-975596842-522)(OA;CI;RPWP;5b47d60f-6090-40b2-9f37-2a4de88f3063;;S-1-5-21-1935163693-1572912069-975596842-526)(OA;CI;RPWP;5b47d60f-6090-40b2-9f37-2a4de88f3063;;S-1-5-21-1935163693-1572912069-975596842-527)";
+
         let sddl_bytes = vec![
             1, 0, 4, 132, 20, 0, 0, 0, 36, 0, 0, 0, 0, 0, 0, 0, 52, 0, 0, 0, 1, 2, 0, 0, 0, 0, 0,
             5, 32, 0, 0, 0, 32, 2, 0, 0, 1, 2, 0, 0, 0, 0, 0, 5, 32, 0, 0, 0, 32, 2, 0, 0, 4, 0,
@@ -273,6 +444,11 @@ mod tests {
             187, 192, 93, 42, 109, 38, 58, 15, 2, 0, 0,
         ];
         let sddl = SDDL::from_bytes(&sddl_bytes).unwrap();
+        // Domain-relative aliases are rendered as full SIDs
+        let expected = get_acl_sddl
+            .replace(";DA)", ";S-1-5-21-1935163693-1572912069-975596842-512)")
+            .replace(";DD)", ";S-1-5-21-1935163693-1572912069-975596842-516)");
+        assert_eq!(sddl.to_sddl_string(), expected);
         assert_eq!(sddl.owner_sid.unwrap().to_string(), "S-1-5-32-544");
         assert_eq!(sddl.group_sid.unwrap().to_string(), "S-1-5-32-544");
     }
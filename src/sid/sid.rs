@@ -1,12 +1,16 @@
 use core::hash::{Hash, Hasher};
 use core::str::FromStr;
 use nom::{
-    bits::complete::take, error::Error, multi::count, number::complete::le_u32, sequence::tuple,
+    bits::complete::take,
+    error::{Error, ErrorKind},
+    multi::count,
+    number::complete::le_u32,
+    sequence::tuple,
     IResult,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct SID {
     revision: u8,
     sub_authority_count: u8,
@@ -24,6 +28,15 @@ impl SID {
         parse_sid(input)
     }
 
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.revision, self.sub_authority_count];
+        bytes.extend_from_slice(&self.identifier_authority);
+        for sub_auth in &self.sub_authorities[..self.sub_authority_count as usize] {
+            bytes.extend_from_slice(&sub_auth.to_le_bytes());
+        }
+        bytes
+    }
+
     pub fn to_string(&self) -> String {
         let auth = u64::from_be_bytes([
             0,
@@ -54,6 +67,12 @@ fn parse_sid(input: &[u8]) -> IResult<&[u8], SID> {
         }),
     ))(input)?;
 
+    // Only revision 1 exists, and at most 15 sub-authorities fit. Checking
+    // both keeps other binary values, like GUIDs, from reading as a SID.
+    if revision != 1 || sub_authority_count > 15 {
+        return Err(nom::Err::Error(Error::new(input, ErrorKind::Verify)));
+    }
+
     let (input, sub_authorities) = count(le_u32, sub_authority_count as usize)(input)?;
 
     let mut sid = SID {
@@ -119,6 +138,16 @@ mod tests {
     use std::collections::hash_map::DefaultHasher;
     use std::hash::{Hash, Hasher};
 
+    #[test]
+    fn test_invalid_sid_rejected() {
+        // Revision 2
+        assert!(SID::from_bytes(&[2, 1, 0, 0, 0, 0, 0, 5, 18, 0, 0, 0]).is_err());
+        // More sub-authorities than a SID can hold
+        let mut too_many = vec![1, 16, 0, 0, 0, 0, 0, 5];
+        too_many.extend(vec![0u8; 64]);
+        assert!(SID::from_bytes(&too_many).is_err());
+    }
+
     #[test]
     fn test_sid_creation_and_to_string() {
         let octet_string = vec![1, 2, 0, 0, 0, 0, 0, 5, 32, 0, 0, 0, 32, 2, 0, 0];
@@ -162,6 +191,19 @@ mod tests {
         assert_eq!(hasher1.finish(), hasher2.finish());
     }
 
+    #[test]
+    fn test_sid_to_bytes_round_trip() {
+        let octet_string = vec![
+            1, 5, 0, 0, 0, 0, 0, 5, 21, 0, 0, 0, 45, 65, 88, 115, 197, 187, 192, 93, 42, 109, 38,
+            58, 80, 4, 0, 0,
+        ];
+        let sid = SID::from_bytes(&octet_string).unwrap();
+        assert_eq!(sid.to_bytes(), octet_string);
+
+        let parsed: SID = "S-1-5-32-544".parse().unwrap();
+        assert_eq!(SID::from_bytes(&parsed.to_bytes()).unwrap(), parsed);
+    }
+
     #[test]
     fn test_from_next_bytes() {
         let input = vec![
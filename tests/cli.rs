@@ -0,0 +1,301 @@
+mod common;
+
+use common::{guid, sid, string, SnapshotBuilder, Value, DOMAIN_DN, DOMAIN_SID, SCHEMA_DN};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const OUTPUTS: [&str; 7] = [
+    "computers.json",
+    "containers.json",
+    "domains.json",
+    "gpos.json",
+    "groups.json",
+    "ous.json",
+    "users.json",
+];
+const GPO_GUID: &str = "31b2f340-016d-11d2-945f-00c04fb984f9";
+
+/// One object of every output type, each with attributes the outputs read
+/// and some they don't.
+fn fixture() -> SnapshotBuilder {
+    let user_classes = || {
+        vec![
+            ("objectClass", string("top")),
+            ("objectClass", string("person")),
+            ("objectClass", string("organizationalPerson")),
+            ("objectClass", string("user")),
+        ]
+    };
+    let dn = |rdn: &str| {
+        (
+            "distinguishedName",
+            string(&format!("{},{}", rdn, DOMAIN_DN)),
+        )
+    };
+
+    SnapshotBuilder::new("DC01.example.com", 133537698450000000)
+        .class("person", "Person")
+        .class("computer", "Computer")
+        .class("group", "Group")
+        .object(vec![
+            ("objectClass", string("top")),
+            ("objectClass", string("domain")),
+            ("objectClass", string("domainDNS")),
+            ("distinguishedName", string(DOMAIN_DN)),
+            ("name", string("EXAMPLE")),
+            ("objectSid", sid(DOMAIN_SID)),
+            ("objectGUID", guid("6e4b8c52-1d9a-4f3b-9a6e-2f1c0b7d5a11")),
+            ("msDS-Behavior-Version", Value::Integer(7)),
+            (
+                "gPLink",
+                string(&format!(
+                    "[LDAP://CN={{{}}},CN=Policies,CN=System,{};0]",
+                    GPO_GUID, DOMAIN_DN
+                )),
+            ),
+        ])
+        .object(vec![
+            ("objectClass", string("top")),
+            ("objectClass", string("leaf")),
+            ("objectClass", string("trustedDomain")),
+            dn("CN=REALM.EXAMPLE.ORG,CN=System"),
+            ("name", string("REALM.EXAMPLE.ORG")),
+            ("trustAttributes", Value::Integer(0)),
+            ("trustDirection", Value::Integer(3)),
+            ("trustType", Value::Integer(3)),
+        ])
+        .object(
+            user_classes()
+                .into_iter()
+                .chain(vec![
+                    (
+                        "objectCategory",
+                        string(&format!("CN=Person,{}", SCHEMA_DN)),
+                    ),
+                    dn("CN=alice,CN=Users"),
+                    ("name", string("alice")),
+                    ("sAMAccountName", string("alice")),
+                    ("sAMAccountType", Value::Integer(0x30000000)),
+                    ("userAccountControl", Value::Integer(0x200)),
+                    ("objectSid", sid(&format!("{}-1100", DOMAIN_SID))),
+                    ("primaryGroupID", Value::Integer(513)),
+                    ("description", string("Alice")),
+                    ("servicePrincipalName", string("HTTP/web.example.com")),
+                    ("pwdLastSet", Value::LargeInteger(133537698450000000)),
+                    ("mail", string("alice@example.com")),
+                    ("telephoneNumber", string("555-0100")),
+                ])
+                .collect(),
+        )
+        .object(
+            user_classes()
+                .into_iter()
+                .chain(vec![
+                    ("objectClass", string("computer")),
+                    (
+                        "objectCategory",
+                        string(&format!("CN=Computer,{}", SCHEMA_DN)),
+                    ),
+                    dn("CN=WS01,OU=Workstations"),
+                    ("name", string("WS01")),
+                    ("sAMAccountName", string("WS01$")),
+                    ("sAMAccountType", Value::Integer(0x30000001)),
+                    ("userAccountControl", Value::Integer(0x1000)),
+                    ("objectSid", sid(&format!("{}-1101", DOMAIN_SID))),
+                    ("primaryGroupID", Value::Integer(515)),
+                    ("dNSHostName", string("ws01.example.com")),
+                    ("operatingSystem", string("Windows 11 Enterprise")),
+                    (
+                        "ms-Mcs-AdmPwdExpirationTime",
+                        Value::LargeInteger(133537698450000000),
+                    ),
+                    ("location", string("Building 1")),
+                ])
+                .collect(),
+        )
+        .object(vec![
+            ("objectClass", string("top")),
+            ("objectClass", string("group")),
+            ("objectCategory", string(&format!("CN=Group,{}", SCHEMA_DN))),
+            dn("CN=Helpdesk,CN=Users"),
+            ("name", string("Helpdesk")),
+            ("sAMAccountName", string("Helpdesk")),
+            ("sAMAccountType", Value::Integer(0x10000000)),
+            ("objectSid", sid(&format!("{}-1102", DOMAIN_SID))),
+            (
+                "member",
+                string(&format!("CN=alice,CN=Users,{}", DOMAIN_DN)),
+            ),
+            ("info", string("Tier 2")),
+        ])
+        .object(vec![
+            ("objectClass", string("top")),
+            ("objectClass", string("organizationalUnit")),
+            dn("OU=Workstations"),
+            ("name", string("Workstations")),
+            ("objectGUID", guid("0b1f6c9e-8a2d-4c3b-9e7f-5d4a3b2c1d0e")),
+            ("street", string("1 Main St")),
+        ])
+        .object(vec![
+            ("objectClass", string("top")),
+            ("objectClass", string("container")),
+            dn("CN=Users"),
+            ("name", string("Users")),
+            ("objectGUID", guid("7c2e9a41-3b5d-4f6e-8a1c-2d3e4f5a6b7c")),
+        ])
+        .object(vec![
+            ("objectClass", string("top")),
+            ("objectClass", string("container")),
+            ("objectClass", string("groupPolicyContainer")),
+            dn(&format!("CN={{{}}},CN=Policies,CN=System", GPO_GUID)),
+            ("name", string(&format!("{{{}}}", GPO_GUID))),
+            ("displayName", string("Default Domain Policy")),
+            (
+                "gPCFileSysPath",
+                string(&format!(
+                    "\\\\example.com\\sysvol\\example.com\\Policies\\{{{}}}",
+                    GPO_GUID
+                )),
+            ),
+            ("objectGUID", guid(GPO_GUID)),
+            ("versionNumber", Value::Integer(3)),
+        ])
+}
+
+fn convertsnapshot(dir: &Path, args: &[&str]) {
+    let status = Command::new(env!("CARGO_BIN_EXE_convertsnapshot"))
+        .current_dir(dir)
+        .args(args)
+        .arg("-q")
+        .status()
+        .unwrap();
+    assert!(status.success());
+}
+
+/// An empty directory in the temp directory named after `name`.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("adexplorersnapshot-cli-{}", name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Converts the fixture into a directory of JSON files and reads them back.
+fn convert_to_dir(dir: &Path, name: &str, flags: &[&str]) -> BTreeMap<String, serde_json::Value> {
+    let input = fixture().write(&format!("cli-{}", name));
+    let output = dir.join(name);
+    let mut args = vec!["--format", "dir", "-o", output.to_str().unwrap()];
+    args.extend(flags);
+    args.push(input.to_str().unwrap());
+    convertsnapshot(dir, &args);
+    std::fs::remove_file(&input).unwrap();
+
+    std::fs::read_dir(&output)
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let json = std::fs::read_to_string(&path).unwrap();
+            (
+                path.file_name().unwrap().to_string_lossy().into_owned(),
+                serde_json::from_str(&json).unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_archive_contains_all_outputs() {
+    let dir = temp_dir("archive");
+    let input = fixture().write("cli-archive");
+    convertsnapshot(&dir, &["-o", "out.tar.gz", input.to_str().unwrap()]);
+    std::fs::remove_file(&input).unwrap();
+
+    let file = std::fs::File::open(dir.join("out.tar.gz")).unwrap();
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+    let mut names = Vec::new();
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let mut json = String::new();
+        entry.read_to_string(&mut json).unwrap();
+        serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        names.push(entry.path().unwrap().display().to_string());
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+    names.sort();
+
+    assert_eq!(names, OUTPUTS);
+}
+
+#[test]
+fn test_output_selection() {
+    let dir = temp_dir("selection");
+
+    let only = convert_to_dir(&dir, "only", &["--only", "users,groups"]);
+    let exclude = convert_to_dir(&dir, "exclude", &["--exclude", "gpos,ous,containers"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(
+        only.keys().collect::<Vec<_>>(),
+        ["groups.json", "users.json"]
+    );
+    assert_eq!(
+        exclude.keys().collect::<Vec<_>>(),
+        [
+            "computers.json",
+            "domains.json",
+            "groups.json",
+            "users.json"
+        ]
+    );
+}
+
+#[test]
+fn test_default_output_name() {
+    let dir = temp_dir("name");
+    let input = fixture().write("cli-name");
+    let input = input.to_str().unwrap();
+    convertsnapshot(&dir, &[input]);
+    convertsnapshot(&dir, &["-c", "0", input]);
+    convertsnapshot(&dir, &["--format", "dir", input]);
+    std::fs::remove_file(input).unwrap();
+
+    // Captured 2024-03-01 12:30:45 UTC
+    let mut names: Vec<String> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    std::fs::remove_dir_all(&dir).unwrap();
+    names.sort();
+
+    assert_eq!(
+        names,
+        [
+            "DC01.example.com_20240301123045",
+            "DC01.example.com_20240301123045.tar",
+            "DC01.example.com_20240301123045.tar.gz",
+        ]
+    );
+}
+
+/// `REQUIRED_ATTRIBUTES` is kept by hand, so every output is compared against
+/// a fully parsed snapshot to catch attributes missing from it.
+#[test]
+fn test_low_memory_outputs_match() {
+    let dir = temp_dir("low-memory");
+
+    let full = convert_to_dir(&dir, "full", &[]);
+    let low_memory = convert_to_dir(&dir, "low-memory", &["--low-memory"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(full.keys().collect::<Vec<_>>(), OUTPUTS);
+    for (name, output) in &full {
+        assert!(output["meta"]["count"].as_u64().unwrap() > 0, "{}", name);
+    }
+    assert_eq!(
+        full["domains.json"]["data"][0]["Trusts"][0]["TrustType"],
+        "MIT"
+    );
+    assert_eq!(low_memory, full);
+}
@@ -0,0 +1,275 @@
+//! Builds small synthetic snapshot files, so the whole pipeline can be tested
+//! without a real capture.
+
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+pub const DOMAIN_DN: &str = "DC=EXAMPLE,DC=COM";
+pub const DOMAIN_SID: &str = "S-1-5-21-1000-2000-3000";
+pub const SCHEMA_DN: &str = "CN=Schema,CN=Configuration,DC=EXAMPLE,DC=COM";
+
+/// Offset of the first object, right after the fixed-size header.
+const OBJECTS_OFFSET: usize = 0x43e;
+
+/// An attribute value, along with the ADSTYPE its property is declared with.
+pub enum Value {
+    String(String),
+    Integer(u32),
+    LargeInteger(i64),
+    OctetString(Vec<u8>),
+}
+
+impl Value {
+    fn ads_type(&self) -> u32 {
+        match self {
+            Value::String(_) => 3,
+            Value::OctetString(_) => 8,
+            Value::Integer(_) => 7,
+            Value::LargeInteger(_) => 10,
+        }
+    }
+}
+
+pub fn string(value: &str) -> Value {
+    Value::String(value.to_string())
+}
+
+pub fn sid(value: &str) -> Value {
+    let parts: Vec<u64> = value
+        .trim_start_matches("S-")
+        .split('-')
+        .map(|part| part.parse().unwrap())
+        .collect();
+    let mut bytes = vec![parts[0] as u8, (parts.len() - 2) as u8];
+    bytes.extend_from_slice(&parts[1].to_be_bytes()[2..]);
+    for sub_authority in &parts[2..] {
+        bytes.extend_from_slice(&(*sub_authority as u32).to_le_bytes());
+    }
+    Value::OctetString(bytes)
+}
+
+/// The mixed-endian bytes AD stores for a GUID in its string form.
+pub fn guid_bytes(value: &str) -> Vec<u8> {
+    let hex: String = value.chars().filter(|c| *c != '-').collect();
+    let raw: Vec<u8> = (0..16)
+        .map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap())
+        .collect();
+    let mut bytes: Vec<u8> = raw[..4].iter().rev().copied().collect();
+    bytes.extend(raw[4..6].iter().rev());
+    bytes.extend(raw[6..8].iter().rev());
+    bytes.extend(&raw[8..]);
+    bytes
+}
+
+pub fn guid(value: &str) -> Value {
+    Value::OctetString(guid_bytes(value))
+}
+
+pub struct SnapshotBuilder {
+    server: String,
+    filetime: u64,
+    properties: Vec<(String, u32)>,
+    objects: Vec<Vec<(usize, Value)>>,
+    classes: Vec<(String, String)>,
+}
+
+impl SnapshotBuilder {
+    pub fn new(server: &str, filetime: u64) -> Self {
+        SnapshotBuilder {
+            server: server.to_string(),
+            filetime,
+            properties: Vec::new(),
+            objects: Vec::new(),
+            classes: Vec::new(),
+        }
+    }
+
+    /// Adds a schema class, e.g. ("person", "Person").
+    pub fn class(mut self, name: &str, common_name: &str) -> Self {
+        self.classes
+            .push((name.to_string(), common_name.to_string()));
+        self
+    }
+
+    /// Adds an object. Attributes with several values repeat their name.
+    pub fn object(mut self, attributes: Vec<(&str, Value)>) -> Self {
+        let mut values = Vec::new();
+        for (name, value) in attributes {
+            let index = match self.properties.iter().position(|(n, _)| n == name) {
+                Some(index) => index,
+                None => {
+                    self.properties.push((name.to_string(), value.ads_type()));
+                    self.properties.len() - 1
+                }
+            };
+            values.push((index, value));
+        }
+        self.objects.push(values);
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let objects: Vec<Vec<u8>> = self.objects.iter().map(|o| object_bytes(o)).collect();
+        let tables_offset = OBJECTS_OFFSET + objects.iter().map(Vec::len).sum::<usize>();
+
+        let mut bytes = b"win-ad-ob\0".to_vec();
+        bytes.extend_from_slice(&0i32.to_le_bytes());
+        bytes.extend_from_slice(&self.filetime.to_le_bytes());
+        bytes.extend(fixed_wstring("", 260));
+        bytes.extend(fixed_wstring(&self.server, 260));
+        bytes.extend_from_slice(&(objects.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.properties.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(tables_offset as u32).to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&0i32.to_le_bytes());
+        assert_eq!(bytes.len(), OBJECTS_OFFSET);
+
+        for object in objects {
+            bytes.extend(object);
+        }
+
+        bytes.extend_from_slice(&(self.properties.len() as u32).to_le_bytes());
+        for (name, ads_type) in &self.properties {
+            bytes.extend(wstring(name));
+            bytes.extend_from_slice(&0i32.to_le_bytes());
+            bytes.extend_from_slice(&ads_type.to_le_bytes());
+            bytes.extend(wstring(&format!("CN={},{}", name, SCHEMA_DN)));
+            bytes.extend(vec![0u8; 16 + 16 + 4]);
+        }
+
+        bytes.extend_from_slice(&(self.classes.len() as u32).to_le_bytes());
+        for (name, common_name) in &self.classes {
+            bytes.extend(wstring(name));
+            bytes.extend(wstring(&format!("CN={},{}", common_name, SCHEMA_DN)));
+            bytes.extend(wstring(common_name));
+            bytes.extend(wstring("top"));
+            bytes.extend(vec![0u8; 16]);
+            // No unk2, blocks, unknown entries, superiors or auxiliary classes
+            bytes.extend(vec![0u8; 4 * 5]);
+        }
+
+        // No extended rights
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes
+    }
+
+    /// Writes the snapshot to a file in the temp directory named after `name`.
+    pub fn write(&self, name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("adexplorersnapshot-{}.dat", name));
+        std::fs::write(&path, self.build()).unwrap();
+        path
+    }
+}
+
+fn object_bytes(values: &[(usize, Value)]) -> Vec<u8> {
+    // Values of the same property form one attribute
+    let mut attributes: Vec<(usize, Vec<&Value>)> = Vec::new();
+    for (index, value) in values {
+        match attributes.iter_mut().find(|(i, _)| i == index) {
+            Some((_, values)) => values.push(value),
+            None => attributes.push((*index, vec![value])),
+        }
+    }
+
+    let encoded: Vec<Vec<u8>> = attributes
+        .iter()
+        .map(|(_, values)| attribute_bytes(values))
+        .collect();
+    let table_size = attributes.len();
+    let mut offset = 8 + table_size * 8;
+
+    let mut bytes = Vec::new();
+    let obj_size = offset + encoded.iter().map(Vec::len).sum::<usize>();
+    bytes.extend_from_slice(&(obj_size as u32).to_le_bytes());
+    bytes.extend_from_slice(&(table_size as u32).to_le_bytes());
+    for ((index, _), data) in attributes.iter().zip(&encoded) {
+        bytes.extend_from_slice(&(*index as u32).to_le_bytes());
+        bytes.extend_from_slice(&(offset as i32).to_le_bytes());
+        offset += data.len();
+    }
+    for data in encoded {
+        bytes.extend(data);
+    }
+    bytes
+}
+
+fn attribute_bytes(values: &[&Value]) -> Vec<u8> {
+    let mut bytes = (values.len() as u32).to_le_bytes().to_vec();
+    match values[0] {
+        Value::String(_) => {
+            // Offsets from the attribute start, then the terminated strings
+            let strings: Vec<Vec<u8>> = values
+                .iter()
+                .map(|value| match value {
+                    Value::String(s) => utf16(s),
+                    _ => panic!("mixed value types"),
+                })
+                .collect();
+            let mut offset = 4 + 4 * strings.len();
+            for s in &strings {
+                bytes.extend_from_slice(&(offset as u32).to_le_bytes());
+                offset += s.len();
+            }
+            for s in strings {
+                bytes.extend(s);
+            }
+        }
+        Value::OctetString(_) => {
+            let blobs: Vec<&Vec<u8>> = values
+                .iter()
+                .map(|value| match value {
+                    Value::OctetString(b) => b,
+                    _ => panic!("mixed value types"),
+                })
+                .collect();
+            for blob in &blobs {
+                bytes.extend_from_slice(&(blob.len() as u32).to_le_bytes());
+            }
+            for blob in blobs {
+                bytes.extend(blob);
+            }
+        }
+        Value::Integer(_) => {
+            for value in values {
+                match value {
+                    Value::Integer(i) => bytes.extend_from_slice(&i.to_le_bytes()),
+                    _ => panic!("mixed value types"),
+                }
+            }
+        }
+        Value::LargeInteger(_) => {
+            for value in values {
+                match value {
+                    Value::LargeInteger(i) => bytes.extend_from_slice(&i.to_le_bytes()),
+                    _ => panic!("mixed value types"),
+                }
+            }
+        }
+    }
+    bytes
+}
+
+/// Null-terminated UTF-16LE.
+fn utf16(value: &str) -> Vec<u8> {
+    format!("{}\0", value)
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+/// Length-prefixed, null-terminated UTF-16LE.
+fn wstring(value: &str) -> Vec<u8> {
+    let chars = utf16(value);
+    let mut bytes = (chars.len() as u32).to_le_bytes().to_vec();
+    bytes.extend(chars);
+    bytes
+}
+
+/// UTF-16LE padded with nulls to `chars` characters.
+fn fixed_wstring(value: &str, chars: usize) -> Vec<u8> {
+    let mut bytes: Vec<u8> = value.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    bytes.resize(chars * 2, 0);
+    bytes
+}
@@ -0,0 +1,164 @@
+mod common;
+
+use adexplorersnapshot::output::bloodhound::{ComputersOutput, DomainsOutput, UsersOutput};
+use adexplorersnapshot::parser::{ADExplorerSnapshot, Snapshot, SnapshotReader};
+use common::{guid, sid, string, SnapshotBuilder, Value, DOMAIN_DN, DOMAIN_SID, SCHEMA_DN};
+use serde_json::json;
+use std::io::{Cursor, ErrorKind};
+
+const DOMAIN_GUID: &str = "6e4b8c52-1d9a-4f3b-9a6e-2f1c0b7d5a11";
+// 2024-03-01T12:30:45Z as a FILETIME
+const CAPTURED: u64 = (1_709_296_245 + 11_644_473_600) * 10_000_000;
+
+fn fixture() -> SnapshotBuilder {
+    SnapshotBuilder::new("DC01.example.com", CAPTURED)
+        .class("person", "Person")
+        .class("computer", "Computer")
+        .object(vec![
+            ("objectClass", string("top")),
+            ("objectClass", string("domain")),
+            ("objectClass", string("domainDNS")),
+            ("distinguishedName", string(DOMAIN_DN)),
+            ("name", string("EXAMPLE")),
+            ("objectSid", sid(DOMAIN_SID)),
+            ("objectGUID", guid(DOMAIN_GUID)),
+            // 2024-03-01T00:00:00Z
+            (
+                "creationTime",
+                Value::LargeInteger(1_709_251_200 * 10_000_000 + 116_444_736_000_000_000),
+            ),
+        ])
+        .object(vec![
+            ("objectClass", string("top")),
+            ("objectClass", string("person")),
+            ("objectClass", string("organizationalPerson")),
+            ("objectClass", string("user")),
+            (
+                "distinguishedName",
+                string(&format!("CN=alice,CN=Users,{}", DOMAIN_DN)),
+            ),
+            ("name", string("alice")),
+            (
+                "objectCategory",
+                string(&format!("CN=Person,{}", SCHEMA_DN)),
+            ),
+            ("sAMAccountName", string("alice")),
+            ("sAMAccountType", Value::Integer(0x30000000)),
+            ("userAccountControl", Value::Integer(0x200)),
+            ("objectSid", sid(&format!("{}-1100", DOMAIN_SID))),
+            ("servicePrincipalName", string("HTTP/web.example.com")),
+        ])
+        .object(vec![
+            ("objectClass", string("top")),
+            ("objectClass", string("computer")),
+            (
+                "distinguishedName",
+                string(&format!("CN=WS01,CN=Computers,{}", DOMAIN_DN)),
+            ),
+            ("name", string("WS01")),
+            (
+                "objectCategory",
+                string(&format!("CN=Computer,{}", SCHEMA_DN)),
+            ),
+            ("sAMAccountName", string("WS01$")),
+            ("sAMAccountType", Value::Integer(0x30000001)),
+            ("userAccountControl", Value::Integer(0x1000)),
+            ("objectSid", sid(&format!("{}-1101", DOMAIN_SID))),
+            ("dNSHostName", string("ws01.example.com")),
+        ])
+}
+
+fn load(name: &str) -> ADExplorerSnapshot {
+    let path = fixture().write(name);
+    let snapshot = ADExplorerSnapshot::snapshot_from_file(&path);
+    std::fs::remove_file(&path).unwrap();
+    snapshot.unwrap()
+}
+
+#[test]
+fn test_header() {
+    let snapshot = load("pipeline-header");
+
+    assert_eq!(snapshot.server(), "DC01.example.com");
+    assert_eq!(snapshot.captured_at().timestamp(), 1_709_296_245);
+    assert_eq!(snapshot.snapshot.objects.len(), 3);
+}
+
+#[test]
+fn test_users_output() {
+    let snapshot = load("pipeline-users");
+    let users = serde_json::to_value(UsersOutput::new(&snapshot)).unwrap();
+
+    assert_eq!(users["meta"]["type"], "users");
+    assert_eq!(users["meta"]["count"], 1);
+    let alice = &users["data"][0];
+    assert_eq!(alice["ObjectIdentifier"], format!("{}-1100", DOMAIN_SID));
+    assert_eq!(alice["Properties"]["name"], "ALICE@EXAMPLE.COM");
+    assert_eq!(alice["Properties"]["enabled"], true);
+    assert_eq!(alice["Properties"]["hasspn"], true);
+    assert_eq!(alice["Properties"]["lastlogon"], -1);
+}
+
+#[test]
+fn test_computers_output() {
+    let snapshot = load("pipeline-computers");
+    let computers = serde_json::to_value(ComputersOutput::new(&snapshot)).unwrap();
+
+    assert_eq!(computers["meta"]["count"], 1);
+    let ws01 = &computers["data"][0];
+    assert_eq!(ws01["ObjectIdentifier"], format!("{}-1101", DOMAIN_SID));
+    assert_eq!(ws01["Properties"]["name"], "WS01.EXAMPLE.COM");
+}
+
+#[test]
+fn test_domain_when_created() {
+    let snapshot = load("pipeline-domains");
+    let domains = serde_json::to_value(DomainsOutput::new(&snapshot)).unwrap();
+
+    assert_eq!(
+        domains["data"][0]["ObjectIdentifier"],
+        DOMAIN_GUID.to_uppercase()
+    );
+    assert_eq!(
+        domains["data"][0]["Properties"]["whencreated"],
+        json!(1_709_251_200)
+    );
+}
+
+#[test]
+fn test_dump_uses_schema_names() {
+    let snapshot = load("pipeline-dump");
+    let dump = snapshot.dump_object("alice").unwrap();
+
+    assert_eq!(dump["sAMAccountName"], "alice");
+    assert_eq!(dump["objectSid"], format!("{}-1100", DOMAIN_SID));
+}
+
+#[test]
+fn test_reader_matches_file() {
+    let bytes = fixture().build();
+    let from_memory = Snapshot::snapshot_from_memory(&bytes).unwrap();
+    let from_reader = Snapshot::snapshot_from_reader(Cursor::new(&bytes)).unwrap();
+
+    assert_eq!(from_memory.to_debug_json(), from_reader.to_debug_json());
+}
+
+#[test]
+fn test_lenient_needs_attribute_definitions() {
+    let bytes = fixture().build();
+    // Cut partway through the objects, as an interrupted save leaves the file
+    let truncated = &bytes[..0x43e + 200];
+
+    let error = SnapshotReader::new(truncated).err().unwrap();
+    assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+    assert!(error.to_string().contains("attribute definitions"));
+
+    // The intact file still parses leniently
+    let snapshot = SnapshotReader::new(&bytes)
+        .unwrap()
+        .lenient()
+        .into_snapshot()
+        .unwrap();
+    assert_eq!(snapshot.objects.len(), 3);
+    assert!(snapshot.errors.is_empty());
+}